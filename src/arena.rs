@@ -0,0 +1,299 @@
+use alloc::alloc as heap;
+use core::{
+	alloc::{GlobalAlloc, Layout},
+	marker, mem, ops,
+	pin::Pin,
+	ptr::{self, NonNull},
+	task,
+};
+
+/// Source of a runtime's internal allocations, see [`Builder::allocator`](crate::rt::Builder::allocator)
+#[derive(Clone, Copy)]
+pub(crate) struct Allocator(Option<&'static (dyn GlobalAlloc + Sync)>);
+
+impl Allocator {
+	/// The global allocator, for runtimes not given one and state created outside of a runtime
+	pub(crate) const GLOBAL: Self = Allocator(None);
+
+	pub(crate) fn new(allocator: &'static (dyn GlobalAlloc + Sync)) -> Self {
+		Allocator(Some(allocator))
+	}
+
+	pub(crate) fn allocate(self, layout: Layout) -> NonNull<u8> {
+		// zero sized values need no memory, just an aligned address
+		if layout.size() == 0 {
+			return NonNull::new(ptr::without_provenance_mut(layout.align())).unwrap();
+		}
+
+		let ptr = match self.0 {
+			Some(allocator) => unsafe { allocator.alloc(layout) },
+			None => unsafe { heap::alloc(layout) },
+		};
+
+		NonNull::new(ptr).unwrap_or_else(|| heap::handle_alloc_error(layout))
+	}
+
+	/// Safety: `block` must have been returned by [`allocate`](Allocator::allocate) of the same allocator, with the same `layout`
+	pub(crate) unsafe fn deallocate(self, block: NonNull<u8>, layout: Layout) {
+		if layout.size() == 0 {
+			return;
+		}
+
+		match self.0 {
+			Some(allocator) => unsafe { allocator.dealloc(block.as_ptr(), layout) },
+			None => unsafe { heap::dealloc(block.as_ptr(), layout) },
+		}
+	}
+
+	fn put<T>(self, value: T) -> NonNull<T> {
+		let ptr = self.allocate(Layout::new::<T>()).cast::<T>();
+		unsafe { ptr.write(value) };
+		ptr
+	}
+}
+
+/// Like `Box`, but freed through the [`Allocator`] it came from
+pub(crate) struct Boxed<T: ?Sized> {
+	ptr: NonNull<T>,
+	allocator: Allocator,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Boxed<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for Boxed<T> {}
+
+impl<T: ?Sized> Unpin for Boxed<T> {}
+
+impl<T> Boxed<T> {
+	pub(crate) fn new_in(value: T, allocator: Allocator) -> Self {
+		Boxed { ptr: allocator.put(value), allocator }
+	}
+
+	pub(crate) fn into_inner(this: Self) -> T {
+		let this = mem::ManuallyDrop::new(this);
+
+		unsafe {
+			let value = this.ptr.read();
+			this.allocator.deallocate(this.ptr.cast(), Layout::new::<T>());
+			value
+		}
+	}
+}
+
+impl<T: ?Sized> Boxed<T> {
+	/// Converts the value into a trait object, with `unsize` coercing the pointer, e.g. `|ptr| ptr as _`.
+	///
+	/// Safety: `unsize` must return the pointer it's given, with only its metadata added
+	pub(crate) unsafe fn unsize<U: ?Sized>(this: Self, unsize: impl FnOnce(*mut T) -> *mut U) -> Boxed<U> {
+		let this = mem::ManuallyDrop::new(this);
+		Boxed { ptr: unsafe { NonNull::new_unchecked(unsize(this.ptr.as_ptr())) }, allocator: this.allocator }
+	}
+
+	pub(crate) fn into_pin(this: Self) -> Pin<Self> {
+		// the value is never moved out of its allocation while boxed
+		unsafe { Pin::new_unchecked(this) }
+	}
+
+	/// Hands over the allocation, e.g. to an intrusive list. Freed by passing it back to [`from_raw`](Boxed::from_raw)
+	pub(crate) fn into_raw(this: Self) -> NonNull<T> {
+		mem::ManuallyDrop::new(this).ptr
+	}
+
+	/// Safety: `ptr` must come from [`into_raw`](Boxed::into_raw), of a value boxed in `allocator`
+	pub(crate) unsafe fn from_raw(ptr: NonNull<T>, allocator: Allocator) -> Self {
+		Boxed { ptr, allocator }
+	}
+}
+
+impl<T: ?Sized> ops::Deref for Boxed<T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		unsafe { self.ptr.as_ref() }
+	}
+}
+
+impl<T: ?Sized> ops::DerefMut for Boxed<T> {
+	fn deref_mut(&mut self) -> &mut T {
+		unsafe { self.ptr.as_mut() }
+	}
+}
+
+impl<T: ?Sized> Drop for Boxed<T> {
+	fn drop(&mut self) {
+		let layout = Layout::for_value(unsafe { self.ptr.as_ref() });
+
+		unsafe {
+			self.ptr.as_ptr().drop_in_place();
+			self.allocator.deallocate(self.ptr.cast(), layout);
+		}
+	}
+}
+
+/// Like `Arc`, but freed through the [`Allocator`] it came from. Loom's `Arc` stands in under `--cfg loom`, so models still report leaks
+#[cfg(not(loom))]
+pub(crate) struct Counted<T> {
+	ptr: NonNull<CountedInner<T>>,
+	_marker: marker::PhantomData<CountedInner<T>>,
+}
+
+#[cfg(not(loom))]
+struct CountedInner<T> {
+	refs: core::sync::atomic::AtomicUsize,
+	allocator: Allocator,
+	value: T,
+}
+
+#[cfg(loom)]
+pub(crate) struct Counted<T>(loom::sync::Arc<T>);
+
+unsafe impl<T: Send + Sync> Send for Counted<T> {}
+unsafe impl<T: Send + Sync> Sync for Counted<T> {}
+
+#[cfg(not(loom))]
+impl<T> Counted<T> {
+	pub(crate) fn new_in(value: T, allocator: Allocator) -> Self {
+		let refs = core::sync::atomic::AtomicUsize::new(1);
+		Counted { ptr: allocator.put(CountedInner { refs, allocator, value }), _marker: marker::PhantomData }
+	}
+
+	fn inner(&self) -> &CountedInner<T> {
+		unsafe { self.ptr.as_ref() }
+	}
+
+	pub(crate) fn ptr_eq(this: &Self, other: &Self) -> bool {
+		this.ptr == other.ptr
+	}
+
+	/// Pointer to the value, keeping its reference alive until passed back to [`from_raw`](Counted::from_raw)
+	pub(crate) fn into_raw(this: Self) -> *const T {
+		let this = mem::ManuallyDrop::new(this);
+		unsafe { &raw const (*this.ptr.as_ptr()).value }
+	}
+
+	/// Safety: `ptr` must come from [`into_raw`](Counted::into_raw), each call takes over one reference
+	pub(crate) unsafe fn from_raw(ptr: *const T) -> Self {
+		let inner = unsafe { ptr.byte_sub(mem::offset_of!(CountedInner<T>, value)) } as *mut CountedInner<T>;
+		Counted { ptr: unsafe { NonNull::new_unchecked(inner) }, _marker: marker::PhantomData }
+	}
+}
+
+#[cfg(loom)]
+impl<T> Counted<T> {
+	pub(crate) fn new_in(value: T, _: Allocator) -> Self {
+		Counted(loom::sync::Arc::new(value))
+	}
+
+	pub(crate) fn ptr_eq(this: &Self, other: &Self) -> bool {
+		loom::sync::Arc::ptr_eq(&this.0, &other.0)
+	}
+
+	pub(crate) fn into_raw(this: Self) -> *const T {
+		loom::sync::Arc::into_raw(this.0)
+	}
+
+	pub(crate) unsafe fn from_raw(ptr: *const T) -> Self {
+		Counted(unsafe { loom::sync::Arc::from_raw(ptr) })
+	}
+}
+
+impl<T> Counted<T> {
+	/// Safety: as for [`from_raw`](Counted::from_raw), though the reference behind `ptr` is left in place
+	pub(crate) unsafe fn increment_strong_count(ptr: *const T) {
+		let this = mem::ManuallyDrop::new(unsafe { Self::from_raw(ptr) });
+		mem::forget(Counted::clone(&this));
+	}
+
+	/// Safety: as for [`from_raw`](Counted::from_raw)
+	pub(crate) unsafe fn decrement_strong_count(ptr: *const T) {
+		drop(unsafe { Self::from_raw(ptr) })
+	}
+}
+
+impl<T> Clone for Counted<T> {
+	#[cfg(not(loom))]
+	fn clone(&self) -> Self {
+		self.inner().refs.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+		Counted { ptr: self.ptr, _marker: marker::PhantomData }
+	}
+
+	#[cfg(loom)]
+	fn clone(&self) -> Self {
+		Counted(self.0.clone())
+	}
+}
+
+impl<T> ops::Deref for Counted<T> {
+	type Target = T;
+
+	#[cfg(not(loom))]
+	fn deref(&self) -> &T {
+		&self.inner().value
+	}
+
+	#[cfg(loom)]
+	fn deref(&self) -> &T {
+		&self.0
+	}
+}
+
+#[cfg(not(loom))]
+impl<T> Drop for Counted<T> {
+	fn drop(&mut self) {
+		use core::sync::atomic::{Ordering, fence};
+
+		if self.inner().refs.fetch_sub(1, Ordering::Release) != 1 {
+			return;
+		}
+
+		// pairs with the release above, every other reference's use happens before the value is dropped
+		fence(Ordering::Acquire);
+		let allocator = self.inner().allocator;
+
+		unsafe {
+			self.ptr.as_ptr().drop_in_place();
+			allocator.deallocate(self.ptr.cast(), Layout::new::<CountedInner<T>>());
+		}
+	}
+}
+
+/// State behind a waker the runtime creates, kept in a [`Counted`] allocation. `alloc::task::Wake` only works with `Arc`s
+pub(crate) trait Wake: Send + Sync + Sized + 'static {
+	fn wake_by_ref(&self);
+}
+
+pub(crate) fn waker<W: Wake>(data: Counted<W>) -> task::Waker {
+	unsafe { task::Waker::new(Counted::into_raw(data) as *const (), &WakerVtable::<W>::VTABLE) }
+}
+
+/// State behind `waker`, if it was created by [`waker`] from a `W`
+#[cfg(feature = "std")]
+pub(crate) fn waker_data<W: Wake>(waker: &task::Waker) -> Option<&W> {
+	(*waker.vtable() == WakerVtable::<W>::VTABLE).then(|| unsafe { &*(waker.data() as *const W) })
+}
+
+struct WakerVtable<W>(marker::PhantomData<W>);
+
+impl<W: Wake> WakerVtable<W> {
+	const VTABLE: task::RawWakerVTable = task::RawWakerVTable::new(Self::clone, Self::wake, Self::wake_by_ref, Self::drop);
+
+	// quartet of waker methods, cloning and dropping only touch the reference count
+	unsafe fn clone(data: *const ()) -> task::RawWaker {
+		unsafe { Counted::increment_strong_count(data as *const W) };
+		task::RawWaker::new(data, &Self::VTABLE)
+	}
+
+	unsafe fn wake(data: *const ()) {
+		unsafe {
+			Self::wake_by_ref(data);
+			Self::drop(data);
+		}
+	}
+
+	unsafe fn wake_by_ref(data: *const ()) {
+		unsafe { &*(data as *const W) }.wake_by_ref()
+	}
+
+	unsafe fn drop(data: *const ()) {
+		unsafe { Counted::decrement_strong_count(data as *const W) }
+	}
+}
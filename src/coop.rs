@@ -0,0 +1,54 @@
+use core::task;
+
+/// Readiness events a task may consume in a single poll, before leaf futures force it to yield
+#[cfg(feature = "std")]
+const BUDGET: usize = 128;
+
+#[cfg(feature = "std")]
+std::thread_local! {
+	/// Budget left for the task currently being polled, `None` outside of the runtime
+	static REMAINING: core::cell::Cell<Option<usize>> = const { core::cell::Cell::new(None) };
+}
+
+/// Runs `poll` with a fresh budget, restoring the previous one afterwards
+pub(crate) fn with_budget<R>(poll: impl FnOnce() -> R) -> R {
+	#[cfg(feature = "std")]
+	{
+		let previous = REMAINING.replace(Some(BUDGET));
+		let res = poll();
+		REMAINING.set(previous);
+
+		res
+	}
+
+	// without thread locals to track it, tasks are never forced to yield
+	#[cfg(not(feature = "std"))]
+	poll()
+}
+
+/// Polls a leaf future, charging the current task's budget if it's ready.
+///
+/// Once the budget is exhausted, the task is rescheduled and `Pending` is returned without polling, letting other tasks run
+pub fn poll_budgeted<T>(cx: &mut task::Context<'_>, poll: impl FnOnce(&mut task::Context<'_>) -> task::Poll<T>) -> task::Poll<T> {
+	#[cfg(feature = "std")]
+	if REMAINING.get() == Some(0) {
+		cx.waker().wake_by_ref();
+		return task::Poll::Pending;
+	}
+
+	let res = poll(cx);
+
+	#[cfg(feature = "std")]
+	if res.is_ready()
+		&& let Some(remaining) = REMAINING.get()
+	{
+		REMAINING.set(Some(remaining - 1));
+	}
+
+	res
+}
+
+/// Charges the current task's budget, yielding to other tasks once it's exhausted
+pub async fn consume_budget() {
+	core::future::poll_fn(|cx| poll_budgeted(cx, |_| task::Poll::Ready(()))).await
+}
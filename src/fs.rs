@@ -0,0 +1,150 @@
+use crate::io::{Async, AsyncRead};
+use std::{
+	collections,
+	ffi::{CString, c_char, c_int},
+	fs, io,
+	os::{fd::FromRawFd, unix::ffi::OsStrExt},
+	path,
+	pin::Pin,
+	task,
+};
+
+unsafe extern "C" {
+	fn inotify_init1(flags: c_int) -> c_int;
+	fn inotify_add_watch(fd: c_int, pathname: *const c_char, mask: u32) -> c_int;
+}
+
+const IN_NONBLOCK: c_int = 0o4000;
+const IN_CLOEXEC: c_int = 0o2000000;
+
+const IN_MODIFY: u32 = 0x2;
+const IN_ATTRIB: u32 = 0x4;
+const IN_MOVED_FROM: u32 = 0x40;
+const IN_MOVED_TO: u32 = 0x80;
+const IN_CREATE: u32 = 0x100;
+const IN_DELETE: u32 = 0x200;
+const IN_DELETE_SELF: u32 = 0x400;
+const IN_MOVE_SELF: u32 = 0x800;
+const IN_Q_OVERFLOW: u32 = 0x4000;
+const IN_IGNORED: u32 = 0x8000;
+
+const WATCH_MASK: u32 = IN_MODIFY | IN_ATTRIB | IN_MOVED_FROM | IN_MOVED_TO | IN_CREATE | IN_DELETE | IN_DELETE_SELF | IN_MOVE_SELF;
+
+/// size of the fixed `inotify_event` header, the name follows it
+const EVENT_HEADER: usize = 16;
+
+/// What happened to a watched path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+	/// A file was created or moved into the watched directory
+	Created,
+	/// Contents or metadata of a file changed
+	Modified,
+	/// A file was deleted or moved away
+	Removed,
+	/// Events were dropped by the OS, watched paths should be rescanned
+	Overflow,
+}
+
+/// A single filesystem change, reported by [`Watch`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+	/// The path affected, either the watched path itself or an entry within it
+	pub path: path::PathBuf,
+	/// What happened to `path`
+	pub kind: EventKind,
+}
+
+/// Starts watching `path` for changes. Directories report changes to their direct entries
+pub fn watch<P: AsRef<path::Path>>(path: P) -> io::Result<Watch> {
+	let path = path.as_ref().to_path_buf();
+	let c_path = CString::new(path.as_os_str().as_bytes())?;
+
+	let fd = unsafe { inotify_init1(IN_NONBLOCK | IN_CLOEXEC) };
+	if fd < 0 {
+		return Err(io::Error::last_os_error());
+	}
+
+	// take ownership immediately, so the descriptor is closed on error
+	let file = unsafe { fs::File::from_raw_fd(fd) };
+
+	if unsafe { inotify_add_watch(fd, c_path.as_ptr(), WATCH_MASK) } < 0 {
+		return Err(io::Error::last_os_error());
+	}
+
+	Ok(Watch { inner: Async::new(file), path, buf: vec![0; 4096], pending: collections::VecDeque::new(), done: false })
+}
+
+/// Stream of [`Event`]s for a watched path, driven by the runtime's reactor. Ends when the watched path is removed
+pub struct Watch {
+	inner: Async<fs::File>,
+	path: path::PathBuf,
+	buf: Vec<u8>,
+	pending: collections::VecDeque<Event>,
+	done: bool,
+}
+
+impl Watch {
+	/// The path being watched
+	pub fn path(&self) -> &path::Path {
+		&self.path
+	}
+
+	fn parse(&mut self, len: usize) {
+		let mut offset = 0;
+
+		while offset + EVENT_HEADER <= len {
+			let field = |at: usize| u32::from_ne_bytes(self.buf[offset + at..offset + at + 4].try_into().unwrap());
+			let (mask, name_len) = (field(4), field(12) as usize);
+
+			let name = &self.buf[offset + EVENT_HEADER..offset + EVENT_HEADER + name_len];
+			let name = &name[..name.iter().position(|b| *b == 0).unwrap_or(name.len())];
+			offset += EVENT_HEADER + name_len;
+
+			let path = match name.is_empty() {
+				true => self.path.clone(),
+				false => self.path.join(std::ffi::OsStr::from_bytes(name)),
+			};
+
+			let kind = if mask & IN_IGNORED != 0 {
+				// the watch was removed by the kernel, nothing else will be reported
+				self.done = true;
+				continue;
+			} else if mask & IN_Q_OVERFLOW != 0 {
+				EventKind::Overflow
+			} else if mask & (IN_CREATE | IN_MOVED_TO) != 0 {
+				EventKind::Created
+			} else if mask & (IN_DELETE | IN_MOVED_FROM | IN_DELETE_SELF | IN_MOVE_SELF) != 0 {
+				EventKind::Removed
+			} else {
+				EventKind::Modified
+			};
+
+			self.pending.push_back(Event { path, kind });
+		}
+	}
+}
+
+impl futures_core::Stream for Watch {
+	type Item = io::Result<Event>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Option<Self::Item>> {
+		let this = self.get_mut();
+
+		loop {
+			if let Some(event) = this.pending.pop_front() {
+				return task::Poll::Ready(Some(Ok(event)));
+			}
+
+			if this.done {
+				return task::Poll::Ready(None);
+			}
+
+			let mut inner = &this.inner;
+			match task::ready!(Pin::new(&mut inner).poll_read(cx, &mut this.buf)) {
+				Ok(len) => this.parse(len),
+				Err(e) => return task::Poll::Ready(Some(Err(e))),
+			}
+		}
+	}
+}
@@ -0,0 +1,454 @@
+use crate::coop;
+use std::{
+	cell,
+	ffi::{c_int, c_short},
+	future, io,
+	io::{Read, Write},
+	os::{fd::AsRawFd, fd::RawFd, unix::net::UnixStream},
+	pin::Pin,
+	sync::{self, mpsc},
+	task, thread,
+};
+
+mod buffered;
+/// Framing of byte streams into messages, like `tokio-util`'s `Framed`
+pub mod codec;
+mod net;
+#[cfg(feature = "timers")]
+mod timeout;
+mod util;
+
+pub use buffered::{BufReader, BufWriter, Lines, lines};
+pub use futures_io::{AsyncBufRead, AsyncRead, AsyncWrite};
+#[cfg(all(feature = "timers", any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+pub use net::ATTEMPT_DELAY;
+pub use net::{Incoming, TcpListener, TcpStream};
+#[cfg(feature = "timers")]
+pub use timeout::{TimeoutReader, TimeoutWriter, read_timeout};
+pub use util::{ReadHalf, WriteHalf, copy, copy_bidirectional, read_exact, read_line, read_until, split, write_all};
+
+thread_local! {
+	/// Used by [`Async`] to register new sources. Set up by runtimes or [`init`], the reactor thread itself is spawned by the first source
+	static REACTOR: cell::RefCell<Option<Reactor>> = const { cell::RefCell::new(None) };
+}
+
+#[repr(C)]
+struct PollFd {
+	fd: c_int,
+	events: c_short,
+	revents: c_short,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+type NFds = std::ffi::c_ulong;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+type NFds = std::ffi::c_uint;
+
+const POLLIN: c_short = 0x1;
+const POLLOUT: c_short = 0x4;
+const POLLERR: c_short = 0x8;
+const POLLHUP: c_short = 0x10;
+const POLLNVAL: c_short = 0x20;
+
+unsafe extern "C" {
+	fn poll(fds: *mut PollFd, nfds: NFds, timeout: c_int) -> c_int;
+}
+
+const READ: usize = 0;
+const WRITE: usize = 1;
+
+/// This thread's reactor, owned by the runtime thread
+struct Reactor {
+	/// `None` until the first [`Async`] is created
+	thread: Option<ReactorThread>,
+	/// runtimes alive that set the reactor up, `None` if set up through [`init`], which keeps it alive until [`shutdown`]
+	runtimes: Option<usize>,
+}
+
+/// Handle to a spawned reactor thread
+struct ReactorThread {
+	sender: mpsc::Sender<sync::Weak<Source>>,
+	notifier: UnixStream,
+	handle: thread::JoinHandle<()>,
+}
+
+impl Reactor {
+	/// Interrupts the reactor's `poll`, making it pick up new sources and interests
+	fn notify(notifier: &UnixStream) {
+		// a full pipe already guarantees a pending wake-up
+		let _ = (&*notifier).write(&[1]);
+	}
+
+	/// Handle to the reactor thread, spawning it if needed
+	fn start(&mut self) -> &ReactorThread {
+		self.thread.get_or_insert_with(spawn_reactor)
+	}
+}
+
+impl Drop for Reactor {
+	fn drop(&mut self) {
+		// closing the notifier ends the reactor's loop
+		if let Some(ReactorThread { sender, notifier, handle }) = self.thread.take() {
+			drop((sender, notifier));
+			let _ = handle.join();
+		}
+	}
+}
+
+/// Sets up this thread's reactor, a dedicated thread waiting on registered file descriptors using `poll(2)`. The thread is only spawned once the first [`Async`] is created.
+///
+/// Runtimes set the reactor up themselves, tearing it down once the last of them is dropped. Set up explicitly, it lives until [`shutdown`]
+pub fn init() {
+	REACTOR.with_borrow_mut(|reactor| match reactor {
+		Some(reactor) => reactor.runtimes = None,
+		None => *reactor = Some(Reactor { thread: None, runtimes: None }),
+	});
+}
+
+/// Sets up this thread's reactor for a runtime
+pub(crate) fn acquire() {
+	REACTOR.with_borrow_mut(|reactor| match reactor {
+		Some(Reactor { runtimes: Some(runtimes), .. }) => *runtimes += 1,
+		Some(_) => (),
+		None => *reactor = Some(Reactor { thread: None, runtimes: Some(1) }),
+	});
+}
+
+/// Releases the reactor acquired by a runtime being dropped
+pub(crate) fn release() {
+	// dropped outside the borrow, stopping the reactor thread
+	let _teardown = REACTOR.try_with(|r| {
+		let mut r = r.try_borrow_mut().ok()?;
+		let runtimes = r.as_mut()?.runtimes.as_mut()?;
+		*runtimes -= 1;
+
+		match *runtimes {
+			0 => r.take(),
+			_ => None,
+		}
+	});
+}
+
+/// Tears down this thread's reactor, stopping its thread. Registered sources are never woken again, and new ones panic until the reactor is set up again
+pub fn shutdown() {
+	let reactor = REACTOR.with_borrow_mut(Option::take);
+	drop(reactor);
+}
+
+/// This thread's reactor thread was spawned
+#[cfg(test)]
+pub(crate) fn is_running() -> bool {
+	REACTOR.with_borrow(|r| r.as_ref().is_some_and(|r| r.thread.is_some()))
+}
+
+fn spawn_reactor() -> ReactorThread {
+	let (sender, receiver) = mpsc::channel::<sync::Weak<Source>>();
+	let (notifier, listener) = UnixStream::pair().expect("Unable to create reactor notifier");
+
+	notifier.set_nonblocking(true).expect("Unable to configure reactor notifier");
+	listener.set_nonblocking(true).expect("Unable to configure reactor notifier");
+
+	let handle = thread::spawn(move || {
+		let mut sources = Vec::<sync::Weak<Source>>::new();
+		let mut fds = Vec::<PollFd>::new();
+		let mut live = Vec::<sync::Arc<Source>>::new();
+
+		loop {
+			// insert new sources, and drop sources whose owners were dropped
+			sources.extend(receiver.try_iter());
+			sources.retain(|s| s.strong_count() > 0);
+
+			// the notifier is always polled first, everything else only if a task is interested
+			fds.clear();
+			live.clear();
+			fds.push(PollFd { fd: listener.as_raw_fd(), events: POLLIN, revents: 0 });
+
+			for source in sources.iter().filter_map(sync::Weak::upgrade) {
+				let events = source.interests();
+
+				if events != 0 {
+					fds.push(PollFd { fd: source.fd, events, revents: 0 });
+					live.push(source);
+				}
+			}
+
+			if unsafe { poll(fds.as_mut_ptr(), fds.len() as NFds, -1) } < 0 {
+				match io::Error::last_os_error().kind() {
+					io::ErrorKind::Interrupted => continue,
+					_ => panic!("Reactor failed to poll sources: {}", io::Error::last_os_error()),
+				}
+			}
+
+			// drain notifier, it reaches its end once the runtime thread tears the reactor down
+			let mut buf = [0; 64];
+			loop {
+				match (&listener).read(&mut buf) {
+					Ok(0) => return,
+					Ok(_) => continue,
+					Err(_) => break,
+				}
+			}
+
+			for (fd, source) in fds[1..].iter().zip(live.drain(..)) {
+				let closed = fd.revents & (POLLERR | POLLHUP | POLLNVAL) != 0;
+
+				if closed || fd.revents & POLLIN != 0 {
+					source.wake(READ);
+				}
+
+				if closed || fd.revents & POLLOUT != 0 {
+					source.wake(WRITE);
+				}
+			}
+		}
+	});
+
+	ReactorThread { sender, notifier, handle }
+}
+
+/// Interest of a single task in one direction of a [`Source`]
+#[derive(Default)]
+struct Direction {
+	waker: Option<task::Waker>,
+	/// incremented every time the reactor reports readiness
+	tick: usize,
+	/// value of `tick` when interest was registered
+	armed: Option<usize>,
+}
+
+/// A file descriptor registered with the reactor
+struct Source {
+	fd: RawFd,
+	directions: sync::Mutex<[Direction; 2]>,
+}
+
+impl Source {
+	fn interests(&self) -> c_short {
+		let directions = self.directions.lock().unwrap();
+		let mut events = 0;
+
+		if directions[READ].waker.is_some() {
+			events |= POLLIN;
+		}
+
+		if directions[WRITE].waker.is_some() {
+			events |= POLLOUT;
+		}
+
+		events
+	}
+
+	fn wake(&self, dir: usize) {
+		let waker = {
+			let mut directions = self.directions.lock().unwrap();
+			let direction = &mut directions[dir];
+
+			direction.tick = direction.tick.wrapping_add(1);
+			direction.waker.take()
+		};
+
+		if let Some(waker) = waker {
+			waker.wake()
+		}
+	}
+
+	fn poll_ready(&self, dir: usize, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		let mut directions = self.directions.lock().unwrap();
+		let direction = &mut directions[dir];
+
+		match direction.armed {
+			// the reactor has reported readiness since we registered interest
+			Some(armed) if armed != direction.tick => {
+				direction.armed = None;
+				return task::Poll::Ready(Ok(()));
+			}
+			Some(_) => {}
+			None => direction.armed = Some(direction.tick),
+		}
+
+		let notify = direction.waker.as_ref().map(|w| !w.will_wake(cx.waker())).unwrap_or(true);
+		direction.waker = Some(cx.waker().clone());
+		drop(directions);
+
+		// the reactor has to rebuild its interest set
+		if notify {
+			REACTOR.with_borrow_mut(|r| match r {
+				Some(reactor) => Reactor::notify(&reactor.start().notifier),
+				None => panic!("Reactor has not been initialized"),
+			});
+		}
+
+		task::Poll::Pending
+	}
+}
+
+/// Async adapter for a non-blocking IO object, registered with the runtime's reactor.
+///
+/// The wrapped object must already be in non-blocking mode, otherwise reads and writes will block the runtime.
+pub struct Async<T> {
+	source: sync::Arc<Source>,
+	io: T,
+}
+
+impl<T: AsRawFd> Async<T> {
+	/// Registers `io` with the reactor running alongside the current runtime
+	pub fn new(io: T) -> Self {
+		let source = sync::Arc::new(Source { fd: io.as_raw_fd(), directions: Default::default() });
+
+		REACTOR.with_borrow_mut(|r| match r {
+			Some(reactor) => {
+				let thread = reactor.start();
+				thread.sender.send(sync::Arc::downgrade(&source)).unwrap();
+				Reactor::notify(&thread.notifier);
+			}
+			None => panic!("Reactor has not been initialized"),
+		});
+
+		Async { source, io }
+	}
+}
+
+impl<T> Async<T> {
+	/// Reference to the inner IO object
+	pub fn get_ref(&self) -> &T {
+		&self.io
+	}
+
+	/// Mutable reference to the inner IO object
+	pub fn get_mut(&mut self) -> &mut T {
+		&mut self.io
+	}
+
+	/// Deregisters the IO object and returns it
+	pub fn into_inner(self) -> T {
+		self.io
+	}
+
+	/// Waits until the IO object is readable. May return spuriously, reads should still handle [`io::ErrorKind::WouldBlock`]
+	pub async fn readable(&self) -> io::Result<()> {
+		future::poll_fn(|cx| coop::poll_budgeted(cx, |cx| self.source.poll_ready(READ, cx))).await
+	}
+
+	/// Waits until the IO object is writable. May return spuriously, writes should still handle [`io::ErrorKind::WouldBlock`]
+	pub async fn writable(&self) -> io::Result<()> {
+		future::poll_fn(|cx| coop::poll_budgeted(cx, |cx| self.source.poll_ready(WRITE, cx))).await
+	}
+
+	/// Repeatedly invokes `op` until it stops returning [`io::ErrorKind::WouldBlock`], waiting for readability in between
+	pub async fn read_with<R>(&self, mut op: impl FnMut(&T) -> io::Result<R>) -> io::Result<R> {
+		loop {
+			match op(&self.io) {
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => self.readable().await?,
+				res => return res,
+			}
+		}
+	}
+
+	/// Repeatedly invokes `op` until it stops returning [`io::ErrorKind::WouldBlock`], waiting for writability in between
+	pub async fn write_with<R>(&self, mut op: impl FnMut(&T) -> io::Result<R>) -> io::Result<R> {
+		loop {
+			match op(&self.io) {
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => self.writable().await?,
+				res => return res,
+			}
+		}
+	}
+}
+
+impl<T: AsRawFd> AsRawFd for Async<T> {
+	fn as_raw_fd(&self) -> RawFd {
+		self.source.fd
+	}
+}
+
+impl<T> Unpin for Async<T> {}
+
+impl<T: Read> AsyncRead for Async<T> {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &mut [u8]) -> task::Poll<io::Result<usize>> {
+		let this = self.get_mut();
+
+		coop::poll_budgeted(cx, |cx| {
+			loop {
+				match this.io.read(buf) {
+					Err(e) if e.kind() == io::ErrorKind::WouldBlock => task::ready!(this.source.poll_ready(READ, cx))?,
+					res => return task::Poll::Ready(res),
+				}
+			}
+		})
+	}
+}
+
+impl<T> AsyncRead for &Async<T>
+where
+	for<'a> &'a T: Read,
+{
+	fn poll_read(self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &mut [u8]) -> task::Poll<io::Result<usize>> {
+		coop::poll_budgeted(cx, |cx| {
+			loop {
+				match (&self.io).read(buf) {
+					Err(e) if e.kind() == io::ErrorKind::WouldBlock => task::ready!(self.source.poll_ready(READ, cx))?,
+					res => return task::Poll::Ready(res),
+				}
+			}
+		})
+	}
+}
+
+impl<T: Write> AsyncWrite for Async<T> {
+	fn poll_write(self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &[u8]) -> task::Poll<io::Result<usize>> {
+		let this = self.get_mut();
+
+		coop::poll_budgeted(cx, |cx| {
+			loop {
+				match this.io.write(buf) {
+					Err(e) if e.kind() == io::ErrorKind::WouldBlock => task::ready!(this.source.poll_ready(WRITE, cx))?,
+					res => return task::Poll::Ready(res),
+				}
+			}
+		})
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		let this = self.get_mut();
+
+		loop {
+			match this.io.flush() {
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => task::ready!(this.source.poll_ready(WRITE, cx))?,
+				res => return task::Poll::Ready(res),
+			}
+		}
+	}
+
+	fn poll_close(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		self.poll_flush(cx)
+	}
+}
+
+impl<T> AsyncWrite for &Async<T>
+where
+	for<'a> &'a T: Write,
+{
+	fn poll_write(self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &[u8]) -> task::Poll<io::Result<usize>> {
+		coop::poll_budgeted(cx, |cx| {
+			loop {
+				match (&self.io).write(buf) {
+					Err(e) if e.kind() == io::ErrorKind::WouldBlock => task::ready!(self.source.poll_ready(WRITE, cx))?,
+					res => return task::Poll::Ready(res),
+				}
+			}
+		})
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		loop {
+			match (&self.io).flush() {
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => task::ready!(self.source.poll_ready(WRITE, cx))?,
+				res => return task::Poll::Ready(res),
+			}
+		}
+	}
+
+	fn poll_close(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		self.poll_flush(cx)
+	}
+}
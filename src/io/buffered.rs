@@ -0,0 +1,257 @@
+use super::{AsyncBufRead, AsyncRead, AsyncWrite};
+use std::{io, pin::Pin, task};
+
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// Adds buffering to an [`AsyncRead`], reducing the number of reads issued to the underlying object
+pub struct BufReader<R> {
+	inner: R,
+	buf: Box<[u8]>,
+	pos: usize,
+	filled: usize,
+}
+
+impl<R> BufReader<R> {
+	/// Wraps `inner` with a default sized buffer
+	pub fn new(inner: R) -> Self {
+		Self::with_capacity(DEFAULT_CAPACITY, inner)
+	}
+
+	/// Wraps `inner` with a buffer of `capacity` bytes
+	pub fn with_capacity(capacity: usize, inner: R) -> Self {
+		BufReader { inner, buf: vec![0; capacity].into_boxed_slice(), pos: 0, filled: 0 }
+	}
+
+	/// Reference to the underlying reader
+	pub fn get_ref(&self) -> &R {
+		&self.inner
+	}
+
+	/// Mutable reference to the underlying reader. Reading from it directly skips any buffered data
+	pub fn get_mut(&mut self) -> &mut R {
+		&mut self.inner
+	}
+
+	/// Currently buffered, unread data
+	pub fn buffer(&self) -> &[u8] {
+		&self.buf[self.pos..self.filled]
+	}
+
+	/// Returns the underlying reader, discarding any buffered data
+	pub fn into_inner(self) -> R {
+		self.inner
+	}
+
+	/// Stream of the reader's lines, see [`Lines`]
+	pub fn lines(self) -> Lines<Self> {
+		lines(self)
+	}
+}
+
+/// Stream of the lines read from `reader`, wrap unbuffered readers in a [`BufReader`] first
+pub fn lines<R>(reader: R) -> Lines<R> {
+	Lines { reader, line: Vec::new() }
+}
+
+/// Stream of lines read from an [`AsyncBufRead`], without their `\n` or `\r\n` endings. Lines that aren't valid UTF-8 fail with [`io::ErrorKind::InvalidData`]
+pub struct Lines<R> {
+	reader: R,
+	/// bytes of the current line read so far
+	line: Vec<u8>,
+}
+
+impl<R> Lines<R> {
+	/// Returns the underlying reader, discarding any partially read line
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+}
+
+impl<R: AsyncBufRead + Unpin> futures_core::Stream for Lines<R> {
+	type Item = io::Result<String>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Option<Self::Item>> {
+		let this = self.get_mut();
+
+		loop {
+			let available = task::ready!(Pin::new(&mut this.reader).poll_fill_buf(cx))?;
+
+			// the last line may lack an ending
+			if available.is_empty() {
+				return match this.line.is_empty() {
+					true => task::Poll::Ready(None),
+					false => task::Poll::Ready(Some(take_line(&mut this.line))),
+				};
+			}
+
+			let (read, complete) = match available.iter().position(|b| *b == b'\n') {
+				Some(end) => (end + 1, true),
+				None => (available.len(), false),
+			};
+
+			this.line.extend_from_slice(&available[..read]);
+			Pin::new(&mut this.reader).consume(read);
+
+			if complete {
+				return task::Poll::Ready(Some(take_line(&mut this.line)));
+			}
+		}
+	}
+}
+
+/// Takes the buffered line, stripping its ending
+fn take_line(line: &mut Vec<u8>) -> io::Result<String> {
+	let mut line = std::mem::take(line);
+
+	if line.last() == Some(&b'\n') {
+		line.pop();
+
+		if line.last() == Some(&b'\r') {
+			line.pop();
+		}
+	}
+
+	String::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for BufReader<R> {
+	fn poll_read(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &mut [u8]) -> task::Poll<io::Result<usize>> {
+		// skip buffering entirely for large reads on an empty buffer
+		if self.pos == self.filled && buf.len() >= self.buf.len() {
+			return Pin::new(&mut self.inner).poll_read(cx, buf);
+		}
+
+		let available = task::ready!(self.as_mut().poll_fill_buf(cx))?;
+		let read = available.len().min(buf.len());
+
+		buf[..read].copy_from_slice(&available[..read]);
+		self.consume(read);
+
+		task::Poll::Ready(Ok(read))
+	}
+}
+
+impl<R: AsyncRead + Unpin> AsyncBufRead for BufReader<R> {
+	fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<&[u8]>> {
+		let this = self.get_mut();
+
+		if this.pos == this.filled {
+			this.filled = task::ready!(Pin::new(&mut this.inner).poll_read(cx, &mut this.buf))?;
+			this.pos = 0;
+		}
+
+		task::Poll::Ready(Ok(&this.buf[this.pos..this.filled]))
+	}
+
+	fn consume(mut self: Pin<&mut Self>, amt: usize) {
+		self.pos = (self.pos + amt).min(self.filled);
+	}
+}
+
+impl<R: AsyncWrite + Unpin> AsyncWrite for BufReader<R> {
+	fn poll_write(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &[u8]) -> task::Poll<io::Result<usize>> {
+		Pin::new(&mut self.inner).poll_write(cx, buf)
+	}
+
+	fn poll_flush(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		Pin::new(&mut self.inner).poll_flush(cx)
+	}
+
+	fn poll_close(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		Pin::new(&mut self.inner).poll_close(cx)
+	}
+}
+
+/// Adds buffering to an [`AsyncWrite`], batching small writes until the buffer fills or is flushed
+pub struct BufWriter<W> {
+	inner: W,
+	buf: Vec<u8>,
+	written: usize,
+}
+
+impl<W> BufWriter<W> {
+	/// Wraps `inner` with a default sized buffer
+	pub fn new(inner: W) -> Self {
+		Self::with_capacity(DEFAULT_CAPACITY, inner)
+	}
+
+	/// Wraps `inner` with a buffer of `capacity` bytes
+	pub fn with_capacity(capacity: usize, inner: W) -> Self {
+		BufWriter { inner, buf: Vec::with_capacity(capacity), written: 0 }
+	}
+
+	/// Reference to the underlying writer
+	pub fn get_ref(&self) -> &W {
+		&self.inner
+	}
+
+	/// Mutable reference to the underlying writer. Writing to it directly bypasses any buffered data
+	pub fn get_mut(&mut self) -> &mut W {
+		&mut self.inner
+	}
+
+	/// Data buffered, but not yet written to the underlying writer
+	pub fn buffer(&self) -> &[u8] {
+		&self.buf[self.written..]
+	}
+
+	/// Returns the underlying writer, discarding any unflushed data
+	pub fn into_inner(self) -> W {
+		self.inner
+	}
+}
+
+impl<W: AsyncWrite + Unpin> BufWriter<W> {
+	/// Writes out buffered data, without flushing the underlying writer
+	fn poll_flush_buf(&mut self, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		while self.written < self.buf.len() {
+			match task::ready!(Pin::new(&mut self.inner).poll_write(cx, &self.buf[self.written..]))? {
+				0 => return task::Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+				n => self.written += n,
+			}
+		}
+
+		self.buf.clear();
+		self.written = 0;
+
+		task::Poll::Ready(Ok(()))
+	}
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for BufWriter<W> {
+	fn poll_write(self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &[u8]) -> task::Poll<io::Result<usize>> {
+		let this = self.get_mut();
+
+		if this.buf.len() + buf.len() > this.buf.capacity() {
+			task::ready!(this.poll_flush_buf(cx))?;
+		}
+
+		// writes larger than the buffer go straight through
+		if buf.len() >= this.buf.capacity() {
+			Pin::new(&mut this.inner).poll_write(cx, buf)
+		} else {
+			this.buf.extend_from_slice(buf);
+			task::Poll::Ready(Ok(buf.len()))
+		}
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		let this = self.get_mut();
+
+		task::ready!(this.poll_flush_buf(cx))?;
+		Pin::new(&mut this.inner).poll_flush(cx)
+	}
+
+	fn poll_close(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		let this = self.get_mut();
+
+		task::ready!(this.poll_flush_buf(cx))?;
+		Pin::new(&mut this.inner).poll_close(cx)
+	}
+}
+
+impl<W: AsyncRead + Unpin> AsyncRead for BufWriter<W> {
+	fn poll_read(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &mut [u8]) -> task::Poll<io::Result<usize>> {
+		Pin::new(&mut self.inner).poll_read(cx, buf)
+	}
+}
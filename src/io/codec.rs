@@ -0,0 +1,266 @@
+use super::{AsyncRead, AsyncWrite, util::write_all};
+use std::{future, io, pin::Pin, task};
+
+/// Size by which [`Framed`] grows its read buffer before each read
+const READ_CHUNK: usize = 4 * 1024;
+
+/// Splits bytes read from an IO object into frames
+pub trait Decoder {
+	/// A decoded frame
+	type Item;
+
+	/// Takes the next complete frame from the front of `buf`, or `None` if more bytes are needed
+	fn decode(&mut self, buf: &mut Vec<u8>) -> io::Result<Option<Self::Item>>;
+
+	/// Called once the reader reached EOF, until it returns `None`. By default, leftover bytes fail with [`io::ErrorKind::UnexpectedEof`]
+	fn decode_eof(&mut self, buf: &mut Vec<u8>) -> io::Result<Option<Self::Item>> {
+		match self.decode(buf)? {
+			Some(item) => Ok(Some(item)),
+			None if buf.is_empty() => Ok(None),
+			None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "stream ended within a frame")),
+		}
+	}
+}
+
+/// Turns frames into bytes to write to an IO object
+pub trait Encoder<Item> {
+	/// Appends the encoded `item` to `buf`
+	fn encode(&mut self, item: Item, buf: &mut Vec<u8>) -> io::Result<()>;
+}
+
+/// Frames delimited by `\n`, decoded without their `\n` or `\r\n` endings. Frames must be valid UTF-8
+#[derive(Debug, Clone, Default)]
+pub struct LinesCodec {
+	max_length: Option<usize>,
+}
+
+impl LinesCodec {
+	/// Lines of any length
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Fails decoding with [`io::ErrorKind::InvalidData`] once a line grows beyond `max_length` bytes, bounding memory used by misbehaving peers
+	pub fn with_max_length(max_length: usize) -> Self {
+		LinesCodec { max_length: Some(max_length) }
+	}
+}
+
+impl Decoder for LinesCodec {
+	type Item = String;
+
+	fn decode(&mut self, buf: &mut Vec<u8>) -> io::Result<Option<String>> {
+		let Some(end) = buf.iter().position(|b| *b == b'\n') else {
+			return match self.max_length {
+				Some(max) if buf.len() > max => Err(io::Error::new(io::ErrorKind::InvalidData, "line exceeds maximum length")),
+				_ => Ok(None),
+			};
+		};
+
+		if self.max_length.is_some_and(|max| end > max) {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "line exceeds maximum length"));
+		}
+
+		let mut line = buf.drain(..=end).collect::<Vec<_>>();
+		line.pop();
+
+		if line.last() == Some(&b'\r') {
+			line.pop();
+		}
+
+		String::from_utf8(line).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+	}
+
+	fn decode_eof(&mut self, buf: &mut Vec<u8>) -> io::Result<Option<String>> {
+		if let Some(line) = self.decode(buf)? {
+			return Ok(Some(line));
+		}
+
+		// the last line may lack an ending
+		match buf.is_empty() {
+			true => Ok(None),
+			false => String::from_utf8(std::mem::take(buf)).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+		}
+	}
+}
+
+impl<S: AsRef<str>> Encoder<S> for LinesCodec {
+	fn encode(&mut self, line: S, buf: &mut Vec<u8>) -> io::Result<()> {
+		buf.extend_from_slice(line.as_ref().as_bytes());
+		buf.push(b'\n');
+
+		Ok(())
+	}
+}
+
+/// Frames prefixed by their length, as a big endian `u32`
+#[derive(Debug, Clone)]
+pub struct LengthDelimitedCodec {
+	max_frame_length: usize,
+}
+
+impl Default for LengthDelimitedCodec {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl LengthDelimitedCodec {
+	/// Accepts frames of up to 8 MiB
+	pub fn new() -> Self {
+		Self::with_max_frame_length(8 * 1024 * 1024)
+	}
+
+	/// Fails with [`io::ErrorKind::InvalidData`] on frames longer than `max_frame_length` bytes
+	pub fn with_max_frame_length(max_frame_length: usize) -> Self {
+		LengthDelimitedCodec { max_frame_length }
+	}
+}
+
+impl Decoder for LengthDelimitedCodec {
+	type Item = Vec<u8>;
+
+	fn decode(&mut self, buf: &mut Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+		let Some(header) = buf.first_chunk::<4>() else { return Ok(None) };
+		let len = u32::from_be_bytes(*header) as usize;
+
+		if len > self.max_frame_length {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "frame exceeds maximum length"));
+		}
+
+		if buf.len() < 4 + len {
+			return Ok(None);
+		}
+
+		let frame = buf[4..4 + len].to_vec();
+		buf.drain(..4 + len);
+
+		Ok(Some(frame))
+	}
+}
+
+impl<B: AsRef<[u8]>> Encoder<B> for LengthDelimitedCodec {
+	fn encode(&mut self, frame: B, buf: &mut Vec<u8>) -> io::Result<()> {
+		let frame = frame.as_ref();
+
+		if frame.len() > self.max_frame_length {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, "frame exceeds maximum length"));
+		}
+
+		buf.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+		buf.extend_from_slice(frame);
+
+		Ok(())
+	}
+}
+
+/// An IO object paired with a codec. A [`Stream`](futures_core::Stream) of decoded frames, and frames can be [`send`](Framed::send) to it
+pub struct Framed<T, C> {
+	io: T,
+	codec: C,
+	read_buf: Vec<u8>,
+	write_buf: Vec<u8>,
+	eof: bool,
+}
+
+impl<T, C> Framed<T, C> {
+	/// Frames reads and writes of `io` using `codec`
+	pub fn new(io: T, codec: C) -> Self {
+		Framed { io, codec, read_buf: Vec::new(), write_buf: Vec::new(), eof: false }
+	}
+
+	/// Reference to the underlying IO object
+	pub fn get_ref(&self) -> &T {
+		&self.io
+	}
+
+	/// Mutable reference to the underlying IO object. Reading or writing to it directly corrupts framing
+	pub fn get_mut(&mut self) -> &mut T {
+		&mut self.io
+	}
+
+	/// Reference to the codec
+	pub fn codec(&self) -> &C {
+		&self.codec
+	}
+
+	/// Returns the IO object, discarding buffered data
+	pub fn into_inner(self) -> T {
+		self.io
+	}
+}
+
+impl<T: AsyncWrite + Unpin, C> Framed<T, C> {
+	/// Encodes `item` into the write buffer, without writing it out. Call [`flush`](Framed::flush) to write buffered frames
+	pub fn feed<I>(&mut self, item: I) -> io::Result<()>
+	where
+		C: Encoder<I>,
+	{
+		self.codec.encode(item, &mut self.write_buf)
+	}
+
+	/// Writes out buffered frames, then flushes the IO object
+	pub async fn flush(&mut self) -> io::Result<()> {
+		let buf = std::mem::take(&mut self.write_buf);
+		write_all(&mut self.io, &buf).await?;
+
+		// reuse the allocation
+		self.write_buf = buf;
+		self.write_buf.clear();
+
+		future::poll_fn(|cx| Pin::new(&mut self.io).poll_flush(cx)).await
+	}
+
+	/// Encodes and writes `item`, flushing the IO object
+	pub async fn send<I>(&mut self, item: I) -> io::Result<()>
+	where
+		C: Encoder<I>,
+	{
+		self.feed(item)?;
+		self.flush().await
+	}
+}
+
+impl<T: AsyncRead + Unpin, C: Decoder + Unpin> futures_core::Stream for Framed<T, C> {
+	type Item = io::Result<C::Item>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Option<Self::Item>> {
+		let this = self.get_mut();
+
+		loop {
+			let decoded = match this.eof {
+				true => this.codec.decode_eof(&mut this.read_buf),
+				false => this.codec.decode(&mut this.read_buf),
+			};
+
+			match decoded {
+				Ok(Some(item)) => return task::Poll::Ready(Some(Ok(item))),
+				Ok(None) if this.eof => return task::Poll::Ready(None),
+				Ok(None) => {}
+				Err(e) => {
+					// a corrupt stream can't be resynchronized
+					this.eof = true;
+					this.read_buf.clear();
+
+					return task::Poll::Ready(Some(Err(e)));
+				}
+			}
+
+			let filled = this.read_buf.len();
+			this.read_buf.resize(filled + READ_CHUNK, 0);
+
+			let read = Pin::new(&mut this.io).poll_read(cx, &mut this.read_buf[filled..]);
+			let read = read.map_ok(|read| (filled + read, read == 0));
+			this.read_buf.truncate(match read {
+				task::Poll::Ready(Ok((len, _))) => len,
+				_ => filled,
+			});
+
+			match read {
+				task::Poll::Pending => return task::Poll::Pending,
+				task::Poll::Ready(Ok((_, eof))) => this.eof = eof,
+				task::Poll::Ready(Err(e)) => return task::Poll::Ready(Some(Err(e))),
+			}
+		}
+	}
+}
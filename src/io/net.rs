@@ -0,0 +1,283 @@
+use super::{Async, READ};
+use crate::coop;
+use std::{io, net, pin::Pin, task};
+
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+use std::{
+	ffi::{c_int, c_void},
+	os::fd::FromRawFd,
+};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod sys {
+	pub(super) const AF_INET6: std::ffi::c_int = 10;
+	pub(super) const EINPROGRESS: i32 = 115;
+	pub(super) type Family = u16;
+}
+
+#[cfg(target_vendor = "apple")]
+mod sys {
+	pub(super) const AF_INET6: std::ffi::c_int = 30;
+	pub(super) const EINPROGRESS: i32 = 36;
+	pub(super) type Family = u8;
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+const AF_INET: c_int = 2;
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+const SOCK_STREAM: c_int = 1;
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+const F_SETFD: c_int = 2;
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+const FD_CLOEXEC: c_int = 1;
+
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+unsafe extern "C" {
+	fn socket(domain: c_int, ty: c_int, protocol: c_int) -> c_int;
+	fn connect(fd: c_int, addr: *const c_void, len: u32) -> c_int;
+	fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+}
+
+/// `sockaddr_in`, BSD derived systems lead with the structure's length
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+#[repr(C)]
+struct SockAddrV4 {
+	#[cfg(target_vendor = "apple")]
+	len: u8,
+	family: sys::Family,
+	port: u16,
+	addr: [u8; 4],
+	zero: [u8; 8],
+}
+
+/// `sockaddr_in6`
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+#[repr(C)]
+struct SockAddrV6 {
+	#[cfg(target_vendor = "apple")]
+	len: u8,
+	family: sys::Family,
+	port: u16,
+	flowinfo: u32,
+	addr: [u8; 16],
+	scope_id: u32,
+}
+
+/// Delay between connection attempts of [`connect_racing`](Async::connect_racing), as recommended by RFC 8305
+#[cfg(all(feature = "timers", any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+pub const ATTEMPT_DELAY: std::time::Duration = std::time::Duration::from_millis(250);
+
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+impl Async<net::TcpStream> {
+	/// Connects to `addr` without blocking the runtime, waiting on the reactor for the handshake to finish
+	pub async fn connect(addr: net::SocketAddr) -> io::Result<TcpStream> {
+		let domain = if addr.is_ipv4() { AF_INET } else { sys::AF_INET6 };
+
+		let fd = unsafe { socket(domain, SOCK_STREAM, 0) };
+		if fd < 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		// take ownership immediately, so the descriptor is closed on error
+		let stream = unsafe { net::TcpStream::from_raw_fd(fd) };
+		if unsafe { fcntl(fd, F_SETFD, FD_CLOEXEC) } < 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		stream.set_nonblocking(true)?;
+
+		let res = match addr {
+			net::SocketAddr::V4(v4) => {
+				let raw = SockAddrV4 {
+					#[cfg(target_vendor = "apple")]
+					len: size_of::<SockAddrV4>() as u8,
+					family: AF_INET as sys::Family,
+					port: v4.port().to_be(),
+					addr: v4.ip().octets(),
+					zero: [0; 8],
+				};
+
+				unsafe { connect(fd, (&raw as *const SockAddrV4).cast(), size_of::<SockAddrV4>() as u32) }
+			}
+			net::SocketAddr::V6(v6) => {
+				let raw = SockAddrV6 {
+					#[cfg(target_vendor = "apple")]
+					len: size_of::<SockAddrV6>() as u8,
+					family: sys::AF_INET6 as sys::Family,
+					port: v6.port().to_be(),
+					flowinfo: v6.flowinfo().to_be(),
+					addr: v6.ip().octets(),
+					scope_id: v6.scope_id(),
+				};
+
+				unsafe { connect(fd, (&raw as *const SockAddrV6).cast(), size_of::<SockAddrV6>() as u32) }
+			}
+		};
+
+		if res < 0 {
+			let err = io::Error::last_os_error();
+
+			if err.raw_os_error() != Some(sys::EINPROGRESS) {
+				return Err(err);
+			}
+		}
+
+		// the socket turns writable once the handshake completes or fails
+		let stream = Async::new(stream);
+		stream.writable().await?;
+
+		match stream.get_ref().take_error()? {
+			Some(err) => Err(err),
+			None => Ok(stream),
+		}
+	}
+
+	/// Like [`connect`](Async::connect), but fails with [`io::ErrorKind::TimedOut`] if the connection isn't established within `timeout`
+	#[cfg(feature = "timers")]
+	pub async fn connect_timeout(addr: net::SocketAddr, timeout: std::time::Duration) -> io::Result<TcpStream> {
+		use std::future::Future;
+
+		let mut connecting = core::pin::pin!(Self::connect(addr));
+		let mut delay = crate::timers::compat::Delay::new(timeout);
+
+		std::future::poll_fn(|cx| {
+			if let task::Poll::Ready(res) = connecting.as_mut().poll(cx) {
+				return task::Poll::Ready(res);
+			}
+
+			task::ready!(Pin::new(&mut delay).poll(cx));
+			task::Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, "connection timed out")))
+		})
+		.await
+	}
+
+	/// Connects to the first of `addrs` to accept, racing attempts in the style of Happy Eyeballs (RFC 8305).
+	///
+	/// IPv6 and IPv4 addresses are tried alternately, IPv6 first, starting another attempt every [`ATTEMPT_DELAY`] or as soon as one fails.
+	/// Resolving a host name through [`ToSocketAddrs`](net::ToSocketAddrs) blocks, pass resolved addresses to avoid stalling the runtime
+	#[cfg(feature = "timers")]
+	pub async fn connect_racing<A: net::ToSocketAddrs>(addrs: A) -> io::Result<TcpStream> {
+		use std::future::Future;
+
+		let (v6, v4): (Vec<_>, Vec<_>) = addrs.to_socket_addrs()?.partition(net::SocketAddr::is_ipv6);
+		let mut pending = interleave(v6, v4).into_iter();
+
+		let mut attempts = Vec::<Pin<Box<dyn Future<Output = io::Result<TcpStream>>>>>::new();
+		let mut stagger = crate::timers::compat::Delay::new(std::time::Duration::ZERO);
+		let mut last_error = None;
+
+		std::future::poll_fn(|cx| {
+			loop {
+				// start the next attempt once the previous one had its head start, or failed
+				if (attempts.is_empty() || Pin::new(&mut stagger).poll(cx).is_ready())
+					&& let Some(addr) = pending.next()
+				{
+					attempts.push(Box::pin(Self::connect(addr)));
+					stagger.reset(ATTEMPT_DELAY);
+					continue;
+				}
+
+				let before = attempts.len();
+				let mut i = 0;
+
+				while i < attempts.len() {
+					match attempts[i].as_mut().poll(cx) {
+						task::Poll::Ready(Ok(stream)) => return task::Poll::Ready(Ok(stream)),
+						task::Poll::Ready(Err(err)) => {
+							last_error = Some(err);
+							drop(attempts.remove(i));
+						}
+						task::Poll::Pending => i += 1,
+					}
+				}
+
+				if attempts.is_empty() && pending.len() == 0 {
+					let err = last_error.take().unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no addresses to connect to"));
+					return task::Poll::Ready(Err(err));
+				}
+
+				// a failed attempt lets the next one start right away
+				match attempts.len() < before {
+					true => stagger.reset(std::time::Duration::ZERO),
+					false => return task::Poll::Pending,
+				}
+			}
+		})
+		.await
+	}
+}
+
+/// Alternates between both lists, starting with `first`
+#[cfg(all(feature = "timers", any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+fn interleave<T>(first: Vec<T>, second: Vec<T>) -> Vec<T> {
+	let (mut first, mut second) = (first.into_iter(), second.into_iter());
+	let mut merged = Vec::new();
+
+	loop {
+		match (first.next(), second.next()) {
+			(None, None) => return merged,
+			(a, b) => merged.extend(a.into_iter().chain(b)),
+		}
+	}
+}
+
+/// A TCP socket listening for connections, registered with the reactor
+pub type TcpListener = Async<net::TcpListener>;
+
+/// A TCP connection, registered with the reactor
+pub type TcpStream = Async<net::TcpStream>;
+
+impl Async<net::TcpListener> {
+	/// Binds a listener to `addr`, ready to accept connections
+	pub fn bind<A: net::ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
+		let listener = net::TcpListener::bind(addr)?;
+		listener.set_nonblocking(true)?;
+
+		Ok(Async::new(listener))
+	}
+
+	/// Address the listener is bound to, e.g. to find the port picked when binding to port 0
+	pub fn local_addr(&self) -> io::Result<net::SocketAddr> {
+		self.get_ref().local_addr()
+	}
+
+	/// Waits for the next connection, along with the peer's address
+	pub async fn accept(&self) -> io::Result<(TcpStream, net::SocketAddr)> {
+		let (stream, addr) = self.read_with(|listener| listener.accept()).await?;
+		stream.set_nonblocking(true)?;
+
+		Ok((Async::new(stream), addr))
+	}
+
+	/// Stream of incoming connections, never ending on its own. Failed accepts are yielded as errors, without ending the stream
+	pub fn incoming(&self) -> Incoming<'_> {
+		Incoming { listener: self }
+	}
+}
+
+/// Connections accepted by a [`TcpListener`], from [`incoming`](Async::incoming)
+pub struct Incoming<'a> {
+	listener: &'a TcpListener,
+}
+
+impl futures_core::Stream for Incoming<'_> {
+	type Item = io::Result<TcpStream>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Option<Self::Item>> {
+		let listener = self.listener;
+
+		coop::poll_budgeted(cx, |cx| {
+			loop {
+				match listener.get_ref().accept() {
+					Ok((stream, _)) => return task::Poll::Ready(Some(stream.set_nonblocking(true).map(|_| Async::new(stream)))),
+					Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+						if let Err(e) = task::ready!(listener.source.poll_ready(READ, cx)) {
+							return task::Poll::Ready(Some(Err(e)));
+						}
+					}
+					Err(e) => return task::Poll::Ready(Some(Err(e))),
+				}
+			}
+		})
+	}
+}
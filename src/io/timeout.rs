@@ -0,0 +1,166 @@
+use super::{AsyncRead, AsyncWrite};
+use crate::timers::compat::Delay;
+use std::{future::Future, io, pin::Pin, task, time};
+
+/// Fails operations pending for longer than `dur`, the clock restarts whenever one makes progress
+struct Timeout {
+	dur: time::Duration,
+	delay: Option<Delay>,
+}
+
+impl Timeout {
+	fn new(dur: time::Duration) -> Self {
+		Timeout { dur, delay: None }
+	}
+
+	fn poll<T>(&mut self, cx: &mut task::Context<'_>, poll: task::Poll<io::Result<T>>) -> task::Poll<io::Result<T>> {
+		if poll.is_ready() {
+			self.delay = None;
+			return poll;
+		}
+
+		let delay = self.delay.get_or_insert_with(|| Delay::new(self.dur));
+		task::ready!(Pin::new(delay).poll(cx));
+		self.delay = None;
+
+		task::Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, "IO operation timed out")))
+	}
+}
+
+/// Fails reads with [`io::ErrorKind::TimedOut`] once they have been pending for longer than the timeout, e.g. to detect dead peers
+pub struct TimeoutReader<R> {
+	inner: R,
+	timeout: Timeout,
+}
+
+impl<R> TimeoutReader<R> {
+	/// Wraps `inner`, failing reads that wait for longer than `timeout`
+	pub fn new(inner: R, timeout: time::Duration) -> Self {
+		TimeoutReader { inner, timeout: Timeout::new(timeout) }
+	}
+
+	/// How long reads may stay pending
+	pub fn timeout(&self) -> time::Duration {
+		self.timeout.dur
+	}
+
+	/// Changes the timeout, restarting the clock of a pending read
+	pub fn set_timeout(&mut self, timeout: time::Duration) {
+		self.timeout = Timeout::new(timeout);
+	}
+
+	/// Reference to the underlying reader
+	pub fn get_ref(&self) -> &R {
+		&self.inner
+	}
+
+	/// Mutable reference to the underlying reader
+	pub fn get_mut(&mut self) -> &mut R {
+		&mut self.inner
+	}
+
+	/// Returns the underlying reader
+	pub fn into_inner(self) -> R {
+		self.inner
+	}
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for TimeoutReader<R> {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &mut [u8]) -> task::Poll<io::Result<usize>> {
+		let this = self.get_mut();
+
+		let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+		this.timeout.poll(cx, poll)
+	}
+}
+
+impl<R: AsyncWrite + Unpin> AsyncWrite for TimeoutReader<R> {
+	fn poll_write(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &[u8]) -> task::Poll<io::Result<usize>> {
+		Pin::new(&mut self.inner).poll_write(cx, buf)
+	}
+
+	fn poll_flush(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		Pin::new(&mut self.inner).poll_flush(cx)
+	}
+
+	fn poll_close(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		Pin::new(&mut self.inner).poll_close(cx)
+	}
+}
+
+/// Fails writes, flushes and closes with [`io::ErrorKind::TimedOut`] once they have been pending for longer than the timeout, e.g. when a peer stops reading
+pub struct TimeoutWriter<W> {
+	inner: W,
+	timeout: Timeout,
+}
+
+impl<W> TimeoutWriter<W> {
+	/// Wraps `inner`, failing writes that wait for longer than `timeout`
+	pub fn new(inner: W, timeout: time::Duration) -> Self {
+		TimeoutWriter { inner, timeout: Timeout::new(timeout) }
+	}
+
+	/// How long writes may stay pending
+	pub fn timeout(&self) -> time::Duration {
+		self.timeout.dur
+	}
+
+	/// Changes the timeout, restarting the clock of a pending write
+	pub fn set_timeout(&mut self, timeout: time::Duration) {
+		self.timeout = Timeout::new(timeout);
+	}
+
+	/// Reference to the underlying writer
+	pub fn get_ref(&self) -> &W {
+		&self.inner
+	}
+
+	/// Mutable reference to the underlying writer
+	pub fn get_mut(&mut self) -> &mut W {
+		&mut self.inner
+	}
+
+	/// Returns the underlying writer
+	pub fn into_inner(self) -> W {
+		self.inner
+	}
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for TimeoutWriter<W> {
+	fn poll_write(self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &[u8]) -> task::Poll<io::Result<usize>> {
+		let this = self.get_mut();
+
+		let poll = Pin::new(&mut this.inner).poll_write(cx, buf);
+		this.timeout.poll(cx, poll)
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		let this = self.get_mut();
+
+		let poll = Pin::new(&mut this.inner).poll_flush(cx);
+		this.timeout.poll(cx, poll)
+	}
+
+	fn poll_close(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		let this = self.get_mut();
+
+		let poll = Pin::new(&mut this.inner).poll_close(cx);
+		this.timeout.poll(cx, poll)
+	}
+}
+
+impl<W: AsyncRead + Unpin> AsyncRead for TimeoutWriter<W> {
+	fn poll_read(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &mut [u8]) -> task::Poll<io::Result<usize>> {
+		Pin::new(&mut self.inner).poll_read(cx, buf)
+	}
+}
+
+/// Reads into `buf`, failing with [`io::ErrorKind::TimedOut`] if nothing arrives within `timeout`
+pub async fn read_timeout<R: AsyncRead + Unpin + ?Sized>(reader: &mut R, buf: &mut [u8], timeout: time::Duration) -> io::Result<usize> {
+	let mut timeout = Timeout::new(timeout);
+	std::future::poll_fn(|cx| {
+		let poll = Pin::new(&mut *reader).poll_read(cx, buf);
+		timeout.poll(cx, poll)
+	})
+	.await
+}
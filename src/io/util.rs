@@ -0,0 +1,204 @@
+use super::{AsyncBufRead, AsyncRead, AsyncWrite};
+use crate::coop;
+use std::{future, io, pin::Pin, sync, task};
+
+/// Size of the buffers used by [`copy`] and [`copy_bidirectional`]
+const COPY_BUFFER: usize = 8 * 1024;
+
+/// Reads exactly enough bytes to fill `buf`, failing with [`io::ErrorKind::UnexpectedEof`] if the reader ends first
+pub async fn read_exact<R: AsyncRead + Unpin + ?Sized>(reader: &mut R, mut buf: &mut [u8]) -> io::Result<()> {
+	future::poll_fn(|cx| {
+		while !buf.is_empty() {
+			match task::ready!(Pin::new(&mut *reader).poll_read(cx, buf))? {
+				0 => return task::Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+				n => buf = &mut std::mem::take(&mut buf)[n..],
+			}
+		}
+
+		task::Poll::Ready(Ok(()))
+	})
+	.await
+}
+
+/// Reads into `buf` until `byte` is found, including it, or EOF is reached. Returns the number of bytes read, zero at EOF
+pub async fn read_until<R: AsyncBufRead + Unpin + ?Sized>(reader: &mut R, byte: u8, buf: &mut Vec<u8>) -> io::Result<usize> {
+	let mut read = 0;
+
+	future::poll_fn(|cx| {
+		loop {
+			let available = task::ready!(Pin::new(&mut *reader).poll_fill_buf(cx))?;
+
+			if available.is_empty() {
+				return task::Poll::Ready(Ok(read));
+			}
+
+			let (used, found) = match available.iter().position(|b| *b == byte) {
+				Some(at) => (at + 1, true),
+				None => (available.len(), false),
+			};
+
+			buf.extend_from_slice(&available[..used]);
+			Pin::new(&mut *reader).consume(used);
+			read += used;
+
+			if found {
+				return task::Poll::Ready(Ok(read));
+			}
+		}
+	})
+	.await
+}
+
+/// Appends a line to `buf`, including its `\n` ending if any. Returns the number of bytes read, zero at EOF.
+///
+/// Fails with [`io::ErrorKind::InvalidData`] if the line isn't valid UTF-8, leaving `buf` unchanged
+pub async fn read_line<R: AsyncBufRead + Unpin + ?Sized>(reader: &mut R, buf: &mut String) -> io::Result<usize> {
+	let mut line = Vec::new();
+	let read = read_until(reader, b'\n', &mut line).await?;
+
+	buf.push_str(&String::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?);
+	Ok(read)
+}
+
+/// Writes the entirety of `buf`, failing with [`io::ErrorKind::WriteZero`] if the writer stops accepting bytes
+pub async fn write_all<W: AsyncWrite + Unpin + ?Sized>(writer: &mut W, mut buf: &[u8]) -> io::Result<()> {
+	future::poll_fn(|cx| {
+		while !buf.is_empty() {
+			match task::ready!(Pin::new(&mut *writer).poll_write(cx, buf))? {
+				0 => return task::Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+				n => buf = &buf[n..],
+			}
+		}
+
+		task::Poll::Ready(Ok(()))
+	})
+	.await
+}
+
+/// Copies the entire contents of `reader` into `writer`, returning the number of bytes copied.
+///
+/// Each chunk charges the task's [budget](crate::coop), so copying from a fast reader still yields to other tasks
+pub async fn copy<R: AsyncRead + Unpin + ?Sized, W: AsyncWrite + Unpin + ?Sized>(reader: &mut R, writer: &mut W) -> io::Result<u64> {
+	let mut buf = vec![0; COPY_BUFFER];
+	let mut copied = 0;
+
+	loop {
+		coop::consume_budget().await;
+		let read = future::poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, &mut buf)).await?;
+
+		if read == 0 {
+			future::poll_fn(|cx| Pin::new(&mut *writer).poll_flush(cx)).await?;
+			return Ok(copied);
+		}
+
+		write_all(writer, &buf[..read]).await?;
+		copied += read as u64;
+	}
+}
+
+/// Copies data in both directions between `a` and `b` until both reach EOF, returning the bytes copied from `a` to `b` and from `b` to `a`.
+///
+/// Once one side reaches EOF, the other is closed, while data keeps flowing the opposite way. The building block of proxies,
+/// each chunk charges the task's [budget](crate::coop), so one fast connection doesn't starve the others
+pub async fn copy_bidirectional<A, B>(a: &mut A, b: &mut B) -> io::Result<(u64, u64)>
+where
+	A: AsyncRead + AsyncWrite + Unpin + ?Sized,
+	B: AsyncRead + AsyncWrite + Unpin + ?Sized,
+{
+	let (mut a_to_b, mut b_to_a) = (Transfer::new(), Transfer::new());
+
+	future::poll_fn(|cx| {
+		let forward = a_to_b.poll(cx, &mut *a, &mut *b)?;
+		let backward = b_to_a.poll(cx, &mut *b, &mut *a)?;
+
+		match (forward, backward) {
+			(task::Poll::Ready(forward), task::Poll::Ready(backward)) => task::Poll::Ready(Ok((forward, backward))),
+			_ => task::Poll::Pending,
+		}
+	})
+	.await
+}
+
+/// One direction of [`copy_bidirectional`]
+struct Transfer {
+	buf: Box<[u8]>,
+	pos: usize,
+	filled: usize,
+	eof: bool,
+	/// set once the writer was closed, the transfer is complete
+	closed: bool,
+	copied: u64,
+}
+
+impl Transfer {
+	fn new() -> Self {
+		Transfer { buf: vec![0; COPY_BUFFER].into_boxed_slice(), pos: 0, filled: 0, eof: false, closed: false, copied: 0 }
+	}
+
+	fn poll<R: AsyncRead + Unpin + ?Sized, W: AsyncWrite + Unpin + ?Sized>(&mut self, cx: &mut task::Context<'_>, reader: &mut R, writer: &mut W) -> task::Poll<io::Result<u64>> {
+		while !self.closed {
+			if self.pos == self.filled && !self.eof {
+				let buf = &mut self.buf;
+
+				match task::ready!(coop::poll_budgeted(cx, |cx| Pin::new(&mut *reader).poll_read(cx, buf)))? {
+					0 => self.eof = true,
+					read => (self.pos, self.filled) = (0, read),
+				}
+			}
+
+			while self.pos < self.filled {
+				match task::ready!(Pin::new(&mut *writer).poll_write(cx, &self.buf[self.pos..self.filled]))? {
+					0 => return task::Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+					written => {
+						self.pos += written;
+						self.copied += written as u64;
+					}
+				}
+			}
+
+			if self.eof {
+				task::ready!(Pin::new(&mut *writer).poll_close(cx))?;
+				self.closed = true;
+			}
+		}
+
+		task::Poll::Ready(Ok(self.copied))
+	}
+}
+
+/// Splits a duplex IO object into separately owned read and write halves
+pub fn split<T: AsyncRead + AsyncWrite + Unpin>(io: T) -> (ReadHalf<T>, WriteHalf<T>) {
+	let inner = sync::Arc::new(sync::Mutex::new(io));
+	(ReadHalf { inner: inner.clone() }, WriteHalf { inner })
+}
+
+/// Read half of an IO object, created by [`split`]
+pub struct ReadHalf<T> {
+	inner: sync::Arc<sync::Mutex<T>>,
+}
+
+/// Write half of an IO object, created by [`split`]
+pub struct WriteHalf<T> {
+	inner: sync::Arc<sync::Mutex<T>>,
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for ReadHalf<T> {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &mut [u8]) -> task::Poll<io::Result<usize>> {
+		// polls are synchronous, so the lock is never held across an await point
+		Pin::new(&mut *self.inner.lock().unwrap()).poll_read(cx, buf)
+	}
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for WriteHalf<T> {
+	fn poll_write(self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &[u8]) -> task::Poll<io::Result<usize>> {
+		Pin::new(&mut *self.inner.lock().unwrap()).poll_write(cx, buf)
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		Pin::new(&mut *self.inner.lock().unwrap()).poll_flush(cx)
+	}
+
+	fn poll_close(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+		Pin::new(&mut *self.inner.lock().unwrap()).poll_close(cx)
+	}
+}
@@ -2,15 +2,48 @@
 
 #![deny(missing_docs)]
 #![doc = include_str!("../README.md")]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
+extern crate alloc;
+
+mod arena;
+mod oneshot;
+/// Synchronization the runtime is built on: its ready queue, task locks and oneshot channels, which also hand wakers to the sleeping thread.
+///
+/// Swapped for loom's checked versions when built with `--cfg loom`, so runtimes can be model checked along with code built on them. Timers and IO
+/// are driven by threads of their own, outside of loom's control, so models cover runtimes without them
+mod primitives;
 #[cfg(test)]
+#[allow(clippy::manual_async_fn, clippy::print_literal)]
 mod tests;
 
+#[cfg(feature = "std")]
+pub use rt::block_in_place;
+
+/// Per-task poll budgets, keeping a single busy task from starving the others
+pub mod coop;
+/// Runtime metrics, such as scheduling latency histograms
+#[cfg(feature = "std")]
+pub mod metrics;
 /// [`Runtime`](rt::Runtime) implementation
 pub mod rt;
+/// Synchronization primitives for tasks running on the same runtime
+pub mod sync;
 /// [`TaskMonitor`](tasks::TaskMonitor) implementation
 pub mod tasks;
 
 /// Lazy Timers implementation, focused on reducing self wake-ups
 #[cfg(feature = "timers")]
 pub mod timers;
+
+/// Async IO adapters, driven by a dedicated `poll(2)` reactor thread
+#[cfg(all(feature = "io", unix))]
+pub mod io;
+
+/// Filesystem watching, built on `inotify` and the IO reactor
+#[cfg(all(feature = "fs", any(target_os = "linux", target_os = "android")))]
+pub mod fs;
+
+/// Child processes, whose exit is awaited through the IO reactor
+#[cfg(all(feature = "process", any(target_os = "linux", target_os = "android")))]
+pub mod process;
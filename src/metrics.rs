@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+/// Number of buckets in a [`Histogram`], the last one also counting anything longer
+const BUCKETS: usize = 32;
+
+/// Distribution of durations, in power of two buckets of microseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Histogram {
+	buckets: [u64; BUCKETS],
+	count: u64,
+	sum: Duration,
+	max: Duration,
+}
+
+impl Histogram {
+	pub(crate) fn record(&mut self, value: Duration) {
+		let micros = value.as_nanos().div_ceil(1000);
+
+		// bucket `i` holds values up to and including 2^i microseconds, matching Prometheus' inclusive bounds
+		let bucket = (u128::BITS - micros.saturating_sub(1).leading_zeros()) as usize;
+		self.buckets[bucket.min(BUCKETS - 1)] += 1;
+
+		self.count += 1;
+		self.sum += value;
+		self.max = self.max.max(value);
+	}
+
+	/// Number of recorded values
+	pub fn count(&self) -> u64 {
+		self.count
+	}
+
+	/// Sum of all recorded values
+	pub fn sum(&self) -> Duration {
+		self.sum
+	}
+
+	/// Largest recorded value
+	pub fn max(&self) -> Duration {
+		self.max
+	}
+
+	/// Average of the recorded values, zero if there are none
+	pub fn mean(&self) -> Duration {
+		match self.count {
+			0 => Duration::ZERO,
+			// dividing by a `u32` would truncate counts of long lived runtimes
+			count => Duration::from_nanos((self.sum.as_nanos() / count as u128) as u64),
+		}
+	}
+
+	/// Upper bound of the bucket containing the `quantile`th value, e.g. `0.99` for p99. Bounded by [`max`](Histogram::max)
+	pub fn quantile(&self, quantile: f64) -> Duration {
+		let target = (quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64;
+		let mut seen = 0;
+
+		for (bound, count) in self.buckets() {
+			seen += count;
+
+			if seen >= target.max(1) {
+				return bound.min(self.max);
+			}
+		}
+
+		self.max
+	}
+
+	/// Pairs of a bucket's inclusive upper bound and the number of values within it, in ascending order
+	pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+		self.buckets.iter().enumerate().map(|(i, count)| (Duration::from_micros(1 << i), *count))
+	}
+}
+
+/// Runtime wide counters and distributions, from [`Runtime::metrics`](crate::rt::Runtime::metrics)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+	/// Tasks alive when the snapshot was taken
+	pub tasks: usize,
+	/// Total number of task polls
+	pub polls: u64,
+	/// Tasks whose [`heartbeat`](crate::tasks::heartbeat) was overdue when the snapshot was taken
+	pub missed_heartbeats: usize,
+	/// Time between a task being woken, and the runtime polling it
+	pub wake_latency: Histogram,
+}
+
+#[cfg(feature = "prometheus")]
+impl Metrics {
+	/// Writes the metrics in Prometheus' text exposition format, each metric name starting with `prefix`
+	pub fn write_prometheus<W: std::fmt::Write>(&self, out: &mut W, prefix: &str) -> std::fmt::Result {
+		writeln!(out, "# HELP {prefix}_tasks Tasks alive in the runtime")?;
+		writeln!(out, "# TYPE {prefix}_tasks gauge")?;
+		writeln!(out, "{prefix}_tasks {}", self.tasks)?;
+
+		writeln!(out, "# HELP {prefix}_polls_total Task polls performed by the runtime")?;
+		writeln!(out, "# TYPE {prefix}_polls_total counter")?;
+		writeln!(out, "{prefix}_polls_total {}", self.polls)?;
+
+		writeln!(out, "# HELP {prefix}_missed_heartbeats Tasks whose heartbeat is overdue")?;
+		writeln!(out, "# TYPE {prefix}_missed_heartbeats gauge")?;
+		writeln!(out, "{prefix}_missed_heartbeats {}", self.missed_heartbeats)?;
+
+		writeln!(out, "# HELP {prefix}_wake_latency_seconds Time between a task being woken and polled")?;
+		writeln!(out, "# TYPE {prefix}_wake_latency_seconds histogram")?;
+		self.wake_latency.write_prometheus(out, &format!("{prefix}_wake_latency_seconds"))
+	}
+}
+
+#[cfg(feature = "prometheus")]
+impl Histogram {
+	/// Prometheus buckets are cumulative, and bounds are inclusive seconds
+	fn write_prometheus<W: std::fmt::Write>(&self, out: &mut W, name: &str) -> std::fmt::Result {
+		let mut cumulative = 0;
+
+		for (bound, count) in self.buckets().take(BUCKETS - 1) {
+			cumulative += count;
+			writeln!(out, "{name}_bucket{{le=\"{}\"}} {cumulative}", bound.as_secs_f64())?;
+		}
+
+		writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", self.count)?;
+		writeln!(out, "{name}_sum {}", self.sum.as_secs_f64())?;
+		writeln!(out, "{name}_count {}", self.count)
+	}
+}
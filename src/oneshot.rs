@@ -1,108 +1,151 @@
-use std::{fmt, mem};
-
-pub(crate) fn channel<T>() -> (Sender<T>, Receiver<T>) {
-	let status = Box::into_raw(Box::new(ChannelStatus::Pending));
-
-	let sender = Sender { status };
-	let receiver = Receiver { status };
+#[cfg(feature = "std")]
+use crate::primitives::{Waiter, thread};
+use crate::{
+	arena::{Allocator, Counted},
+	primitives::{
+		UnsafeCell,
+		atomic::{self, AtomicU8},
+	},
+};
+use core::{fmt, mem::MaybeUninit};
+
+/// Nothing was sent yet, and both halves are alive
+const EMPTY: u8 = 0;
+/// A message was written by the [`Sender`], and not yet taken
+const MESSAGE: u8 = 1;
+/// Either half was dropped without a message passing
+const CLOSED: u8 = 2;
+/// The message was taken by the [`Receiver`]
+const RECEIVED: u8 = 3;
+
+/// Creates a channel, whose state is allocated from `allocator`
+pub(crate) fn channel<T>(allocator: Allocator) -> (Sender<T>, Receiver<T>) {
+	let inner = Counted::new_in(
+		Inner {
+			state: AtomicU8::new(EMPTY),
+			message: UnsafeCell::new(MaybeUninit::uninit()),
+			#[cfg(feature = "std")]
+			waiter: Waiter::new(),
+		},
+		allocator,
+	);
+
+	(Sender { inner: inner.clone() }, Receiver { inner })
+}
 
-	(sender, receiver)
+/// State shared by both halves. `message` is only written by the sender while `EMPTY`, and only read by whoever moves the state out of `MESSAGE`
+struct Inner<T> {
+	state: AtomicU8,
+	message: UnsafeCell<MaybeUninit<T>>,
+	/// thread blocked in [`Receiver::recv_blocking`], unparked once the state leaves `EMPTY`
+	#[cfg(feature = "std")]
+	waiter: Waiter,
 }
 
-#[repr(u8)]
-/// The status of a channel
-pub(crate) enum ChannelStatus<T> {
-	/// [`Sender`] is pending to send messages
-	Pending,
-	/// Either [`Sender`] or [`Receiver`] has been dropped, without a message passing
-	Closed,
-	/// A message has been currently sent by the [`Sender`]
-	Active(T),
-	/// A message was written to, and consumed from this channel
-	Consumed,
+// SAFETY: access to `message` is serialized by `state`, so sharing the channel only requires the message to be sendable
+unsafe impl<T: Send> Send for Inner<T> {}
+unsafe impl<T: Send> Sync for Inner<T> {}
+
+impl<T> Inner<T> {
+	/// Moves the state out of `EMPTY`, then wakes a blocked receiver
+	fn settle(&self, state: u8) -> Result<(), u8> {
+		self.state.compare_exchange(EMPTY, state, atomic::Ordering::AcqRel, atomic::Ordering::Acquire)?;
+
+		// pairs with the fence in `recv_blocking`, either we see the waiter, or it sees the new state
+		#[cfg(feature = "std")]
+		{
+			atomic::fence(atomic::Ordering::SeqCst);
+			if let Some(thread) = self.waiter.get() {
+				thread.unpark();
+			}
+		}
+
+		Ok(())
+	}
 }
 
-impl<T> fmt::Debug for ChannelStatus<T> {
-	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		match self {
-			Self::Pending => write!(f, "Pending"),
-			Self::Closed => write!(f, "Closed"),
-			Self::Active(..) => f.debug_tuple("Active").field(&"[packet]").finish(),
-			Self::Consumed => write!(f, "Consumed"),
+impl<T> Drop for Inner<T> {
+	fn drop(&mut self) {
+		// a message nobody took
+		if self.state.load(atomic::Ordering::Relaxed) == MESSAGE {
+			self.message.with_mut(|message| unsafe { (*message).assume_init_drop() })
 		}
 	}
 }
 
 pub(crate) struct Sender<T> {
-	status: *mut ChannelStatus<T>,
+	inner: Counted<Inner<T>>,
 }
 
 impl<T> Sender<T> {
-	/// If `Some(T)` then the receiver was closed, [`None`] is the success path
+	/// Fails with the message if the receiver was dropped
 	pub(crate) fn send(self, data: T) -> Result<(), T> {
-		let status = unsafe { self.status.as_mut().unwrap() };
-
-		// attempt to write data to pointer
-		match status {
-			ChannelStatus::Pending => {
-				// set status to active
-				*status = ChannelStatus::Active(data);
-			}
-			// receiver was closed
-			ChannelStatus::Closed => return Err(data),
-			// double send?
-			ChannelStatus::Consumed | ChannelStatus::Active(..) => unreachable!("Double Send on oneshot channel"),
-		};
+		// only the sender writes, and only before the state leaves `EMPTY`
+		self.inner.message.with_mut(|message| unsafe { (*message).write(data) });
 
-		Ok(())
+		match self.inner.settle(MESSAGE) {
+			Ok(()) => Ok(()),
+			// receiver was dropped, nobody else will touch the message
+			Err(_) => Err(self.inner.message.with_mut(|message| unsafe { (*message).assume_init_read() })),
+		}
 	}
 }
 
 impl<T> Drop for Sender<T> {
 	fn drop(&mut self) {
-		let status = unsafe { self.status.as_mut().unwrap() };
-
-		match status {
-			// sender dropped without sending a message
-			ChannelStatus::Pending => *status = ChannelStatus::Closed,
-			// message already sent, or receiver dropped
-			ChannelStatus::Consumed | ChannelStatus::Active(..) | ChannelStatus::Closed => {}
-		}
+		// sender dropped without sending a message, fails if a message was sent or the receiver is gone
+		let _ = self.inner.settle(CLOSED);
 	}
 }
 
 pub(crate) struct Receiver<T> {
-	status: *mut ChannelStatus<T>,
+	inner: Counted<Inner<T>>,
 }
 
 impl<T> Receiver<T> {
 	pub(crate) fn try_recv(&self) -> Result<T, TryRecvError> {
-		let status = unsafe { self.status.as_mut().unwrap() };
+		match self.inner.state.compare_exchange(MESSAGE, RECEIVED, atomic::Ordering::AcqRel, atomic::Ordering::Acquire) {
+			// the exchange hands us exclusive ownership of the message
+			Ok(_) => Ok(self.inner.message.with_mut(|message| unsafe { (*message).assume_init_read() })),
+			Err(EMPTY) => Err(TryRecvError::Empty),
+			Err(_) => Err(TryRecvError::Disconnected),
+		}
+	}
 
-		match status {
-			ChannelStatus::Active(..) => {
-				let ChannelStatus::Active(data) = mem::replace(status, ChannelStatus::Consumed) else { unreachable!() };
-				Ok(data)
+	/// A message is waiting to be received
+	pub(crate) fn has_message(&self) -> bool {
+		self.inner.state.load(atomic::Ordering::Acquire) == MESSAGE
+	}
+
+	/// No message will ever be received, the sender was dropped or the message was already taken
+	pub(crate) fn is_closed(&self) -> bool {
+		matches!(self.inner.state.load(atomic::Ordering::Acquire), CLOSED | RECEIVED)
+	}
+
+	/// Blocks the current thread until a message arrives, or the sender is dropped
+	#[cfg(feature = "std")]
+	pub(crate) fn recv_blocking(&self) -> Result<T, TryRecvError> {
+		// the first thread to block keeps the slot, any other one polls with a timeout
+		let registered = self.inner.waiter.get_or_init(thread::current).id() == thread::current().id();
+		atomic::fence(atomic::Ordering::SeqCst);
+
+		loop {
+			match self.try_recv() {
+				Err(TryRecvError::Empty) if registered => thread::park(),
+				#[cfg(not(loom))]
+				Err(TryRecvError::Empty) => thread::park_timeout(core::time::Duration::from_millis(1)),
+				#[cfg(loom)]
+				Err(TryRecvError::Empty) => thread::yield_now(),
+				res => return res,
 			}
-			ChannelStatus::Pending => Err(TryRecvError::Empty),
-			ChannelStatus::Consumed | ChannelStatus::Closed => Err(TryRecvError::Disconnected),
 		}
 	}
 }
 
 impl<T> Drop for Receiver<T> {
 	fn drop(&mut self) {
-		let _ = unsafe { Box::from_raw(self.status) };
-
-		// update status
-		let status = unsafe { self.status.as_mut().unwrap() };
-
-		match status {
-			// receiver dropped without receiving a message
-			ChannelStatus::Pending => *status = ChannelStatus::Closed,
-			// message already sent, or sender dropped
-			ChannelStatus::Consumed | ChannelStatus::Active(..) | ChannelStatus::Closed => {}
-		}
+		// receiver dropped without receiving a message, a later send hands the message back
+		let _ = self.inner.settle(CLOSED);
 	}
 }
 
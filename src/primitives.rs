@@ -0,0 +1,62 @@
+#[cfg(not(loom))]
+pub(crate) use imp::*;
+#[cfg(loom)]
+pub(crate) use loom_imp::*;
+
+#[cfg(not(loom))]
+mod imp {
+	pub(crate) use core::{hint::spin_loop, sync::atomic};
+	#[cfg(feature = "std")]
+	pub(crate) use std::thread;
+
+	/// Slot of the first thread blocking on a channel
+	#[cfg(feature = "std")]
+	pub(crate) type Waiter = std::sync::OnceLock<thread::Thread>;
+
+	/// `core`'s cell, with the closure based access loom tracks
+	pub(crate) struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+	impl<T> UnsafeCell<T> {
+		pub(crate) const fn new(data: T) -> Self {
+			UnsafeCell(core::cell::UnsafeCell::new(data))
+		}
+
+		pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+			f(self.0.get())
+		}
+	}
+}
+
+#[cfg(loom)]
+mod loom_imp {
+	pub(crate) use loom::{cell::UnsafeCell, hint::spin_loop, sync::atomic, thread};
+
+	/// Loom has no `OnceLock`, its mutex stands in for the slot
+	#[derive(Default)]
+	pub(crate) struct Waiter(loom::sync::Mutex<Option<thread::Thread>>);
+
+	impl Waiter {
+		pub(crate) fn new() -> Self {
+			Self::default()
+		}
+
+		pub(crate) fn get(&self) -> Option<thread::Thread> {
+			self.0.lock().unwrap().clone()
+		}
+
+		pub(crate) fn get_or_init(&self, f: impl FnOnce() -> thread::Thread) -> thread::Thread {
+			self.0.lock().unwrap().get_or_insert_with(f).clone()
+		}
+	}
+
+	/// Parks the current loom thread, the default parker of runtimes built within a model
+	impl crate::rt::Parker for thread::Thread {
+		fn park(&self) {
+			thread::park()
+		}
+
+		fn unpark(&self) {
+			thread::Thread::unpark(self)
+		}
+	}
+}
@@ -0,0 +1,148 @@
+use crate::io::{Async, BufReader, Lines};
+use std::{
+	ffi::{c_int, c_long},
+	fs, io,
+	os::fd::{AsRawFd, FromRawFd},
+	process,
+};
+
+unsafe extern "C" {
+	fn syscall(num: c_long, ...) -> c_long;
+	fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+}
+
+const SYS_PIDFD_OPEN: c_long = 434;
+
+const F_GETFL: c_int = 3;
+const F_SETFL: c_int = 4;
+const O_NONBLOCK: c_int = 0o4000;
+
+/// Lines written by a child to one of its output pipes, from [`Child::stdout_lines`] and [`Child::stderr_lines`]
+pub type OutputLines<T> = Lines<BufReader<Async<T>>>;
+
+/// Registers one of the child's pipes with the reactor, switching it to non-blocking mode
+fn pipe<T: AsRawFd>(pipe: T) -> io::Result<Async<T>> {
+	let fd = pipe.as_raw_fd();
+	let flags = unsafe { fcntl(fd, F_GETFL) };
+
+	if flags < 0 || unsafe { fcntl(fd, F_SETFL, flags | O_NONBLOCK) } < 0 {
+		return Err(io::Error::last_os_error());
+	}
+
+	Ok(Async::new(pipe))
+}
+
+/// Spawns `command`, returning a [`Child`] whose exit can be awaited
+pub fn spawn(command: &mut process::Command) -> io::Result<Child> {
+	let child = command.spawn()?;
+
+	// the descriptor turns readable once the process exits
+	let fd = unsafe { syscall(SYS_PIDFD_OPEN, child.id() as c_int, 0 as c_int) };
+	if fd < 0 {
+		let err = io::Error::last_os_error();
+		let mut child = child;
+		let _ = child.kill().and_then(|_| child.wait());
+
+		return Err(err);
+	}
+
+	let pidfd = unsafe { fs::File::from_raw_fd(fd as c_int) };
+	Ok(Child { inner: child, pidfd: Async::new(pidfd), kill_on_drop: false })
+}
+
+/// A spawned child process, from [`spawn`]. Awaiting its exit goes through the IO reactor, using a `pidfd`
+pub struct Child {
+	inner: process::Child,
+	pidfd: Async<fs::File>,
+	kill_on_drop: bool,
+}
+
+impl Child {
+	/// OS assigned process id
+	pub fn id(&self) -> u32 {
+		self.inner.id()
+	}
+
+	/// The underlying child, e.g. to take its stdio handles
+	pub fn get_mut(&mut self) -> &mut process::Child {
+		&mut self.inner
+	}
+
+	/// Takes the child's stdout, if it was [piped](process::Stdio::piped), as an async reader
+	pub fn stdout(&mut self) -> io::Result<Option<Async<process::ChildStdout>>> {
+		self.inner.stdout.take().map(pipe).transpose()
+	}
+
+	/// Takes the child's stderr, if it was [piped](process::Stdio::piped), as an async reader
+	pub fn stderr(&mut self) -> io::Result<Option<Async<process::ChildStderr>>> {
+		self.inner.stderr.take().map(pipe).transpose()
+	}
+
+	/// Takes the child's piped stdout as a stream of lines, ending once the child closes it.
+	///
+	/// Streams of many children can be merged, e.g. with `futures::stream::select_all`, to forward their output from a single task
+	pub fn stdout_lines(&mut self) -> io::Result<Option<OutputLines<process::ChildStdout>>> {
+		Ok(self.stdout()?.map(|stdout| BufReader::new(stdout).lines()))
+	}
+
+	/// Like [`stdout_lines`](Child::stdout_lines), for the child's piped stderr
+	pub fn stderr_lines(&mut self) -> io::Result<Option<OutputLines<process::ChildStderr>>> {
+		Ok(self.stderr()?.map(|stderr| BufReader::new(stderr).lines()))
+	}
+
+	/// Kills the process when this handle is dropped before it exited, e.g. because the managing task was aborted.
+	///
+	/// The killed process is reaped right away, blocking briefly until it exits
+	pub fn kill_on_drop(&mut self, enabled: bool) {
+		self.kill_on_drop = enabled;
+	}
+
+	/// Exit status, if the process has exited already
+	pub fn try_wait(&mut self) -> io::Result<Option<process::ExitStatus>> {
+		self.inner.try_wait()
+	}
+
+	/// Waits for the process to exit
+	pub async fn wait(&mut self) -> io::Result<process::ExitStatus> {
+		loop {
+			if let Some(status) = self.inner.try_wait()? {
+				return Ok(status);
+			}
+
+			self.pidfd.readable().await?
+		}
+	}
+
+	/// Kills the process, then waits for it to exit
+	pub async fn kill(&mut self) -> io::Result<process::ExitStatus> {
+		self.inner.kill()?;
+		self.wait().await
+	}
+
+	/// Like [`wait`](Child::wait), but gives up after `timeout`, returning `None` if the process is still running
+	#[cfg(feature = "timers")]
+	pub async fn wait_with_timeout(&mut self, timeout: std::time::Duration) -> io::Result<Option<process::ExitStatus>> {
+		use std::future::Future;
+
+		let mut delay = crate::timers::compat::Delay::new(timeout);
+		let mut wait = core::pin::pin!(self.wait());
+
+		std::future::poll_fn(|cx| {
+			if let std::task::Poll::Ready(res) = wait.as_mut().poll(cx) {
+				return std::task::Poll::Ready(res.map(Some));
+			}
+
+			std::task::ready!(core::pin::Pin::new(&mut delay).poll(cx));
+			std::task::Poll::Ready(Ok(None))
+		})
+		.await
+	}
+}
+
+impl Drop for Child {
+	fn drop(&mut self) {
+		if self.kill_on_drop && matches!(self.inner.try_wait(), Ok(None)) {
+			let _ = self.inner.kill().and_then(|_| self.inner.wait());
+		}
+	}
+}
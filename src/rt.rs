@@ -1,167 +1,2546 @@
 use super::*;
-use std::{collections, future::Future, mem, sync, task, thread};
+use crate::{
+	arena::{self, Allocator, Boxed, Counted},
+	primitives::atomic,
+};
+use alloc::{boxed::Box, collections, string::String, sync::Arc, vec::Vec};
+use core::{any::Any, cell, fmt, future::Future, mem, pin::Pin, ptr, task};
 
-/// A minimal single-threaded async runtime
-pub struct Runtime {
-	/// Host thread of the runtime, used for parking and parking
-	host: thread::Thread,
+#[cfg(feature = "std")]
+use std::{thread, time};
 
-	/// Stores tasks to be polled when woken
-	tasks: collections::BTreeMap<usize, tasks::Task>,
+/// Puts the runtime's host to sleep while no tasks are pending.
+///
+/// An [`unpark`](Parker::unpark) issued before [`park`](Parker::park) must cause the next `park` to return immediately.
+pub trait Parker: Send + Sync {
+	/// Blocks the host until [`unpark`](Parker::unpark) is called. Spurious returns are allowed
+	fn park(&self);
+
+	/// Wakes the host from [`park`](Parker::park). Invoked by wakers, possibly from foreign threads or interrupt handlers
+	fn unpark(&self);
+
+	/// Like [`park`](Parker::park), but returns once `timeout` has passed. Parkers without a time source may wait longer
+	fn park_timeout(&self, timeout: core::time::Duration) {
+		let _ = timeout;
+		self.park()
+	}
+}
+
+/// Parks the current thread, the parker must be created on the thread that will run the runtime
+#[cfg(feature = "std")]
+impl Parker for thread::Thread {
+	fn park(&self) {
+		// without threads, the host waits on pending timers instead
+		#[cfg(all(feature = "timers", target_os = "wasi"))]
+		crate::timers::park(None);
+
+		#[cfg(not(all(feature = "timers", target_os = "wasi")))]
+		thread::park()
+	}
+
+	fn unpark(&self) {
+		thread::Thread::unpark(self)
+	}
+
+	fn park_timeout(&self, timeout: time::Duration) {
+		#[cfg(all(feature = "timers", target_os = "wasi"))]
+		crate::timers::park(Some(timeout));
+
+		#[cfg(not(all(feature = "timers", target_os = "wasi")))]
+		thread::park_timeout(timeout)
+	}
+}
+
+/// Creates a parker for a runtime driven by a foreign event loop, such as calloop or glib's `MainContext`, along with a descriptor for that loop to watch.
+///
+/// The descriptor turns readable whenever tasks are woken. The loop should then [`clear`](EventFd::clear) it and call [`Runtime::run_ready`].
+/// Blocking calls like [`block_on`](Runtime::block_on) still park the calling thread, which must be the thread that will run the runtime
+#[cfg(all(feature = "std", unix))]
+pub fn event_fd() -> std::io::Result<(FdParker, EventFd)> {
+	let (reader, writer) = std::os::unix::net::UnixStream::pair()?;
+	reader.set_nonblocking(true)?;
+	writer.set_nonblocking(true)?;
+
+	Ok((FdParker { thread: thread::current(), writer }, EventFd { reader }))
+}
+
+/// Parker signalling an [`EventFd`], from [`event_fd`]
+#[cfg(all(feature = "std", unix))]
+pub struct FdParker {
+	thread: thread::Thread,
+	writer: std::os::unix::net::UnixStream,
+}
+
+#[cfg(all(feature = "std", unix))]
+impl Parker for FdParker {
+	fn park(&self) {
+		thread::park()
+	}
+
+	fn unpark(&self) {
+		use std::io::Write;
+
+		// a full buffer already makes the descriptor readable
+		let _ = (&self.writer).write(&[1]);
+		self.thread.unpark()
+	}
+
+	fn park_timeout(&self, timeout: time::Duration) {
+		thread::park_timeout(timeout)
+	}
+}
+
+/// Descriptor readable while the runtime has woken tasks, from [`event_fd`]
+#[cfg(all(feature = "std", unix))]
+pub struct EventFd {
+	reader: std::os::unix::net::UnixStream,
+}
+
+#[cfg(all(feature = "std", unix))]
+impl EventFd {
+	/// Drains pending signals, call before [`Runtime::run_ready`] so wakes from during the run aren't lost
+	pub fn clear(&self) {
+		use std::io::Read;
+
+		let mut buf = [0; 64];
+		while let Ok(1..) = (&self.reader).read(&mut buf) {}
+	}
+}
+
+#[cfg(all(feature = "std", unix))]
+impl std::os::fd::AsRawFd for EventFd {
+	fn as_raw_fd(&self) -> std::os::fd::RawFd {
+		self.reader.as_raw_fd()
+	}
+}
+
+/// Pins the calling thread to CPU `core`, keeping a latency critical loop off cores busy with other work. Only supported on Linux and Android
+#[cfg(feature = "std")]
+pub fn pin_current_thread(core: usize) -> std::io::Result<()> {
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	{
+		unsafe extern "C" {
+			fn sched_setaffinity(pid: i32, size: usize, mask: *const u64) -> i32;
+		}
+
+		// a `cpu_set_t`, with room for 1024 cores
+		let mut mask = [0u64; 16];
+		*mask.get_mut(core / 64).ok_or(std::io::ErrorKind::InvalidInput)? |= 1 << (core % 64);
+
+		match unsafe { sched_setaffinity(0, mem::size_of_val(&mask), mask.as_ptr()) } {
+			0 => Ok(()),
+			_ => Err(std::io::Error::last_os_error()),
+		}
+	}
+
+	#[cfg(not(any(target_os = "linux", target_os = "android")))]
+	{
+		let _ = core;
+		Err(std::io::ErrorKind::Unsupported.into())
+	}
+}
+
+/// Parker invoking a callback on every unpark, for hosts whose loops are woken by posting messages, like iOS's `CFRunLoop` (signal a source, then wake the loop).
+///
+/// The callback should schedule [`Runtime::run_ready`] on the loop, and may be called from any thread. On Android, `ALooper_addFd` can watch an [`event_fd`] instead
+#[cfg(feature = "std")]
+pub struct CallbackParker {
+	thread: thread::Thread,
+	callback: Box<dyn Fn() + Send + Sync>,
+}
+
+#[cfg(feature = "std")]
+impl CallbackParker {
+	/// Blocking calls like [`block_on`](Runtime::block_on) park the calling thread, so this must be created on the thread that will run the runtime
+	pub fn new(callback: impl Fn() + Send + Sync + 'static) -> Self {
+		CallbackParker { thread: thread::current(), callback: Box::new(callback) }
+	}
+}
+
+#[cfg(feature = "std")]
+impl Parker for CallbackParker {
+	fn park(&self) {
+		thread::park()
+	}
+
+	fn unpark(&self) {
+		(self.callback)();
+		self.thread.unpark()
+	}
+
+	fn park_timeout(&self, timeout: time::Duration) {
+		thread::park_timeout(timeout)
+	}
+}
+
+/// State shared between the runtime, its wakers and monitors
+pub(crate) struct Shared {
+	/// Used to wake the host when tasks are queued
+	parker: Arc<dyn Parker>,
 
 	/// queue of tasks woken by various wakers
-	queue: sync::mpsc::Receiver<usize>,
+	queue: ReadyQueue,
+
+	/// wake times are stored relative to this, so they fit in an atomic
+	#[cfg(feature = "std")]
+	base: time::Instant,
+
+	/// queue of tasks to be dropped before they complete
+	aborts: WakeQueue,
+
+	/// set once the host has been unparked, until the runtime drains its queues
+	notified: atomic::AtomicBool,
+
+	/// Next task id to hand out, ids are never reused
+	next_id: atomic::AtomicUsize,
 
-	/// used to queue tasks to runtime
-	sender: sync::mpsc::Sender<usize>,
+	/// Tasks spawned through a [`Handle`], inserted the next time the runtime polls. `None` once the runtime is dropped
+	#[cfg(feature = "std")]
+	injected: std::sync::Mutex<Option<Vec<Injected>>>,
+
+	/// Flipped by [`Runtime::initiate_shutdown`], never reset
+	shutdown: atomic::AtomicBool,
+
+	/// Waker slots of the live [`ShutdownSignal`]s polled so far, removed as they're dropped
+	shutdown_waiters: tasks::Lock<Vec<Counted<WakerSlot>>>,
+
+	/// Source of the runtime's own allocations, see [`Builder::allocator`]
+	allocator: Allocator,
 }
 
-impl Runtime {
-	/// Instantiate a new Runtime
-	pub fn new() -> Self {
-		let (sender, queue) = sync::mpsc::channel();
-		let host = thread::current();
+/// Waker of a [`ShutdownSignal`], replaced whenever the signal is polled by another task
+type WakerSlot = tasks::Lock<Option<task::Waker>>;
 
-		// start sleeping subroutine
-		#[cfg(feature = "timers")]
-		crate::timers::init();
+/// Inserts a task spawned from another thread, runs on the runtime's thread
+#[cfg(feature = "std")]
+type Injected = Boxed<dyn FnMut(&Runtime) + Send>;
 
-		Self { queue, host, sender, tasks: collections::BTreeMap::new() }
+impl Shared {
+	/// Queues a task for cancellation, it's dropped the next time the runtime polls
+	pub(crate) fn abort(&self, id: tasks::TaskId) {
+		self.aborts.push(id);
+		self.notify();
 	}
 
-	/// Blocks execution, continuously polling tasks and waiting for `fut` to complete
-	pub fn block_on<T: 'static, F: Future<Output = T> + 'static>(&mut self, fut: F) -> T {
-		let task_id = self.tasks.len();
-		let (results_tx, results_rx) = oneshot::channel();
+	/// Completes every [`ShutdownSignal`], returns `false` if shutdown was already initiated
+	fn initiate_shutdown(&self) -> bool {
+		if self.shutdown.swap(true, atomic::Ordering::AcqRel) {
+			return false;
+		}
 
-		let waker = self.create_waker(task_id);
-		waker.wake_by_ref(); // poll once
+		let waiters = self.shutdown_waiters.with(mem::take);
+		for slot in waiters {
+			if let Some(waker) = slot.with(Option::take) {
+				waker.wake()
+			}
+		}
 
-		let inner = Box::pin(async move {
-			let res = fut.await;
+		true
+	}
 
-			if let Err(_) = results_tx.send(res) {
-				panic!("Unable to send results for completed task: {}", task_id)
-			};
-		});
+	/// Unparks the host, unless a previous wake already did so since the last poll. Collapses bursts of wakes into a single unpark
+	fn notify(&self) {
+		if !self.notified.swap(true, atomic::Ordering::AcqRel) {
+			self.parker.unpark();
+		}
+	}
+
+	fn next_task_id(&self) -> Result<tasks::TaskId, RuntimeError> {
+		match self.next_id.fetch_update(atomic::Ordering::Relaxed, atomic::Ordering::Relaxed, |id| id.checked_add(1)) {
+			Ok(id) => Ok(tasks::TaskId(id)),
+			Err(_) => Err(RuntimeError::TaskIdsExhausted),
+		}
+	}
+}
+
+/// Lock-free stack of woken task ids, drained in wake order by the runtime
+struct WakeQueue {
+	head: atomic::AtomicPtr<WakeNode>,
+	/// nodes are allocated from here
+	allocator: Allocator,
+}
+
+struct WakeNode {
+	wake: Wake,
+	next: *mut WakeNode,
+}
+
+/// A queued task, along with when it was woken
+struct Wake {
+	id: tasks::TaskId,
+	#[cfg(feature = "std")]
+	at: time::Instant,
+	/// woken by a high priority timer, polled ahead of the rest of its batch
+	#[cfg(feature = "timers")]
+	urgent: bool,
+}
+
+impl WakeQueue {
+	fn push(&self, id: tasks::TaskId) {
+		let wake = Wake {
+			id,
+			#[cfg(feature = "std")]
+			at: time::Instant::now(),
+			#[cfg(feature = "timers")]
+			urgent: false,
+		};
 
-		self.tasks.insert(task_id, tasks::Task { inner, waker, monitor_waker: None });
+		let node = Boxed::into_raw(Boxed::new_in(WakeNode { wake, next: ptr::null_mut() }, self.allocator)).as_ptr();
+		let mut head = self.head.load(atomic::Ordering::Relaxed);
 
 		loop {
-			self.poll();
+			unsafe { (*node).next = head };
 
-			match results_rx.try_recv() {
-				Ok(r) => return r,
-				Err(oneshot::TryRecvError::Empty) => {}
-				Err(oneshot::TryRecvError::Disconnected) => unreachable!("Task was dropped during execution"),
+			match self.head.compare_exchange_weak(head, node, atomic::Ordering::Release, atomic::Ordering::Relaxed) {
+				Ok(_) => break,
+				Err(current) => head = current,
 			}
+		}
+	}
 
-			// wait for external events to wake up thread
-			thread::park();
+	/// Takes every task queued so far, oldest first
+	fn drain(&self) -> Vec<Wake> {
+		let mut node = self.head.swap(ptr::null_mut(), atomic::Ordering::Acquire);
+		let mut wakes = Vec::new();
+
+		while !node.is_null() {
+			let next = Boxed::into_inner(unsafe { Boxed::from_raw(ptr::NonNull::new_unchecked(node), self.allocator) });
+			wakes.push(next.wake);
+			node = next.next;
 		}
+
+		wakes.reverse();
+		wakes
 	}
+}
 
-	/// Spawns a future as a `Task`, and returns a [`TaskMonitor`](tasks::TaskMonitor)
-	pub fn spawn<T: 'static, F: Future<Output = T> + 'static>(&mut self, fut: F) -> tasks::TaskMonitor<T> {
-		let task_id = self.tasks.len();
-		let (result_tx, result_rx) = oneshot::channel();
-		let (waker_tx, waker_rx) = oneshot::channel();
+impl Drop for WakeQueue {
+	fn drop(&mut self) {
+		self.drain();
+	}
+}
 
-		// poll once, and initialize task
-		let waker = self.create_waker(task_id);
-		waker.wake_by_ref();
+/// State behind a task's waker, allocated once per waker created by the runtime, never per clone
+struct WakerData {
+	shared: Counted<Shared>,
+	id: tasks::TaskId,
+	/// set while in the ready queue, wakes in the meantime are collapsed into the queued one
+	queued: atomic::AtomicBool,
+	/// next node in the ready queue, only written while being pushed
+	next: atomic::AtomicPtr<WakerData>,
+	/// nanoseconds since [`Shared::base`], when the task was queued
+	#[cfg(feature = "std")]
+	woken_at: atomic::AtomicU64,
+	/// set by [`wake_prioritized`], taken along with the wake once drained
+	#[cfg(feature = "timers")]
+	urgent: atomic::AtomicBool,
+}
 
-		let inner = Box::pin(async move {
-			let res = fut.await;
+impl WakerData {
+	/// Data behind `waker`, if it was created by a runtime
+	#[cfg(feature = "std")]
+	fn of(waker: &task::Waker) -> Option<&Self> {
+		arena::waker_data(waker)
+	}
+}
 
-			if let Err(_) = result_tx.send(res) {
-				panic!("Unable to send results for completed task: {}", task_id)
-			};
-		});
+impl arena::Wake for WakerData {
+	fn wake_by_ref(&self) {
+		// queue task and unpark host, in that order so the runtime can't park on an empty queue
+		if self.shared.queue.push(self) {
+			self.shared.notify();
+		}
+	}
+}
+
+/// Deterministic generator behind [`Builder::shuffle`], splitmix64
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+	pub(crate) fn new(seed: u64) -> Self {
+		Rng(seed)
+	}
 
-		let task = tasks::Task { inner, waker, monitor_waker: Some(waker_rx) };
-		self.tasks.insert(task_id, task);
+	pub(crate) fn next(&mut self) -> u64 {
+		self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
 
-		tasks::TaskMonitor { result_rx, waker_tx: Some(waker_tx) }
+		let mut z = self.0;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		z ^ (z >> 31)
 	}
 
-	fn create_waker(&mut self, id: usize) -> task::Waker {
-		static WAKER_VTABLE: task::RawWakerVTable = task::RawWakerVTable::new(clone, wake, wake_by_ref, drop);
-		type WakerData = (sync::mpsc::Sender<usize>, usize, thread::Thread);
+	/// Fisher-Yates shuffle of `items`
+	pub(crate) fn shuffle<T>(&mut self, items: &mut [T]) {
+		for i in (1..items.len()).rev() {
+			let j = (self.next() % (i as u64 + 1)) as usize;
+			items.swap(i, j);
+		}
+	}
+}
 
-		// quartet of waker methods
-		unsafe fn clone(data: *const ()) -> task::RawWaker {
-			let data = data as *const WakerData;
-			let (sender, id, thread) = unsafe { data.as_ref() }.expect("Got NULL as waker data");
+/// Intrusive lock-free stack of woken tasks, whose nodes are the wakers themselves. Queueing a task takes a few atomic operations, and no allocation
+struct ReadyQueue {
+	head: atomic::AtomicPtr<WakerData>,
+}
 
-			// create a new clone to avoid a double-free
-			let inner: Box<WakerData> = Box::new((sender.clone(), *id, thread.clone()));
-			let leak = Box::leak(inner);
+impl ReadyQueue {
+	/// Head of a closed queue, never the address of a live waker
+	const CLOSED: *mut WakerData = ptr::dangling_mut();
 
-			task::RawWaker::new(leak as *const WakerData as *const (), &WAKER_VTABLE)
+	/// Queues the waker's task, returns `false` if it was already queued or the queue is closed
+	fn push(&self, data: &WakerData) -> bool {
+		if data.queued.swap(true, atomic::Ordering::AcqRel) {
+			return false;
 		}
 
-		unsafe fn wake(data: *const ()) {
-			unsafe {
-				wake_by_ref(data);
-				drop(data);
+		#[cfg(feature = "std")]
+		data.woken_at.store(data.shared.base.elapsed().as_nanos() as u64, atomic::Ordering::Relaxed);
+
+		// the queue keeps the waker alive until drained
+		let node = data as *const WakerData;
+		unsafe { Counted::increment_strong_count(node) };
+
+		let mut head = self.head.load(atomic::Ordering::Relaxed);
+
+		loop {
+			if head == Self::CLOSED {
+				unsafe { Counted::decrement_strong_count(node) };
+				return false;
 			}
+
+			data.next.store(head, atomic::Ordering::Relaxed);
+
+			match self.head.compare_exchange_weak(head, node as *mut WakerData, atomic::Ordering::Release, atomic::Ordering::Relaxed) {
+				Ok(_) => return true,
+				Err(current) => head = current,
+			}
+		}
+	}
+
+	/// Takes every task queued so far, oldest first
+	fn drain(&self) -> Vec<Wake> {
+		let mut node = self.head.swap(ptr::null_mut(), atomic::Ordering::Acquire);
+		let mut wakes = Vec::new();
+
+		while !node.is_null() {
+			let data = unsafe { Counted::from_raw(node) };
+
+			// read before clearing the flag, a new push overwrites it
+			node = data.next.load(atomic::Ordering::Relaxed);
+
+			#[cfg(feature = "timers")]
+			let urgent = data.urgent.swap(false, atomic::Ordering::Relaxed);
+			data.queued.store(false, atomic::Ordering::Release);
+
+			wakes.push(Wake {
+				id: data.id,
+				#[cfg(feature = "std")]
+				at: data.shared.base + time::Duration::from_nanos(data.woken_at.load(atomic::Ordering::Relaxed)),
+				#[cfg(feature = "timers")]
+				urgent,
+			});
 		}
 
-		unsafe fn wake_by_ref(data: *const ()) {
-			let data = data as *const WakerData;
-			let data = unsafe { data.as_ref() }.expect("Got NULL as waker data");
+		wakes.reverse();
+		wakes
+	}
 
-			let (sender, id, thread) = data;
+	/// Drops every queued waker and turns away later pushes. Queued wakers keep [`Shared`] alive, so the runtime closes the queue once dropped
+	fn close(&self) {
+		let mut node = self.head.swap(Self::CLOSED, atomic::Ordering::Acquire);
 
-			// unpark thread and queue task
-			thread.unpark();
-			sender.send(*id).unwrap();
+		while !node.is_null() && node != Self::CLOSED {
+			let data = unsafe { Counted::from_raw(node) };
+			node = data.next.load(atomic::Ordering::Relaxed);
 		}
+	}
+}
 
-		unsafe fn drop(data: *const ()) {
-			let data = data as *const WakerData as *mut WakerData;
-			let data = unsafe { data.as_mut() }.expect("Got NULL as waker data");
+impl Drop for ReadyQueue {
+	fn drop(&mut self) {
+		self.close();
+	}
+}
 
-			unsafe {
-				let data: Box<WakerData> = Box::from_raw(data);
-				mem::drop(data);
-			}
+/// Conditions that stop the runtime from completing a call, returned by its fallible APIs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RuntimeError {
+	/// A task panicked while being polled, it was dropped from the runtime
+	Panicked(tasks::TaskId),
+	/// The future passed to [`try_block_on`](Runtime::try_block_on) was dropped before completing
+	Dropped(tasks::TaskId),
+	/// Every task id has been handed out, ids are never reused
+	TaskIdsExhausted,
+	/// The runtime behind a [`Handle`] was dropped
+	Closed,
+	/// The runtime already holds as many tasks as allowed by [`Builder::max_tasks`]
+	TaskLimitReached,
+}
+
+impl fmt::Display for RuntimeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			RuntimeError::Panicked(id) => write!(f, "Task {} panicked while being polled", id),
+			RuntimeError::Dropped(id) => write!(f, "Task {} was dropped during execution", id),
+			RuntimeError::TaskIdsExhausted => write!(f, "Runtime ran out of task ids"),
+			RuntimeError::TaskLimitReached => write!(f, "Runtime reached its task limit"),
+			RuntimeError::Closed => write!(f, "Runtime was dropped"),
 		}
+	}
+}
 
-		let data: WakerData = (self.sender.clone(), id, self.host.clone());
-		let data = Box::leak(Box::new(data));
+impl core::error::Error for RuntimeError {}
 
-		// simple waker that adds id to vector
-		unsafe { task::Waker::new(data as *const WakerData as *const (), &WAKER_VTABLE) }
+/// Payload of a panic caught while polling a task
+type Panic = Box<dyn Any + Send>;
+
+/// What the runtime does with a task that panics while being polled, see [`Builder::panic_policy`]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+	/// The panic is resumed from [`block_on`](Runtime::block_on), or returned by [`try_block_on`](Runtime::try_block_on) as [`RuntimeError::Panicked`]
+	#[default]
+	Propagate,
+	/// The task is removed and its monitor resolves to `None`, while other tasks keep running. The panic is still printed by the panic hook,
+	/// and reported to [`on_panic`](Builder::on_panic) and the [event stream](Runtime::events). A future passed to `block_on` still propagates its own panic
+	Isolate,
+	/// The process is aborted, once the panic is reported to [`on_panic`](Builder::on_panic)
+	Abort,
+}
+
+/// Waker of a future driven by [`block_on_pinned`](Runtime::block_on_pinned), which lives outside of the task map
+struct RootWaker {
+	woken: atomic::AtomicBool,
+	shared: Counted<Shared>,
+}
+
+impl arena::Wake for RootWaker {
+	fn wake_by_ref(&self) {
+		self.woken.store(true, atomic::Ordering::Release);
+		self.shared.notify();
 	}
+}
 
-	/// must be called manually to progress execution of tasks
-	fn poll(&mut self) {
-		for next in self.queue.try_iter() {
-			// tasks queued during this block will be processed in a later iteration
-			// meaning if `poll` returns, there aren't any tasks pending or trying to self wake
-			let mut remove = false;
+/// A minimal single-threaded async runtime
+pub struct Runtime {
+	/// Stores tasks to be polled when woken
+	tasks: cell::RefCell<collections::BTreeMap<tasks::TaskId, tasks::Task>>,
 
-			if let Some(task) = self.tasks.get_mut(&next) {
-				let fut = task.inner.as_mut();
-				let mut context = task::Context::from_waker(&task.waker);
+	/// Woken tasks yet to be polled, kept here so nested polls continue the current batch
+	ready: cell::RefCell<collections::VecDeque<Wake>>,
 
-				if let task::Poll::Ready(_) = fut.poll(&mut context) {
-					if let Some(waker_rx) = task.monitor_waker.take() {
-						if let Ok(waker) = waker_rx.try_recv() {
-							waker.wake()
-						}
-					}
+	/// Parker and wake queue, shared with every waker
+	shared: Counted<Shared>,
+
+	/// Callbacks invoked as tasks move through their lifecycle
+	hooks: cell::RefCell<Hooks>,
+
+	/// Idle strategy, run before parking
+	idle: cell::RefCell<Idle>,
+
+	/// Collected across all tasks, see [`Runtime::metrics`]
+	#[cfg(feature = "std")]
+	metrics: cell::RefCell<crate::metrics::Metrics>,
+
+	/// Maximum number of live tasks, spawning beyond it fails
+	max_tasks: Option<usize>,
 
-					// mark task for removal
-					remove = true;
+	/// Polls each task at most once per batch, strictly in wake order, see [`Builder::fifo`]
+	fifo: bool,
+
+	/// Share of polls given to each task group, see [`Builder::group_weight`]
+	weights: collections::BTreeMap<String, u32>,
+
+	/// Wakes drained at once beyond which they're sorted by task id, see [`Builder::batch_threshold`]
+	batch_threshold: usize,
+
+	/// Seed and generator shuffling each batch of wakes, see [`Builder::shuffle`]
+	shuffle: Option<(u64, cell::Cell<Rng>)>,
+
+	/// Handling of panicking tasks, see [`Builder::panic_policy`]
+	#[cfg(feature = "std")]
+	panic_policy: PanicPolicy,
+
+	/// Futures registered through [`Runtime::on_shutdown`], and how long dropping the runtime waits for them
+	#[cfg(feature = "std")]
+	cleanups: cell::RefCell<Vec<Pin<Boxed<dyn Future<Output = ()>>>>>,
+	#[cfg(feature = "std")]
+	shutdown_timeout: time::Duration,
+
+	/// Recycles memory of completed tasks' futures
+	pool: Counted<tasks::Pool>,
+
+	/// Queue of the stream returned by [`Runtime::events`], if one is alive
+	#[cfg(feature = "std")]
+	events: cell::RefCell<alloc::rc::Weak<EventQueue>>,
+
+	/// Task whose future is being polled, edges reported through [`record_await`] are attached to it
+	#[cfg(feature = "std")]
+	polling: cell::Cell<Option<tasks::TaskId>>,
+
+	/// When the runtime was built, see [`Snapshot::uptime`]
+	#[cfg(feature = "std")]
+	started: time::Instant,
+
+	/// Tasks waiting in [`spawn_when_available`] for a slot to free up
+	#[cfg(feature = "std")]
+	capacity_waiters: cell::RefCell<Vec<task::Waker>>,
+
+	/// Invoked with polls that took longer than the threshold
+	#[cfg(feature = "std")]
+	slow_poll: cell::RefCell<Option<(time::Duration, SlowPollHook)>>,
+
+	/// Invoked with tasks that woke themselves in as many consecutive polls as the threshold
+	#[cfg(feature = "std")]
+	busy_wake: cell::RefCell<Option<(u32, TaskHook)>>,
+
+	/// Decorates the waker each task is polled with, see [`Builder::wrap_wakers`]
+	wrap_wakers: cell::RefCell<Option<WakerHook>>,
+
+	/// Set up the thread's timers, released once dropped
+	#[cfg(feature = "timers")]
+	timers: bool,
+
+	/// Tracks the task being polled, for the watchdog thread
+	#[cfg(all(feature = "std", not(target_os = "wasi")))]
+	heartbeat: Option<Arc<Heartbeat>>,
+}
+
+#[cfg(feature = "std")]
+type SlowPollHook = Box<dyn FnMut(tasks::TaskId, time::Duration)>;
+
+#[cfg(all(feature = "std", not(target_os = "wasi")))]
+type StallHook = Box<dyn FnMut(Stall) + Send>;
+
+/// A poll blocking the runtime, reported by the watchdog set with [`Builder::on_stall`]
+#[cfg(all(feature = "std", not(target_os = "wasi")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stall {
+	/// Task whose poll hasn't returned yet
+	pub task: tasks::TaskId,
+	/// How long the poll has been running for
+	pub stuck_for: time::Duration,
+}
+
+/// Task currently being polled, shared with the watchdog thread
+#[cfg(all(feature = "std", not(target_os = "wasi")))]
+struct Heartbeat {
+	base: time::Instant,
+	/// id of the polled task plus one, zero while no task is being polled
+	task: atomic::AtomicUsize,
+	/// nanoseconds since `base`, when the current poll started
+	started: atomic::AtomicU64,
+}
+
+#[cfg(all(feature = "std", not(target_os = "wasi")))]
+impl Heartbeat {
+	/// Spawns the watchdog thread, it exits once the runtime drops the heartbeat
+	fn watch(interval: time::Duration, mut hook: StallHook) -> Arc<Self> {
+		let heartbeat = Arc::new(Heartbeat { base: time::Instant::now(), task: atomic::AtomicUsize::new(0), started: atomic::AtomicU64::new(0) });
+		let weak = Arc::downgrade(&heartbeat);
+
+		thread::spawn(move || {
+			let mut reported = None;
+
+			while let Some(heartbeat) = weak.upgrade() {
+				let (task, started) = (heartbeat.task.load(atomic::Ordering::Acquire), heartbeat.started.load(atomic::Ordering::Acquire));
+				let stuck_for = heartbeat.base.elapsed().saturating_sub(time::Duration::from_nanos(started));
+				drop(heartbeat);
+
+				if task != 0 && stuck_for > interval && reported != Some((task, started)) {
+					hook(Stall { task: tasks::TaskId(task - 1), stuck_for });
+					reported = Some((task, started));
 				}
-			}
 
-			if remove {
-				self.tasks.remove(&next);
+				thread::sleep(interval / 4);
 			}
+		});
+
+		heartbeat
+	}
+
+	/// Marks the start of a poll, returning the state to restore once it ends
+	fn enter(&self, id: tasks::TaskId) -> (usize, u64) {
+		// the start time is updated first, so the watchdog never pairs a new task with an old start
+		let started = self.started.swap(self.base.elapsed().as_nanos() as u64, atomic::Ordering::AcqRel);
+		(self.task.swap(id.0 + 1, atomic::Ordering::AcqRel), started)
+	}
+
+	fn exit(&self, (task, started): (usize, u64)) {
+		self.task.store(task, atomic::Ordering::Release);
+		self.started.store(started, atomic::Ordering::Release);
+	}
+}
+
+/// When a newly spawned task is first polled
+enum Start {
+	Now,
+	/// handed to the sleeping thread, which wakes it once due
+	#[cfg(feature = "timers")]
+	At(time::Instant),
+}
+
+/// Name and group a task is spawned with
+#[derive(Default)]
+struct Labels {
+	name: Option<String>,
+	group: Option<String>,
+}
+
+/// Called with a task's id and name
+type TaskHook = Box<dyn FnMut(tasks::TaskId, Option<&str>)>;
+
+/// Called with a task's id and waker, returning the waker to poll the task with
+type WakerHook = Box<dyn FnMut(tasks::TaskId, task::Waker) -> task::Waker>;
+
+/// What the host does when it runs out of work, before parking
+#[derive(Default)]
+struct Idle {
+	spins: u32,
+	#[cfg(feature = "std")]
+	yields: u32,
+	hook: Option<Box<dyn FnMut()>>,
+}
+
+#[derive(Default)]
+struct Hooks {
+	on_spawn: Option<TaskHook>,
+	on_complete: Option<TaskHook>,
+	#[cfg(feature = "std")]
+	on_panic: Option<TaskHook>,
+}
+
+/// Configures a [`Runtime`] before creating it
+pub struct Builder {
+	parker: Option<Arc<dyn Parker>>,
+	hooks: Hooks,
+	max_tasks: Option<usize>,
+	fifo: bool,
+	weights: collections::BTreeMap<String, u32>,
+	batch_threshold: usize,
+	shuffle: Option<u64>,
+	#[cfg(feature = "std")]
+	panic_policy: PanicPolicy,
+	#[cfg(feature = "std")]
+	shutdown_timeout: time::Duration,
+	pool_capacity: usize,
+	allocator: Allocator,
+	idle: Idle,
+	#[cfg(feature = "std")]
+	slow_poll: Option<(time::Duration, SlowPollHook)>,
+	#[cfg(feature = "std")]
+	busy_wake: Option<(u32, TaskHook)>,
+	wrap_wakers: Option<WakerHook>,
+	#[cfg(all(feature = "std", not(target_os = "wasi")))]
+	watchdog: Option<(time::Duration, StallHook)>,
+	#[cfg(feature = "std")]
+	core: Option<usize>,
+	#[cfg(feature = "timers")]
+	shared_timers: bool,
+	#[cfg(feature = "timers")]
+	timers: bool,
+}
+
+impl Default for Builder {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Builder {
+	/// Starts with the default configuration
+	pub fn new() -> Self {
+		Builder {
+			parker: None,
+			hooks: Hooks::default(),
+			max_tasks: None,
+			fifo: false,
+			weights: collections::BTreeMap::new(),
+			batch_threshold: 256,
+			shuffle: None,
+			#[cfg(feature = "std")]
+			panic_policy: PanicPolicy::default(),
+			#[cfg(feature = "std")]
+			shutdown_timeout: time::Duration::from_secs(5),
+			pool_capacity: 64,
+			allocator: Allocator::GLOBAL,
+			idle: Idle::default(),
+			#[cfg(feature = "std")]
+			slow_poll: None,
+			#[cfg(feature = "std")]
+			busy_wake: None,
+			wrap_wakers: None,
+			#[cfg(all(feature = "std", not(target_os = "wasi")))]
+			watchdog: None,
+			#[cfg(feature = "std")]
+			core: None,
+			#[cfg(feature = "timers")]
+			shared_timers: false,
+			#[cfg(feature = "timers")]
+			timers: true,
 		}
 	}
+
+	/// Uses `parker` to wait for wakes while idle. Required without `std`, otherwise the building thread is parked
+	pub fn parker<P: Parker + 'static>(mut self, parker: P) -> Self {
+		self.parker = Some(Arc::new(parker));
+		self
+	}
+
+	/// Limits the number of live tasks, including the future driven by [`block_on`](Runtime::block_on).
+	///
+	/// Spawning beyond the limit fails with [`RuntimeError::TaskLimitReached`], [`spawn_when_available`] waits for a slot instead
+	pub fn max_tasks(mut self, limit: usize) -> Self {
+		self.max_tasks = Some(limit);
+		self
+	}
+
+	/// Guarantees tasks are polled in the order they were woken, so runs are reproducible across releases. Scheduling optimizations that reorder tasks are disabled.
+	///
+	/// Tasks woken together are polled in wake order, each once even if woken repeatedly, and tasks woken while polling wait for the next batch.
+	/// Spawned tasks are first polled in spawn order, and tasks spawned through a [`Handle`] are inserted in the order they were spawned.
+	/// Wakes from other threads are ordered by when they reach the runtime
+	pub fn fifo(mut self, enabled: bool) -> Self {
+		self.fifo = enabled;
+		self
+	}
+
+	/// Interleaves polls of woken tasks between [groups](Runtime::spawn_in_group) proportionally to their weights, instead of polling them in wake order.
+	///
+	/// E.g. weights of 4 and 1 poll four tasks of the first group for every one of the second. Groups without a weight, and tasks outside any group, weigh 1.
+	/// Ignored in [`fifo`](Builder::fifo) mode
+	pub fn group_weight(mut self, group: impl Into<String>, weight: u32) -> Self {
+		self.weights.insert(group.into(), weight.max(1));
+		self
+	}
+
+	/// Once at least `wakes` are drained at once, they're sorted by task id and repeats are dropped, so task lookups walk the task map in order. Defaults to 256.
+	///
+	/// Tasks in such a burst aren't polled in wake order. Ignored in [`fifo`](Builder::fifo) mode
+	pub fn batch_threshold(mut self, wakes: usize) -> Self {
+		self.batch_threshold = wakes;
+		self
+	}
+
+	/// Simulation mode for tests, shuffling each batch of wakes with a generator seeded by `seed`.
+	///
+	/// Shakes out assumptions on the order tasks run in, while the same seed reproduces the same run. The seed is printed to stderr if the runtime is dropped while panicking. Ignored in [`fifo`](Builder::fifo) mode.
+	/// Timers sharing a deadline are fired by a sleeping thread serving every runtime on the thread, they're shuffled through [`timers::set_shuffle`](crate::timers::set_shuffle)
+	pub fn shuffle(mut self, seed: u64) -> Self {
+		self.shuffle = Some(seed);
+		self
+	}
+
+	/// Keeps up to `blocks` freed task allocations, reusing them for later spawns of similarly sized futures. Defaults to 64, zero disables pooling.
+	///
+	/// Futures of at most 64 bytes are always stored in shared slabs instead, without an allocation of their own
+	pub fn pool_capacity(mut self, blocks: usize) -> Self {
+		self.pool_capacity = blocks;
+		self
+	}
+
+	/// Allocates the runtime's per-task state from `allocator` instead of the global allocator, e.g. an arena sized for a game's entities.
+	///
+	/// Covers task futures, wakers, monitors, result channels, queued wakes, shutdown cleanups and scope children. The runtime's own collections, task names,
+	/// hooks and the parker given to the builder, the event stream, as well as the watchdog, timers and IO, still use the global allocator
+	pub fn allocator(mut self, allocator: &'static (dyn core::alloc::GlobalAlloc + Sync)) -> Self {
+		self.allocator = Allocator::new(allocator);
+		self
+	}
+
+	/// Busy-waits for up to `spins` iterations for a wake before parking, trading CPU time for wake latency. Defaults to zero, parking right away
+	pub fn idle_spins(mut self, spins: u32) -> Self {
+		self.idle.spins = spins;
+		self
+	}
+
+	/// Yields the thread to the OS up to `yields` times while waiting for a wake, after spinning and before parking. Defaults to zero
+	#[cfg(feature = "std")]
+	pub fn idle_yields(mut self, yields: u32) -> Self {
+		self.idle.yields = yields;
+		self
+	}
+
+	/// Calls `hook` each time the runtime runs out of work, before it spins or parks. Useful to run background work in idle time
+	pub fn on_idle(mut self, hook: impl FnMut() + 'static) -> Self {
+		self.idle.hook = Some(Box::new(hook));
+		self
+	}
+
+	/// Calls `hook` whenever a task is spawned, including futures passed to [`block_on`](Runtime::block_on). Tasks the hook spawns itself, e.g. through [`Runtime::with_current`], don't reach it
+	pub fn on_spawn(mut self, hook: impl FnMut(tasks::TaskId, Option<&str>) + 'static) -> Self {
+		self.hooks.on_spawn = Some(Box::new(hook));
+		self
+	}
+
+	/// Calls `hook` whenever a task runs to completion. Aborted tasks don't complete
+	pub fn on_complete(mut self, hook: impl FnMut(tasks::TaskId, Option<&str>) + 'static) -> Self {
+		self.hooks.on_complete = Some(Box::new(hook));
+		self
+	}
+
+	/// Calls `hook` whenever a task panics while being polled, before the panic is resumed or returned by [`try_block_on`](Runtime::try_block_on)
+	#[cfg(feature = "std")]
+	pub fn on_panic(mut self, hook: impl FnMut(tasks::TaskId, Option<&str>) + 'static) -> Self {
+		self.hooks.on_panic = Some(Box::new(hook));
+		self
+	}
+
+	/// Chooses whether a panicking task is propagated to the caller of [`block_on`](Runtime::block_on), removed alone, or aborts the process. Defaults to [`PanicPolicy::Propagate`]
+	#[cfg(feature = "std")]
+	pub fn panic_policy(mut self, policy: PanicPolicy) -> Self {
+		self.panic_policy = policy;
+		self
+	}
+
+	/// How long dropping the runtime waits for [`on_shutdown`](Runtime::on_shutdown) cleanups that haven't run yet. Defaults to 5 seconds
+	#[cfg(feature = "std")]
+	pub fn shutdown_timeout(mut self, timeout: time::Duration) -> Self {
+		self.shutdown_timeout = timeout;
+		self
+	}
+
+	/// Calls `hook` with the task's id and poll duration, whenever a single poll takes longer than `threshold`
+	#[cfg(feature = "std")]
+	pub fn on_slow_poll(mut self, threshold: time::Duration, hook: impl FnMut(tasks::TaskId, time::Duration) + 'static) -> Self {
+		self.slow_poll = Some((threshold, Box::new(hook)));
+		self
+	}
+
+	/// Calls `hook` when a task wakes itself in `polls` consecutive polls, a sign it's spinning without waiting on anything.
+	///
+	/// Each streak is reported once. Tasks that yield in a loop, or run out of their [`coop`](crate::coop) budget, count as woken by themselves too
+	#[cfg(feature = "std")]
+	pub fn on_busy_wake(mut self, polls: u32, hook: impl FnMut(tasks::TaskId, Option<&str>) + 'static) -> Self {
+		self.busy_wake = Some((polls.max(1), Box::new(hook)));
+		self
+	}
+
+	/// Polls each task with the waker returned by `wrap`, called once per task with the waker the runtime created for it. Lets tooling count wakes per source, attach tracing context or rate-limit noisy wakers.
+	///
+	/// High priority timers only mark wakers created by the runtime, tasks polled with decorated wakers are woken by them as ordinary tasks
+	pub fn wrap_wakers(mut self, wrap: impl FnMut(tasks::TaskId, task::Waker) -> task::Waker + 'static) -> Self {
+		self.wrap_wakers = Some(Box::new(wrap));
+		self
+	}
+
+	/// Starts a watchdog thread, calling `hook` from it whenever a single poll has been blocking the runtime for longer than `interval`.
+	///
+	/// Each stall is reported once, while it's still ongoing
+	#[cfg(all(feature = "std", not(target_os = "wasi")))]
+	pub fn on_stall(mut self, interval: time::Duration, hook: impl FnMut(Stall) + Send + 'static) -> Self {
+		self.watchdog = Some((interval, Box::new(hook)));
+		self
+	}
+
+	/// Pins the thread building the runtime, which should be the one running it, to CPU `core`. Best effort, see [`pin_current_thread`]
+	#[cfg(feature = "std")]
+	pub fn pin_to_core(mut self, core: usize) -> Self {
+		self.core = Some(core);
+		self
+	}
+
+	/// Drives the runtime's timers from a process-wide sleeping thread, instead of one per runtime thread. See [`init_shared`](crate::timers::init_shared)
+	#[cfg(feature = "timers")]
+	pub fn shared_timers(mut self) -> Self {
+		self.shared_timers = true;
+		self
+	}
+
+	/// Leaves the thread's timers alone, for embedders bringing their own time source. Timers then panic unless set up with [`init_with_clock`](crate::timers::init_with_clock) or the like
+	#[cfg(feature = "timers")]
+	pub fn without_timers(mut self) -> Self {
+		self.timers = false;
+		self
+	}
+
+	/// Creates the runtime, starting any subroutines enabled by features
+	pub fn build(self) -> Runtime {
+		let parker = match self.parker {
+			Some(parker) => parker,
+			#[cfg(feature = "std")]
+			None => Arc::new(crate::primitives::thread::current()),
+			#[cfg(not(feature = "std"))]
+			None => panic!("A Parker must be provided to build a Runtime without std"),
+		};
+
+		let allocator = self.allocator;
+		let queue = ReadyQueue { head: atomic::AtomicPtr::new(ptr::null_mut()) };
+		let aborts = WakeQueue { head: atomic::AtomicPtr::new(ptr::null_mut()), allocator };
+		let shared = Shared {
+			parker,
+			queue,
+			#[cfg(feature = "std")]
+			base: time::Instant::now(),
+			aborts,
+			notified: atomic::AtomicBool::new(false),
+			next_id: atomic::AtomicUsize::new(0),
+			#[cfg(feature = "std")]
+			injected: std::sync::Mutex::new(Some(Vec::new())),
+			shutdown: atomic::AtomicBool::new(false),
+			shutdown_waiters: tasks::Lock::new(Vec::new()),
+			allocator,
+		};
+
+		// set up sleeping subroutine, its thread is spawned by the first timer
+		#[cfg(feature = "timers")]
+		if self.timers {
+			crate::timers::acquire(self.shared_timers)
+		}
+
+		// failures leave the thread where it was, callers wanting them can pin the thread themselves
+		#[cfg(feature = "std")]
+		if let Some(core) = self.core {
+			let _ = pin_current_thread(core);
+		}
+
+		// the reactor thread itself is spawned by the first `Async`
+		#[cfg(all(feature = "io", unix))]
+		crate::io::acquire();
+
+		Runtime {
+			shared: Counted::new_in(shared, allocator),
+			tasks: cell::RefCell::new(collections::BTreeMap::new()),
+			ready: cell::RefCell::new(collections::VecDeque::new()),
+			hooks: cell::RefCell::new(self.hooks),
+			idle: cell::RefCell::new(self.idle),
+			#[cfg(feature = "std")]
+			metrics: cell::RefCell::new(Default::default()),
+			max_tasks: self.max_tasks,
+			fifo: self.fifo,
+			weights: self.weights,
+			batch_threshold: self.batch_threshold,
+			shuffle: self.shuffle.map(|seed| (seed, cell::Cell::new(Rng::new(seed)))),
+			#[cfg(feature = "std")]
+			panic_policy: self.panic_policy,
+			#[cfg(feature = "std")]
+			cleanups: cell::RefCell::new(Vec::new()),
+			#[cfg(feature = "std")]
+			shutdown_timeout: self.shutdown_timeout,
+			pool: Counted::new_in(tasks::Pool::new(self.pool_capacity, allocator), allocator),
+			#[cfg(feature = "std")]
+			events: cell::RefCell::new(alloc::rc::Weak::new()),
+			#[cfg(feature = "std")]
+			polling: cell::Cell::new(None),
+			#[cfg(feature = "std")]
+			started: time::Instant::now(),
+			#[cfg(feature = "std")]
+			capacity_waiters: cell::RefCell::new(Vec::new()),
+			#[cfg(feature = "std")]
+			slow_poll: cell::RefCell::new(self.slow_poll),
+			#[cfg(feature = "std")]
+			busy_wake: cell::RefCell::new(self.busy_wake),
+			wrap_wakers: cell::RefCell::new(self.wrap_wakers),
+			#[cfg(feature = "timers")]
+			timers: self.timers,
+			#[cfg(all(feature = "std", not(target_os = "wasi")))]
+			heartbeat: self.watchdog.map(|(interval, hook)| Heartbeat::watch(interval, hook)),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl Default for Runtime {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Runtime {
+	/// Instantiate a new Runtime, that parks the current thread while idle
+	#[cfg(feature = "std")]
+	pub fn new() -> Self {
+		Builder::new().build()
+	}
+
+	/// Like [`new`](Runtime::new), but doesn't set up the thread's timers, see [`Builder::without_timers`]
+	#[cfg(feature = "timers")]
+	pub fn new_without_timers() -> Self {
+		Builder::new().without_timers().build()
+	}
+
+	/// Instantiate a new Runtime, that uses `parker` to wait for wakes while idle
+	pub fn with_parker<P: Parker + 'static>(parker: P) -> Self {
+		Builder::new().parker(parker).build()
+	}
+
+	/// Number of live tasks, including ones checked out for polling
+	pub fn len(&self) -> usize {
+		self.tasks.borrow().len()
+	}
+
+	/// Whether every task has completed
+	pub fn is_empty(&self) -> bool {
+		self.tasks.borrow().is_empty()
+	}
+
+	/// Whether no task is waiting to be polled, live tasks may still be woken later
+	pub fn is_idle(&self) -> bool {
+		// pending wakes are moved along, so they can be inspected
+		let mut ready = self.ready.borrow_mut();
+		ready.extend(self.shared.queue.drain());
+
+		#[cfg(feature = "std")]
+		if self.shared.injected.lock().unwrap().as_ref().is_some_and(|i| !i.is_empty()) {
+			return false;
+		}
+
+		let tasks = self.tasks.borrow();
+		!tasks.values().any(|t| t.rewake) && !ready.iter().any(|w| tasks.contains_key(&w.id))
+	}
+
+	/// Ids and names of live tasks, in spawn order
+	pub fn tasks(&self) -> impl Iterator<Item = (tasks::TaskId, Option<String>)> + use<> {
+		self.tasks.borrow().iter().map(|(id, task)| (*id, task.name.clone())).collect::<Vec<_>>().into_iter()
+	}
+
+	/// Poll statistics of a task that hasn't completed yet
+	#[cfg(feature = "std")]
+	pub fn poll_stats(&self, id: tasks::TaskId) -> Option<tasks::PollStats> {
+		self.tasks.borrow().get(&id).map(|t| t.stats)
+	}
+
+	/// Snapshot of runtime wide metrics, like how long woken tasks wait to be polled
+	#[cfg(feature = "std")]
+	pub fn metrics(&self) -> crate::metrics::Metrics {
+		let tasks = self.tasks.borrow();
+		let now = time::Instant::now();
+
+		crate::metrics::Metrics { tasks: tasks.len(), missed_heartbeats: tasks.values().filter(|t| t.overdue(now).is_some()).count(), ..*self.metrics.borrow() }
+	}
+
+	/// Describes every live task, e.g. to log while diagnosing a hang. Works from within tasks too
+	#[cfg(feature = "std")]
+	pub fn task_dump(&self) -> Vec<tasks::TaskDump> {
+		// pending wakes are moved along, so they can be inspected
+		let mut ready = self.ready.borrow_mut();
+		ready.extend(self.shared.queue.drain());
+		let scheduled = ready.iter().map(|w| w.id).collect::<collections::BTreeSet<_>>();
+
+		let state = |id, task: &tasks::Task| {
+			if task.inner.is_none() {
+				tasks::TaskState::Running
+			} else if task.rewake || scheduled.contains(id) {
+				tasks::TaskState::Scheduled
+			} else {
+				tasks::TaskState::Idle
+			}
+		};
+
+		let now = time::Instant::now();
+
+		self.tasks
+			.borrow()
+			.iter()
+			.map(|(id, task)| tasks::TaskDump {
+				id: *id,
+				name: task.name.clone(),
+				state: state(id, task),
+				age: task.spawned.elapsed(),
+				wakes: task.wakes,
+				stats: task.stats,
+				awaiting: task.awaiting.clone(),
+				overdue: task.overdue(now),
+			})
+			.collect()
+	}
+
+	/// Summary of the runtime's health, also what its [`Debug`](fmt::Debug) and [`Display`](fmt::Display) implementations print
+	#[cfg(feature = "std")]
+	pub fn snapshot(&self) -> Snapshot {
+		// pending wakes are moved along, so they can be counted
+		let mut ready = self.ready.borrow_mut();
+		ready.extend(self.shared.queue.drain());
+
+		let tasks = self.tasks.borrow();
+		let scheduled = ready.iter().map(|w| w.id).filter(|id| tasks.contains_key(id)).collect::<collections::BTreeSet<_>>();
+		let injected = self.shared.injected.lock().unwrap().as_ref().map_or(0, Vec::len);
+
+		#[cfg(feature = "timers")]
+		let next_timer = tasks
+			.values()
+			.flat_map(|t| &t.awaiting)
+			.filter_map(|a| match a {
+				tasks::Awaiting::Timer(due) => Some(*due),
+				_ => None,
+			})
+			.min();
+
+		Snapshot {
+			tasks: tasks.len(),
+			scheduled: scheduled.len() + injected,
+			#[cfg(feature = "timers")]
+			next_timer,
+			uptime: self.started.elapsed(),
+		}
+	}
+
+	/// Follows what `id` is waiting on, e.g. `[Task(2), Timer(..)]` when it awaits a task that sleeps. Only the first edge of each task is followed
+	#[cfg(feature = "std")]
+	pub fn await_chain(&self, id: tasks::TaskId) -> Vec<tasks::Awaiting> {
+		let tasks = self.tasks.borrow();
+		let mut chain = Vec::new();
+		let mut next = id;
+
+		while let Some(&awaiting) = tasks.get(&next).and_then(|t| t.awaiting.first()) {
+			// tasks awaiting each other would make the chain endless
+			if chain.contains(&awaiting) {
+				break;
+			}
+
+			chain.push(awaiting);
+			match awaiting {
+				tasks::Awaiting::Task(task) => next = task,
+				#[cfg(feature = "timers")]
+				tasks::Awaiting::Timer(_) => break,
+			}
+		}
+
+		chain
+	}
+
+	/// Stream of lifecycle events, for in-process monitoring. Events are only recorded while the stream is alive.
+	///
+	/// At most [`EVENTS_CAPACITY`] events are buffered, older ones are dropped if the consumer falls behind. Taking a new stream ends the previous one
+	#[cfg(feature = "std")]
+	pub fn events(&self) -> Events {
+		let queue = alloc::rc::Rc::new(EventQueue::default());
+
+		if let Some(previous) = mem::replace(&mut *self.events.borrow_mut(), alloc::rc::Rc::downgrade(&queue)).upgrade() {
+			previous.close()
+		}
+
+		Events { queue }
+	}
+
+	/// Records an event, only built if a stream is listening
+	#[cfg(feature = "std")]
+	fn emit(&self, event: impl FnOnce() -> Event) {
+		let Some(queue) = self.events.borrow().upgrade() else { return };
+		let mut events = queue.events.borrow_mut();
+
+		if events.len() == EVENTS_CAPACITY {
+			events.pop_front();
+		}
+
+		events.push_back(event());
+		drop(events);
+
+		if let Some(waker) = queue.waker.take() {
+			waker.wake()
+		}
+	}
+
+	/// A [`Notifier`] scheduling `task` when notified, for foreign threads and callbacks that can't handle wakers
+	pub fn notifier(&self, task: tasks::TaskId) -> Notifier {
+		Notifier { waker: self.create_waker(task), task }
+	}
+
+	/// A future completing once [`initiate_shutdown`](Runtime::initiate_shutdown) is called, for tasks to await in their select loops and start draining
+	pub fn shutdown_signal(&self) -> ShutdownSignal {
+		ShutdownSignal { shared: self.shared.clone(), slot: None }
+	}
+
+	/// Completes every [`ShutdownSignal`] of this runtime, including ones created afterwards. Returns `false` if shutdown was already initiated.
+	///
+	/// Tasks keep running, it's up to them to wind down once signalled
+	pub fn initiate_shutdown(&self) -> bool {
+		self.shared.initiate_shutdown()
+	}
+
+	/// Whether [`initiate_shutdown`](Runtime::initiate_shutdown) was called
+	pub fn is_shutting_down(&self) -> bool {
+		self.shared.shutdown.load(atomic::Ordering::Acquire)
+	}
+
+	/// Registers `fut` to run during [`shutdown`](Runtime::shutdown), e.g. to flush buffered writers or metrics.
+	///
+	/// Dropping the runtime runs cleanups that haven't run yet, waiting at most [`Builder::shutdown_timeout`] for them. Tasks can register through [`Runtime::with_current`]
+	#[cfg(feature = "std")]
+	pub fn on_shutdown<F: Future<Output = ()> + 'static>(&self, fut: F) {
+		self.cleanups.borrow_mut().push(Boxed::into_pin(unsafe { Boxed::unsize(Boxed::new_in(fut, self.shared.allocator), |fut| fut as _) }))
+	}
+
+	/// Initiates shutdown, then drives the runtime until every [`on_shutdown`](Runtime::on_shutdown) cleanup completes, or `timeout` passes.
+	///
+	/// Returns `true` if all cleanups completed in time, late ones are aborted. Other tasks are polled alongside, so they can drain on their [`ShutdownSignal`]
+	#[cfg(feature = "std")]
+	pub fn shutdown(&self, timeout: time::Duration) -> bool {
+		self.initiate_shutdown();
+
+		// spawned even if the runtime is full, so shutting down never panics on the task limit
+		let cleanups = self.cleanups.take().into_iter().map(|fut| self.spawn_uncapped::<_, (), _>(fut, None, Labels::default(), Start::Now).unwrap_or_else(|e| panic!("{}", e))).collect::<Vec<_>>();
+		self.run_until_or(time::Instant::now() + timeout, || cleanups.iter().all(tasks::TaskMonitor::is_finished));
+
+		let completed = cleanups.iter().all(tasks::TaskMonitor::is_finished);
+		cleanups.iter().for_each(tasks::TaskMonitor::abort);
+
+		completed
+	}
+
+	/// Blocks execution, continuously polling tasks and waiting for `fut` to complete.
+	///
+	/// Like spawning, it only needs `&self`, so the runtime can be shared behind an `Rc`. Called from within a task, other tasks are polled in place, as with [`block_in_place`]
+	pub fn block_on<T: 'static, F: Future<Output = T> + 'static>(&self, fut: F) -> T {
+		match self.drive(fut) {
+			Ok(res) => res,
+			// resume with the task's own panic, instead of a generic error
+			Err((_, Some(payload))) => resume_unwind(payload),
+			Err((error, None)) => panic!("{}", error),
+		}
+	}
+
+	/// Like [`block_on`](Runtime::block_on), but polls an already pinned future in place instead of moving it into a task.
+	///
+	/// Skips boxing the future, which also doesn't have to be `'static`
+	pub fn block_on_pinned<F: Future + ?Sized>(&self, mut fut: Pin<&mut F>) -> F::Output {
+		let root = Counted::new_in(RootWaker { woken: atomic::AtomicBool::new(true), shared: self.shared.clone() }, self.shared.allocator);
+		let waker = arena::waker(root.clone());
+		let mut context = task::Context::from_waker(&waker);
+
+		loop {
+			if root.woken.swap(false, atomic::Ordering::AcqRel) {
+				#[cfg(feature = "std")]
+				let _current = CurrentGuard::enter(self);
+
+				if let task::Poll::Ready(res) = coop::with_budget(|| fut.as_mut().poll(&mut context)) {
+					return res;
+				}
+			}
+
+			if let Err((_, payload)) = self.poll() {
+				resume_unwind(payload)
+			}
+
+			// tasks polled just now may have woken the root future
+			if !root.woken.load(atomic::Ordering::Acquire) && !self.idle() {
+				self.shared.parker.park();
+			}
+		}
+	}
+
+	/// Like [`block_on`](Runtime::block_on), but returns an error instead of panicking.
+	///
+	/// With `std`, a panic in any task is caught and returned as [`RuntimeError::Panicked`], leaving the runtime usable
+	pub fn try_block_on<T: 'static, F: Future<Output = T> + 'static>(&self, fut: F) -> Result<T, RuntimeError> {
+		self.drive(fut).map_err(|(error, _)| error)
+	}
+
+	fn drive<T: 'static, F: Future<Output = T> + 'static>(&self, fut: F) -> Result<T, (RuntimeError, Option<Panic>)> {
+		let task_id = self.try_next_task_id().map_err(|e| (e, None))?;
+		let (results_tx, results_rx) = oneshot::channel(self.shared.allocator);
+
+		let waker = self.create_waker(task_id);
+		waker.wake_by_ref(); // poll once
+
+		let inner = tasks::TaskFuture::new(
+			async move {
+				// the caller may have returned early, if another task panicked
+				let _ = results_tx.send(fut.await);
+			},
+			&self.pool,
+		);
+
+		let task = tasks::Task::new(inner, waker, None, None);
+
+		#[cfg(feature = "std")]
+		let task = tasks::Task { root: true, ..task };
+
+		self.insert_task(task_id, task);
+
+		loop {
+			if let Err((id, payload)) = self.poll() {
+				// nobody is left to receive the result
+				if let Some(task) = self.tasks.borrow_mut().remove(&task_id) {
+					self.release(task)
+				}
+				return Err((RuntimeError::Panicked(id), Some(payload)));
+			}
+
+			match results_rx.try_recv() {
+				Ok(r) => return Ok(r),
+				Err(oneshot::TryRecvError::Empty) => {}
+				Err(oneshot::TryRecvError::Disconnected) => return Err((RuntimeError::Dropped(task_id), None)),
+			}
+
+			// wait for external events to wake up host
+			if !self.idle() {
+				self.shared.parker.park();
+			}
+		}
+	}
+
+	/// Like [`block_on`](Runtime::block_on), but gives up once `timeout` passes, returning a monitor of the still running future.
+	///
+	/// The monitor can be passed to a later `block_on` call, e.g. on the next frame of a game loop.
+	/// Fails like [`try_spawn`](Runtime::try_spawn) if `fut` can't be spawned, without running anything
+	#[cfg(feature = "std")]
+	pub fn block_on_timeout<T: 'static, F: Future<Output = T> + 'static>(&self, fut: F, timeout: time::Duration) -> Result<Result<T, tasks::TaskMonitor<T>>, RuntimeError> {
+		let mut monitor = self.try_spawn(fut)?;
+		self.run_until_or(time::Instant::now() + timeout, || monitor.is_finished());
+
+		match monitor.result_rx.try_recv() {
+			Ok(res) => {
+				monitor.terminated = true;
+				Ok(Ok(res))
+			}
+			_ => Ok(Err(monitor)),
+		}
+	}
+
+	/// Drives spawned tasks for at most `duration`, returning early if none are left
+	#[cfg(feature = "std")]
+	pub fn run_for(&self, duration: time::Duration) {
+		self.run_until(time::Instant::now() + duration)
+	}
+
+	/// Drives spawned tasks until `deadline`, returning early if none are left
+	#[cfg(feature = "std")]
+	pub fn run_until(&self, deadline: time::Instant) {
+		self.run_until_or(deadline, || self.tasks.borrow().is_empty())
+	}
+
+	#[cfg(feature = "std")]
+	fn run_until_or(&self, deadline: time::Instant, mut done: impl FnMut() -> bool) {
+		loop {
+			if let Err((_, payload)) = self.poll() {
+				resume_unwind(payload)
+			}
+
+			let now = time::Instant::now();
+			if done() || now >= deadline {
+				return;
+			}
+
+			// wait for external events, or the deadline
+			if !self.idle() {
+				self.shared.parker.park_timeout(deadline - now);
+			}
+		}
+	}
+
+	/// Runs the idle strategy, returns `true` if a wake arrived meanwhile and parking can be skipped
+	fn idle(&self) -> bool {
+		let idle = &mut *self.idle.borrow_mut();
+		let woken = || self.shared.notified.load(atomic::Ordering::Acquire);
+
+		if let Some(hook) = &mut idle.hook {
+			hook()
+		}
+
+		for _ in 0..idle.spins {
+			if woken() {
+				return true;
+			}
+
+			core::hint::spin_loop()
+		}
+
+		#[cfg(feature = "std")]
+		for _ in 0..idle.yields {
+			if woken() {
+				return true;
+			}
+
+			thread::yield_now()
+		}
+
+		woken()
+	}
+
+	/// Polls every task woken so far, then returns without blocking. Lets a foreign event loop drive the runtime, see [`event_fd`]
+	pub fn run_ready(&self) {
+		if let Err((_, payload)) = self.poll() {
+			resume_unwind(payload)
+		}
+	}
+
+	/// Spawns a future as a `Task`, and returns a [`TaskMonitor`](tasks::TaskMonitor)
+	pub fn spawn<T: 'static, F: Future<Output = T> + 'static>(&self, fut: F) -> tasks::TaskMonitor<T> {
+		self.try_spawn(fut).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// Like [`spawn`](Runtime::spawn), but returns an error if the task can't be spawned
+	pub fn try_spawn<T: 'static, F: Future<Output = T> + 'static>(&self, fut: F) -> Result<tasks::TaskMonitor<T>, RuntimeError> {
+		self.spawn_monitored(fut, None, Labels::default(), Start::Now)
+	}
+
+	/// Like [`spawn`](Runtime::spawn), but `name` is passed along to lifecycle hooks
+	pub fn spawn_named<T: 'static, F: Future<Output = T> + 'static>(&self, name: impl Into<String>, fut: F) -> tasks::TaskMonitor<T> {
+		self.spawn_monitored(fut, None, Labels { name: Some(name.into()), group: None }, Start::Now).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// Like [`spawn`](Runtime::spawn), but the task joins `group`, so it can be aborted along with the rest of it through [`cancel_group`](Runtime::cancel_group)
+	pub fn spawn_in_group<T: 'static, F: Future<Output = T> + 'static>(&self, group: impl Into<String>, fut: F) -> tasks::TaskMonitor<T> {
+		self.spawn_monitored(fut, None, Labels { name: None, group: Some(group.into()) }, Start::Now).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// Like [`spawn`](Runtime::spawn), but the task is given a [`Progress`](tasks::Progress) handle, readable from the returned monitor
+	pub fn spawn_with_progress<T: 'static, S, F: Future<Output = T> + 'static>(&self, f: impl FnOnce(tasks::Progress<S>) -> F) -> tasks::TaskMonitor<T, S> {
+		let report = Counted::new_in(tasks::Report::new((None, None)), self.shared.allocator);
+		self.spawn_monitored(f(tasks::Progress { report: report.clone() }), Some(report), Labels::default(), Start::Now).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// Spawns a task whose output is discarded, skipping the result channel and monitor a [`spawn`](Runtime::spawn) allocates.
+	///
+	/// For fire-and-forget work like flushing logs. The returned id can still [`abort`](Runtime::abort) it
+	pub fn spawn_detached<F: Future + 'static>(&self, fut: F) -> tasks::TaskId {
+		if !self.has_capacity() {
+			panic!("{}", RuntimeError::TaskLimitReached)
+		}
+
+		let task_id = self.try_next_task_id().unwrap_or_else(|e| panic!("{}", e));
+		let waker = self.create_waker(task_id);
+		waker.wake_by_ref();
+
+		let inner = tasks::TaskFuture::new(
+			async move {
+				fut.await;
+			},
+			&self.pool,
+		);
+
+		self.insert_task(task_id, tasks::Task::new(inner, waker, None, None));
+		task_id
+	}
+
+	/// Spawns a task that's first polled once `dur` has passed, see [`spawn_at`](Runtime::spawn_at)
+	#[cfg(feature = "timers")]
+	pub fn spawn_after<T: 'static, F: Future<Output = T> + 'static>(&self, dur: time::Duration, fut: F) -> tasks::TaskMonitor<T> {
+		self.spawn_at(crate::timers::now() + dur, fut)
+	}
+
+	/// Spawns a task that's first polled at `deadline`. The task is handed to the sleeping thread, instead of being polled right away only to arm a timer
+	#[cfg(feature = "timers")]
+	pub fn spawn_at<T: 'static, F: Future<Output = T> + 'static>(&self, deadline: time::Instant, fut: F) -> tasks::TaskMonitor<T> {
+		let delayed = async move {
+			// only reached early through a stray wake, e.g. from a notifier
+			let deadline = crate::timers::Deadline::at(deadline);
+			while !deadline.is_elapsed() {
+				deadline.await;
+			}
+
+			fut.await
+		};
+
+		self.spawn_monitored(delayed, None, Labels::default(), Start::At(deadline)).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// Spawns a task running the future produced by `f` every `period`, starting right away, until aborted.
+	///
+	/// Runs are scheduled from the first one, so time spent running doesn't shift later runs. A run taking longer than `period` skips the runs it overlapped.
+	/// Fails like [`try_spawn`](Runtime::try_spawn) if the task can't be spawned
+	#[cfg(feature = "timers")]
+	pub fn spawn_periodic<F: Future<Output = ()> + 'static>(&self, period: time::Duration, mut f: impl FnMut() -> F + 'static) -> Result<tasks::TaskMonitor<()>, RuntimeError> {
+		let mut interval = crate::timers::interval(period);
+		interval.set_missed_tick_behavior(crate::timers::MissedTickBehavior::Skip);
+
+		self.try_spawn(async move {
+			loop {
+				interval.tick().await;
+				f().await
+			}
+		})
+	}
+
+	/// Cancels a task by id, like [`TaskMonitor::abort`](tasks::TaskMonitor::abort). Does nothing if it already completed
+	pub fn abort(&self, id: tasks::TaskId) {
+		self.shared.abort(id)
+	}
+
+	/// Aborts every live task spawned in `group`, returning how many were aborted. Tasks are dropped the next time the runtime polls
+	pub fn cancel_group(&self, group: &str) -> usize {
+		let members = self.tasks.borrow().iter().filter(|(_, task)| task.group.as_deref() == Some(group)).map(|(id, _)| *id).collect::<Vec<_>>();
+
+		for id in &members {
+			self.abort(*id);
+		}
+
+		members.len()
+	}
+
+	/// Blocks on a [`scope`](tasks::scope), until `f` and every child it spawns complete, or one of them fails
+	pub fn scope<T: 'static, E: 'static, F: AsyncFnOnce(&tasks::Scope<'static, E>) -> Result<T, E> + 'static>(&self, f: F) -> Result<T, E> {
+		self.block_on(tasks::scope(f))
+	}
+
+	fn spawn_monitored<T: 'static, S, F: Future<Output = T> + 'static>(
+		&self,
+		fut: F,
+		report: Option<Counted<tasks::Report<S>>>,
+		labels: Labels,
+		start: Start,
+	) -> Result<tasks::TaskMonitor<T, S>, RuntimeError> {
+		if !self.has_capacity() {
+			return Err(RuntimeError::TaskLimitReached);
+		}
+
+		self.spawn_uncapped(fut, report, labels, start)
+	}
+
+	/// Like `spawn_monitored`, past the [`max_tasks`](Builder::max_tasks) limit. For cleanups, a full runtime still has to shut down
+	fn spawn_uncapped<T: 'static, S, F: Future<Output = T> + 'static>(
+		&self,
+		fut: F,
+		report: Option<Counted<tasks::Report<S>>>,
+		labels: Labels,
+		start: Start,
+	) -> Result<tasks::TaskMonitor<T, S>, RuntimeError> {
+		let task_id = self.try_next_task_id()?;
+		let (result_tx, result_rx) = oneshot::channel(self.shared.allocator);
+		let monitor_waker = Counted::new_in(tasks::Lock::new(None), self.shared.allocator);
+
+		self.insert_monitored(task_id, fut, result_tx, monitor_waker.clone(), labels, start);
+		Ok(tasks::TaskMonitor { id: task_id, result_rx, waker: monitor_waker, report, shared: self.shared.clone(), abort_on_drop: false, terminated: false })
+	}
+
+	/// Wraps `fut` into a task that reports its result to a monitor
+	fn insert_monitored<T: 'static, F: Future<Output = T> + 'static>(
+		&self,
+		task_id: tasks::TaskId,
+		fut: F,
+		result_tx: oneshot::Sender<T>,
+		monitor_waker: Counted<tasks::Lock<Option<task::Waker>>>,
+		labels: Labels,
+		start: Start,
+	) {
+		let fut = async move {
+			let res = fut.await;
+
+			// the monitor may have been dropped, leaving the task detached
+			let _ = result_tx.send(res);
+		};
+
+		self.insert_reporting(task_id, fut, monitor_waker, labels, start)
+	}
+
+	/// Inserts a task whose future reports its own result, or drops its sender to resolve the monitor to `None`
+	fn insert_reporting<F: Future<Output = ()> + 'static>(&self, task_id: tasks::TaskId, fut: F, monitor_waker: Counted<tasks::Lock<Option<task::Waker>>>, labels: Labels, start: Start) {
+		let waker = self.create_waker(task_id);
+
+		match start {
+			// poll once, and initialize task
+			Start::Now => waker.wake_by_ref(),
+			#[cfg(feature = "timers")]
+			Start::At(due) => {
+				crate::timers::wake_at(due, waker.clone());
+			}
+		}
+
+		let inner = tasks::TaskFuture::new(fut, &self.pool);
+		let mut task = tasks::Task::new(inner, waker, Some(monitor_waker), labels.name);
+		task.group = labels.group;
+
+		self.insert_task(task_id, task);
+	}
+
+	/// Spawns a task awaiting `first`, then running `f` on its result. Resolves to `None` if `first` does, see [`TaskMonitor::and_then`](tasks::TaskMonitor::and_then)
+	pub(crate) fn spawn_chained<T: 'static, S: 'static, U: 'static, Fut: Future<Output = U> + 'static>(
+		&self,
+		first: tasks::TaskMonitor<T, S>,
+		f: impl FnOnce(T) -> Fut + 'static,
+	) -> Result<tasks::TaskMonitor<U>, RuntimeError> {
+		if !self.has_capacity() {
+			return Err(RuntimeError::TaskLimitReached);
+		}
+
+		let task_id = self.try_next_task_id()?;
+		let (result_tx, result_rx) = oneshot::channel(self.shared.allocator);
+		let monitor_waker = Counted::new_in(tasks::Lock::new(None), self.shared.allocator);
+
+		let fut = async move {
+			if let Some(res) = first.await {
+				let _ = result_tx.send(f(res).await);
+			}
+		};
+
+		self.insert_reporting(task_id, fut, monitor_waker.clone(), Labels::default(), Start::Now);
+		Ok(tasks::TaskMonitor { id: task_id, result_rx, waker: monitor_waker, report: None, shared: self.shared.clone(), abort_on_drop: false, terminated: false })
+	}
+
+	/// A handle for spawning tasks onto this runtime from any thread
+	#[cfg(feature = "std")]
+	pub fn handle(&self) -> Handle {
+		Handle { shared: self.shared.clone() }
+	}
+
+	/// Handle of the runtime polling the calling task, `None` outside of a runtime
+	#[cfg(feature = "std")]
+	pub fn current() -> Option<Handle> {
+		current().map(Runtime::handle)
+	}
+
+	/// Calls `f` with the runtime polling the calling task, e.g. to spawn tasks that aren't `Send` from deep library code. `None` outside of a runtime
+	#[cfg(feature = "std")]
+	pub fn with_current<R>(f: impl FnOnce(&Runtime) -> R) -> Option<R> {
+		current().map(f)
+	}
+
+	/// Registers this runtime's [`Handle`] under `name`, replacing any runtime previously registered under it.
+	///
+	/// Other threads may then fetch it through [`Handle::named`]. The entry is removed when the runtime is dropped
+	#[cfg(feature = "std")]
+	pub fn register(&self, name: impl Into<String>) {
+		REGISTRY.lock().unwrap().insert(name.into(), self.handle());
+	}
+
+	fn has_capacity(&self) -> bool {
+		self.max_tasks.is_none_or(|max| self.tasks.borrow().len() < max)
+	}
+
+	/// Closes a task removed from `tasks`, waking anyone waiting for its slot
+	fn release(&self, task: tasks::Task) {
+		task.close();
+
+		#[cfg(feature = "std")]
+		for waker in self.capacity_waiters.take() {
+			waker.wake()
+		}
+	}
+
+	/// Runs a lifecycle hook taken out of `hooks`, so it may spawn or inspect the runtime. Tasks it spawns don't reach the hook itself
+	fn run_hook(&self, hook: fn(&mut Hooks) -> &mut Option<TaskHook>, id: tasks::TaskId, name: Option<&str>) {
+		let taken = hook(&mut self.hooks.borrow_mut()).take();
+
+		if let Some(mut f) = taken {
+			f(id, name);
+			*hook(&mut self.hooks.borrow_mut()) = Some(f);
+		}
+	}
+
+	/// Hooks and events see the task already inserted, so they may abort it or cancel its group
+	fn insert_task(&self, id: tasks::TaskId, task: tasks::Task) {
+		let name = task.name.clone();
+
+		#[cfg(feature = "std")]
+		let group = task.group.clone();
+
+		self.tasks.borrow_mut().insert(id, task);
+		self.run_hook(|hooks| &mut hooks.on_spawn, id, name.as_deref());
+
+		#[cfg(feature = "std")]
+		self.emit(|| Event::TaskSpawned { id, name, group });
+	}
+
+	fn try_next_task_id(&self) -> Result<tasks::TaskId, RuntimeError> {
+		self.shared.next_task_id()
+	}
+
+	/// Creates a waker for `id`, its clones share one allocation that doubles as the task's node in the wake queue
+	fn create_waker(&self, id: tasks::TaskId) -> task::Waker {
+		let data = WakerData {
+			shared: self.shared.clone(),
+			id,
+			queued: atomic::AtomicBool::new(false),
+			next: atomic::AtomicPtr::new(ptr::null_mut()),
+			#[cfg(feature = "std")]
+			woken_at: atomic::AtomicU64::new(0),
+			#[cfg(feature = "timers")]
+			urgent: atomic::AtomicBool::new(false),
+		};
+
+		arena::waker(Counted::new_in(data, self.shared.allocator))
+	}
+
+	/// Decorated waker of `id`, created on its first poll, if wakers are wrapped at all
+	fn wrapped_waker(&self, id: tasks::TaskId, waker: &task::Waker) -> Option<task::Waker> {
+		let mut wrap = self.wrap_wakers.borrow_mut();
+		let wrap = wrap.as_mut()?;
+
+		if let Some(wrapped) = self.tasks.borrow().get(&id).and_then(|task| task.wrapped.clone()) {
+			return Some(wrapped);
+		}
+
+		// called outside the task map's borrow, the hook may inspect the runtime
+		let wrapped = wrap(id, waker.clone());
+		if let Some(task) = self.tasks.borrow_mut().get_mut(&id) {
+			task.wrapped = Some(wrapped.clone());
+		}
+
+		Some(wrapped)
+	}
+
+	/// Reorders woken tasks by weighted round robin over their groups, keeping wake order within each group
+	fn interleave(&self, ready: &mut collections::VecDeque<Wake>) {
+		let tasks = self.tasks.borrow();
+
+		// weight, accumulated credit, and woken tasks of each group
+		let mut groups = collections::BTreeMap::<Option<&str>, (i64, i64, collections::VecDeque<Wake>)>::new();
+
+		for wake in ready.drain(..) {
+			let group = tasks.get(&wake.id).and_then(|t| t.group.as_deref());
+			let weight = group.and_then(|g| self.weights.get(g)).copied().unwrap_or(1);
+			groups.entry(group).or_insert_with(|| (weight as i64, 0, collections::VecDeque::new())).2.push_back(wake);
+		}
+
+		// every round credits each group its weight, and the richest one pays the total for a poll
+		loop {
+			let mut total = 0;
+			for (weight, credit, _) in groups.values_mut().filter(|(_, _, wakes)| !wakes.is_empty()) {
+				*credit += *weight;
+				total += *weight;
+			}
+
+			let Some((_, credit, wakes)) = groups.values_mut().filter(|(_, _, wakes)| !wakes.is_empty()).max_by_key(|(_, credit, _)| *credit) else { break };
+			*credit -= total;
+			ready.extend(wakes.pop_front());
+		}
+	}
+
+	/// must be called manually to progress execution of tasks, stops at the first task that panics.
+	///
+	/// Futures are checked out of `tasks` while being polled, so [`block_in_place`] can poll the runtime again from within them
+	fn poll(&self) -> Result<(), (tasks::TaskId, Panic)> {
+		#[cfg(feature = "std")]
+		let _current = CurrentGuard::enter(self);
+
+		// cleared before draining, so any wake from here on unparks the host again
+		self.shared.notified.store(false, atomic::Ordering::Release);
+
+		#[cfg(feature = "std")]
+		let injected = self.shared.injected.lock().unwrap().as_mut().map(mem::take);
+
+		#[cfg(feature = "std")]
+		for mut inject in injected.into_iter().flatten() {
+			inject(self)
+		}
+
+		for aborted in self.shared.aborts.drain().into_iter().map(|w| w.id) {
+			let mut tasks = self.tasks.borrow_mut();
+
+			let removed = match tasks.get_mut(&aborted) {
+				// being polled further up the stack, it's dropped once returned
+				Some(task) if task.inner.is_none() => {
+					task.aborted = true;
+					None
+				}
+				_ => tasks.remove(&aborted),
+			};
+
+			drop(tasks);
+
+			// monitors of the task now resolve to `None`
+			if let Some(task) = removed {
+				self.release(task)
+			}
+		}
+
+		let batch = {
+			let mut ready = self.ready.borrow_mut();
+			let mut wakes = self.shared.queue.drain();
+
+			// a deep queue is polled in id order, stable so each task keeps its earliest wake
+			if !self.fifo && wakes.len() >= self.batch_threshold {
+				wakes.sort_by_key(|w| w.id);
+				wakes.dedup_by_key(|w| w.id);
+			}
+
+			if !self.fifo
+				&& let Some((_, rng)) = &self.shuffle
+			{
+				let mut shuffler = rng.get();
+				shuffler.shuffle(&mut wakes);
+				rng.set(shuffler);
+			}
+
+			// jump ahead of every other wake, including ones left over from earlier batches
+			#[cfg(feature = "timers")]
+			let urgent = match self.fifo {
+				true => Vec::new(),
+				false => wakes.extract_if(.., |w| w.urgent).collect(),
+			};
+
+			ready.extend(wakes);
+
+			if !self.fifo && !self.weights.is_empty() {
+				self.interleave(&mut ready)
+			}
+
+			#[cfg(feature = "timers")]
+			urgent.into_iter().rev().for_each(|w| ready.push_front(w));
+
+			ready.len()
+		};
+
+		// tasks already polled in this batch, only tracked in fifo mode
+		let mut polled = collections::BTreeSet::new();
+
+		for _ in 0..batch {
+			// tasks queued during this block will be processed in a later iteration
+			// meaning if `poll` returns, there aren't any tasks pending or trying to self wake
+			let Some(wake) = self.ready.borrow_mut().pop_front() else { break };
+			let next = wake.id;
+
+			// repeated wakes collapse into the first one
+			if self.fifo && !polled.insert(next) {
+				continue;
+			}
+
+			#[cfg(feature = "std")]
+			if let Some(task) = self.tasks.borrow_mut().get_mut(&next) {
+				task.wakes += 1;
+			}
+
+			let checked_out = match self.tasks.borrow_mut().get_mut(&next) {
+				// being polled further up the stack, poll it again once it's returned
+				Some(task) if task.inner.is_none() => {
+					task.rewake = true;
+					None
+				}
+				Some(task) => {
+					// edges are reported anew by every poll
+					#[cfg(feature = "std")]
+					task.awaiting.clear();
+
+					task.inner.take().map(|fut| (fut, mem::replace(&mut task.waker, task::Waker::noop().clone())))
+				}
+				None => None,
+			};
+
+			let Some((mut fut, waker)) = checked_out else { continue };
+			let wrapped = self.wrapped_waker(next, &waker);
+			let mut context = task::Context::from_waker(wrapped.as_ref().unwrap_or(&waker));
+
+			#[cfg(feature = "std")]
+			let started = time::Instant::now();
+
+			#[cfg(feature = "std")]
+			{
+				let mut metrics = self.metrics.borrow_mut();
+				metrics.polls += 1;
+				metrics.wake_latency.record(started.saturating_duration_since(wake.at));
+			}
+
+			#[cfg(all(feature = "std", not(target_os = "wasi")))]
+			let beat = self.heartbeat.as_ref().map(|h| h.enter(next));
+
+			#[cfg(feature = "std")]
+			let outer = self.polling.replace(Some(next));
+
+			#[cfg(feature = "std")]
+			let poll = coop::with_budget(|| std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| fut.poll(&mut context))));
+
+			#[cfg(not(feature = "std"))]
+			let poll = Ok::<_, Panic>(coop::with_budget(|| fut.poll(&mut context)));
+
+			#[cfg(feature = "std")]
+			let elapsed = started.elapsed();
+
+			#[cfg(feature = "std")]
+			self.polling.set(outer);
+
+			#[cfg(all(feature = "std", not(target_os = "wasi")))]
+			if let Some((heartbeat, beat)) = self.heartbeat.as_ref().zip(beat) {
+				heartbeat.exit(beat)
+			}
+
+			// taken out while it runs, so a hook that re-enters the runtime (e.g. through block_in_place) doesn't find it borrowed
+			#[cfg(feature = "std")]
+			if self.slow_poll.borrow().as_ref().is_some_and(|(threshold, _)| elapsed > *threshold)
+				&& let Some((threshold, mut hook)) = self.slow_poll.take()
+			{
+				hook(next, elapsed);
+				self.slow_poll.borrow_mut().get_or_insert((threshold, hook));
+			}
+
+			let mut tasks = self.tasks.borrow_mut();
+			let task = tasks.get_mut(&next).expect("Task was removed while being polled");
+
+			task.waker = waker;
+			#[cfg(feature = "std")]
+			task.stats.record(elapsed);
+
+			// woken again before its poll returned, repeated streaks are a busy loop. The hook runs once `tasks` is released, so it may inspect the runtime
+			#[cfg(feature = "std")]
+			let mut busy = None;
+
+			#[cfg(feature = "std")]
+			if let Ok(task::Poll::Pending) = poll {
+				let queued = WakerData::of(&task.waker).is_some_and(|data| data.queued.load(atomic::Ordering::Acquire));
+
+				match queued || task.rewake {
+					true => {
+						task.stats.self_wakes += 1;
+						task.self_woken += 1;
+					}
+					false => task.self_woken = 0,
+				}
+
+				if let Some((polls, _)) = &*self.busy_wake.borrow()
+					&& task.self_woken == *polls
+				{
+					busy = Some(task.name.clone());
+				}
+			}
+
+			if let Ok(task::Poll::Pending) = poll
+				&& !task.aborted
+			{
+				// return the future, and honor wakes received while it was checked out
+				task.inner = Some(fut);
+
+				if mem::take(&mut task.rewake) {
+					task.waker.wake_by_ref()
+				}
+
+				drop(tasks);
+
+				#[cfg(feature = "std")]
+				if let Some(name) = busy
+					&& let Some((_, hook)) = &mut *self.busy_wake.borrow_mut()
+				{
+					hook(next, name.as_deref())
+				}
+
+				continue;
+			}
+
+			let task = tasks.remove(&next).unwrap();
+			drop(tasks);
+			drop(fut);
+
+			match poll {
+				Ok(task::Poll::Ready(())) => {
+					self.run_hook(|hooks| &mut hooks.on_complete, next, task.name.as_deref());
+
+					#[cfg(feature = "std")]
+					self.emit(|| Event::TaskCompleted { id: next, lifetime: task.spawned.elapsed() });
+
+					self.release(task)
+				}
+				// aborted while blocked in place
+				Ok(task::Poll::Pending) => self.release(task),
+				Err(payload) => {
+					#[cfg(feature = "std")]
+					self.run_hook(|hooks| &mut hooks.on_panic, next, task.name.as_deref());
+
+					#[cfg(feature = "std")]
+					self.emit(|| Event::TaskPanicked { id: next });
+
+					#[cfg(feature = "std")]
+					if self.panic_policy == PanicPolicy::Abort {
+						std::process::abort()
+					}
+
+					#[cfg(feature = "std")]
+					let isolated = self.panic_policy == PanicPolicy::Isolate && !task.root;
+
+					// the future can't be polled again, monitors resolve to `None`
+					self.release(task);
+
+					#[cfg(feature = "std")]
+					if isolated {
+						continue;
+					}
+
+					return Err((next, payload));
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+	/// Runtime currently polling tasks on this thread
+	static CURRENT: cell::Cell<*const Runtime> = const { cell::Cell::new(ptr::null()) };
+}
+
+#[cfg(feature = "std")]
+impl Drop for Runtime {
+	fn drop(&mut self) {
+		// polling while unwinding could panic again, aborting the process
+		if !self.cleanups.get_mut().is_empty() && !thread::panicking() {
+			self.shutdown(self.shutdown_timeout);
+		}
+
+		// a failing simulation is only worth as much as the seed reproducing it
+		if let Some((seed, _)) = self.shuffle
+			&& !self.fifo
+			&& thread::panicking()
+		{
+			eprintln!("pinokkio: runtime panicked while shuffling wakes, reproduce with Builder::shuffle({seed})");
+		}
+
+		// the event stream ends along with the runtime
+		if let Some(queue) = self.events.get_mut().upgrade() {
+			queue.close()
+		}
+
+		// pending wakes would otherwise keep the shared state alive through the queue it owns
+		self.shared.queue.close();
+
+		// registered handles would otherwise spawn into a runtime that's gone
+		if let Ok(mut registry) = REGISTRY.lock() {
+			registry.retain(|_, handle| !Counted::ptr_eq(&handle.shared, &self.shared));
+		}
+
+		// tasks injected since the last poll are dropped outside the lock, their monitors resolve as dropped
+		let injected = self.shared.injected.lock().map(|mut injected| injected.take());
+		drop(injected);
+
+		// the last runtime using the thread's timers stops the sleeping thread
+		#[cfg(feature = "timers")]
+		if self.timers {
+			crate::timers::release()
+		}
+
+		// and the last one using its reactor stops the reactor thread
+		#[cfg(all(feature = "io", unix))]
+		crate::io::release();
+	}
+}
+
+/// Health report of a [`Runtime`], from [`Runtime::snapshot`]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+	/// Live tasks
+	pub tasks: usize,
+	/// Tasks woken or spawned, waiting to be polled
+	pub scheduled: usize,
+	/// Earliest timer a task is waiting on
+	#[cfg(feature = "timers")]
+	pub next_timer: Option<time::Instant>,
+	/// Time since the runtime was built
+	pub uptime: time::Duration,
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for Snapshot {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "runtime up for {:?}: {} tasks, {} scheduled", self.uptime, self.tasks, self.scheduled)?;
+
+		#[cfg(feature = "timers")]
+		if let Some(due) = self.next_timer {
+			write!(f, ", next timer due in {:?}", due.saturating_duration_since(crate::timers::now()))?;
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for Runtime {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let snapshot = self.snapshot();
+		let mut s = f.debug_struct("Runtime");
+		s.field("tasks", &snapshot.tasks).field("scheduled", &snapshot.scheduled);
+
+		#[cfg(feature = "timers")]
+		s.field("next_timer", &snapshot.next_timer.map(|due| due.saturating_duration_since(crate::timers::now())));
+
+		s.field("uptime", &snapshot.uptime).finish()
+	}
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for Runtime {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Display::fmt(&self.snapshot(), f)
+	}
+}
+
+/// Events buffered by [`Events`] at most
+#[cfg(feature = "std")]
+pub const EVENTS_CAPACITY: usize = 1024;
+
+/// A runtime lifecycle event, from [`Runtime::events`]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+	/// A task was spawned, including futures passed to [`block_on`](Runtime::block_on)
+	TaskSpawned {
+		/// Id of the new task
+		id: tasks::TaskId,
+		/// Name given to [`spawn_named`](Runtime::spawn_named)
+		name: Option<String>,
+		/// Group given to [`spawn_in_group`](Runtime::spawn_in_group)
+		group: Option<String>,
+	},
+	/// A task ran to completion
+	TaskCompleted {
+		/// Id of the task
+		id: tasks::TaskId,
+		/// Time between spawning and completing the task
+		lifetime: time::Duration,
+	},
+	/// A task panicked while being polled
+	TaskPanicked {
+		/// Id of the task
+		id: tasks::TaskId,
+	},
+	/// A [`Sleep`](crate::timers::Sleep) completed
+	#[cfg(feature = "timers")]
+	TimerFired {
+		/// Task that awaited the timer, `None` for a future driven by [`block_on_pinned`](Runtime::block_on_pinned)
+		task: Option<tasks::TaskId>,
+		/// When the timer was due
+		due: time::Instant,
+	},
+	/// A future wrapped with [`instrumented`](tasks::instrumented) completed
+	FutureCompleted {
+		/// Name given to the wrapper
+		name: String,
+		/// Task that awaited the future, `None` for a future driven by [`block_on_pinned`](Runtime::block_on_pinned)
+		task: Option<tasks::TaskId>,
+		/// Time between the future's first poll and its completion
+		elapsed: time::Duration,
+		/// Time spent within the future's polls
+		busy: time::Duration,
+		/// Number of times the future was polled
+		polls: u64,
+	},
+}
+
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct EventQueue {
+	events: cell::RefCell<collections::VecDeque<Event>>,
+	waker: cell::Cell<Option<task::Waker>>,
+	closed: cell::Cell<bool>,
+}
+
+#[cfg(feature = "std")]
+impl EventQueue {
+	fn close(&self) {
+		self.closed.set(true);
+
+		if let Some(waker) = self.waker.take() {
+			waker.wake()
+		}
+	}
+}
+
+/// Stream of [`Event`]s, from [`Runtime::events`]. Ends once the runtime drops, or a newer stream is taken
+#[cfg(feature = "std")]
+pub struct Events {
+	queue: alloc::rc::Rc<EventQueue>,
+}
+
+#[cfg(feature = "std")]
+impl futures_core::Stream for Events {
+	type Item = Event;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Option<Event>> {
+		if let Some(event) = self.queue.events.borrow_mut().pop_front() {
+			return task::Poll::Ready(Some(event));
+		}
+
+		if self.queue.closed.get() {
+			return task::Poll::Ready(None);
+		}
+
+		self.queue.waker.set(Some(cx.waker().clone()));
+		task::Poll::Pending
+	}
+}
+
+/// Schedules a designated task from any thread, obtained from [`Runtime::notifier`]. Behaves like the task's waker, without having to clone one through FFI layers
+#[derive(Clone)]
+pub struct Notifier {
+	waker: task::Waker,
+	task: tasks::TaskId,
+}
+
+impl Notifier {
+	/// Queues the task to be polled and unparks the runtime. Notifying a task that has completed does nothing
+	pub fn notify(&self) {
+		self.waker.wake_by_ref()
+	}
+
+	/// The task scheduled by this notifier
+	pub fn task(&self) -> tasks::TaskId {
+		self.task
+	}
+}
+
+/// Completes once the runtime's shutdown is initiated, from [`Runtime::shutdown_signal`]. Clones complete together, and polling again after completion completes immediately
+pub struct ShutdownSignal {
+	shared: Counted<Shared>,
+	/// registered with the runtime on first poll
+	slot: Option<Counted<WakerSlot>>,
+}
+
+impl ShutdownSignal {
+	/// Checks whether shutdown was initiated, without polling the signal
+	pub fn is_triggered(&self) -> bool {
+		self.shared.shutdown.load(atomic::Ordering::Acquire)
+	}
+}
+
+impl Clone for ShutdownSignal {
+	fn clone(&self) -> Self {
+		ShutdownSignal { shared: self.shared.clone(), slot: None }
+	}
+}
+
+impl Drop for ShutdownSignal {
+	fn drop(&mut self) {
+		// the slot holds a waker, which may hold the runtime's shared state in turn
+		if let Some(slot) = self.slot.take() {
+			self.shared.shutdown_waiters.with(|waiters| waiters.retain(|w| !Counted::ptr_eq(w, &slot)));
+		}
+	}
+}
+
+impl Future for ShutdownSignal {
+	type Output = ();
+
+	fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<()> {
+		let this = self.get_mut();
+
+		if this.is_triggered() {
+			return task::Poll::Ready(());
+		}
+
+		let slot = this.slot.get_or_insert_with(|| {
+			let slot = Counted::new_in(tasks::Lock::new(None), this.shared.allocator);
+			this.shared.shutdown_waiters.with(|waiters| waiters.push(slot.clone()));
+			slot
+		});
+
+		slot.with(|waker| match waker {
+			Some(w) if w.will_wake(cx.waker()) => {}
+			_ => *waker = Some(cx.waker().clone()),
+		});
+
+		// shutdown may have been initiated before our waker was stored
+		match this.is_triggered() {
+			true => task::Poll::Ready(()),
+			false => task::Poll::Pending,
+		}
+	}
+}
+
+/// Handles registered through [`Runtime::register`]
+#[cfg(feature = "std")]
+static REGISTRY: std::sync::Mutex<collections::BTreeMap<String, Handle>> = std::sync::Mutex::new(collections::BTreeMap::new());
+
+/// Spawns tasks onto a [`Runtime`] from any thread, obtained from [`Runtime::handle`]
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct Handle {
+	shared: Counted<Shared>,
+}
+
+#[cfg(feature = "std")]
+impl Handle {
+	/// The handle of the runtime registered under `name`, see [`Runtime::register`]
+	pub fn named(name: &str) -> Option<Handle> {
+		REGISTRY.lock().unwrap().get(name).cloned()
+	}
+
+	/// Spawns a future onto the runtime, it's inserted and first polled the next time the runtime polls.
+	///
+	/// Injected tasks bypass the runtime's [`max_tasks`](Builder::max_tasks) limit. Panics if the runtime was dropped, see [`try_spawn`](Handle::try_spawn)
+	pub fn spawn<T: Send + 'static, F: Future<Output = T> + Send + 'static>(&self, fut: F) -> tasks::TaskMonitor<T> {
+		self.try_spawn(fut).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// Like [`spawn`](Handle::spawn), but fails with [`RuntimeError::Closed`] once the runtime was dropped.
+	///
+	/// If the runtime is dropped before inserting the task, the task is dropped and its monitor resolves with an error
+	pub fn try_spawn<T: Send + 'static, F: Future<Output = T> + Send + 'static>(&self, fut: F) -> Result<tasks::TaskMonitor<T>, RuntimeError> {
+		let (result_tx, result_rx) = oneshot::channel(self.shared.allocator);
+		let monitor_waker = Counted::new_in(tasks::Lock::new(None), self.shared.allocator);
+
+		// checked under the lock, so the runtime can't be dropped in between
+		let task_id = match self.shared.injected.lock().unwrap().as_mut() {
+			Some(injected) => {
+				let task_id = self.shared.next_task_id()?;
+				let waker = monitor_waker.clone();
+
+				// boxed as `FnMut`, as unsized `FnOnce`s can't be called out of a `Boxed`
+				let mut insert = Some(move |rt: &Runtime| rt.insert_monitored(task_id, fut, result_tx, waker, Labels::default(), Start::Now));
+				let insert = move |rt: &Runtime| insert.take().map_or((), |insert| insert(rt));
+				injected.push(unsafe { Boxed::unsize(Boxed::new_in(insert, self.shared.allocator), |insert| insert as _) });
+				task_id
+			}
+			None => return Err(RuntimeError::Closed),
+		};
+
+		self.shared.notify();
+
+		Ok(tasks::TaskMonitor { id: task_id, result_rx, waker: monitor_waker, report: None, shared: self.shared.clone(), abort_on_drop: false, terminated: false })
+	}
+
+	/// Whether the runtime still exists, spawning fails once it's dropped
+	pub fn is_alive(&self) -> bool {
+		self.shared.injected.lock().unwrap().is_some()
+	}
+
+	/// Like [`Runtime::initiate_shutdown`], for signal handlers and other threads
+	pub fn initiate_shutdown(&self) -> bool {
+		self.shared.initiate_shutdown()
+	}
+}
+
+/// Runtime polling the calling task, if any
+#[cfg(feature = "std")]
+fn current<'a>() -> Option<&'a Runtime> {
+	// only set while the runtime is polling on this thread, so it outlives the calling task's poll
+	unsafe { CURRENT.get().as_ref() }
+}
+
+/// Allocator of the runtime polling the calling task, or the global one outside of a runtime
+#[cfg(feature = "std")]
+pub(crate) fn current_allocator() -> Allocator {
+	current().map_or(Allocator::GLOBAL, |rt| rt.shared.allocator)
+}
+
+// without `std` there's no current runtime to ask
+#[cfg(not(feature = "std"))]
+pub(crate) fn current_allocator() -> Allocator {
+	Allocator::GLOBAL
+}
+
+/// Records a timer firing for the task being polled, if the runtime has an event stream
+#[cfg(feature = "timers")]
+pub(crate) fn emit_timer_fired(due: time::Instant) {
+	if let Some(rt) = current() {
+		rt.emit(|| Event::TimerFired { task: rt.polling.get(), due })
+	}
+}
+
+/// Wakes `waker`, a task of any runtime is then polled ahead of ordinary wakes in its batch. Used by high priority timers
+#[cfg(feature = "timers")]
+pub(crate) fn wake_prioritized(waker: &task::Waker) {
+	// set before queueing, so the drain taking the wake sees it
+	if let Some(data) = WakerData::of(waker) {
+		data.urgent.store(true, atomic::Ordering::Relaxed);
+	}
+
+	waker.wake_by_ref();
+}
+
+/// Records an instrumented future completing within the task being polled, if the runtime has an event stream
+#[cfg(feature = "std")]
+pub(crate) fn emit_future_completed(name: String, elapsed: time::Duration, busy: time::Duration, polls: u64) {
+	if let Some(rt) = current() {
+		rt.emit(|| Event::FutureCompleted { name, task: rt.polling.get(), elapsed, busy, polls })
+	}
+}
+
+/// Attaches an edge to the task being polled, shown by [`Runtime::task_dump`]
+#[cfg(feature = "std")]
+pub(crate) fn record_await(awaiting: tasks::Awaiting) {
+	let Some(rt) = current() else { return };
+
+	// futures polled by `block_on` directly aren't tasks
+	if let Some(id) = rt.polling.get()
+		&& let Ok(mut tasks) = rt.tasks.try_borrow_mut()
+		&& let Some(task) = tasks.get_mut(&id)
+		&& !task.awaiting.contains(&awaiting)
+	{
+		task.awaiting.push(awaiting)
+	}
+}
+
+/// Records a beat for the task being polled, see [`tasks::heartbeat`]
+#[cfg(feature = "std")]
+pub(crate) fn record_heartbeat(interval: time::Duration) {
+	let Some(rt) = current() else { return };
+
+	if let Some(id) = rt.polling.get()
+		&& let Ok(mut tasks) = rt.tasks.try_borrow_mut()
+		&& let Some(task) = tasks.get_mut(&id)
+	{
+		task.heartbeat = Some((interval, time::Instant::now()))
+	}
+}
+
+/// Marks a runtime as current while it polls, restoring the outer one afterwards, even when unwinding
+#[cfg(feature = "std")]
+struct CurrentGuard(*const Runtime);
+
+#[cfg(feature = "std")]
+impl CurrentGuard {
+	fn enter(rt: &Runtime) -> Self {
+		CurrentGuard(CURRENT.replace(rt))
+	}
+}
+
+#[cfg(feature = "std")]
+impl Drop for CurrentGuard {
+	fn drop(&mut self) {
+		CURRENT.set(self.0)
+	}
+}
+
+/// Wakes a blocked [`block_in_place`] call, through the parker it waits on
+#[cfg(feature = "std")]
+struct Unparker(Arc<dyn Parker>);
+
+#[cfg(feature = "std")]
+impl arena::Wake for Unparker {
+	fn wake_by_ref(&self) {
+		self.0.unpark()
+	}
+}
+
+/// Blocks the calling task until `fut` completes, while driving the runtime's other tasks, so sync code can wait on async code.
+///
+/// Outside of a runtime, it simply parks the current thread between polls of `fut`. Panics of other tasks are resumed into the caller
+#[cfg(feature = "std")]
+pub fn block_in_place<F: Future>(fut: F) -> F::Output {
+	let mut fut = core::pin::pin!(fut);
+
+	let rt = current();
+	let parker = rt.map(|rt| rt.shared.parker.clone()).unwrap_or_else(|| Arc::new(crate::primitives::thread::current()));
+
+	let allocator = rt.map_or(Allocator::GLOBAL, |rt| rt.shared.allocator);
+	let waker = arena::waker(Counted::new_in(Unparker(parker.clone()), allocator));
+	let mut context = task::Context::from_waker(&waker);
+
+	loop {
+		// the calling task's budget may already be exhausted
+		if let task::Poll::Ready(res) = coop::with_budget(|| fut.as_mut().poll(&mut context)) {
+			return res;
+		}
+
+		if let Some(rt) = rt
+			&& let Err((_, payload)) = rt.poll()
+		{
+			resume_unwind(payload)
+		}
+
+		parker.park();
+	}
+}
+
+/// Spawns `fut` on the runtime polling the calling task, waiting for a free slot if [`Builder::max_tasks`] is reached.
+///
+/// Panics if not awaited within a runtime's task
+#[cfg(feature = "std")]
+pub async fn spawn_when_available<T: 'static, F: Future<Output = T> + 'static>(fut: F) -> tasks::TaskMonitor<T> {
+	let mut fut = Some(fut);
+
+	core::future::poll_fn(|cx| {
+		let rt = current().expect("spawn_when_available must be awaited within a runtime task");
+
+		if !rt.has_capacity() {
+			rt.capacity_waiters.borrow_mut().push(cx.waker().clone());
+			return task::Poll::Pending;
+		}
+
+		task::Poll::Ready(rt.spawn_monitored(fut.take().unwrap(), None, Labels::default(), Start::Now).unwrap_or_else(|e| panic!("{}", e)))
+	})
+	.await
+}
+
+#[cfg(feature = "std")]
+fn resume_unwind(payload: Panic) -> ! {
+	std::panic::resume_unwind(payload)
+}
+
+// without `std`, panics are never caught
+#[cfg(not(feature = "std"))]
+fn resume_unwind(_: Panic) -> ! {
+	unreachable!()
 }
@@ -0,0 +1,432 @@
+use alloc::{collections::VecDeque, vec::Vec};
+use core::{cell, fmt, future, mem, ops, task};
+
+/// Multi-producer multi-consumer channels, for distributing work across tasks
+pub mod mpmc;
+
+/// Lets a fixed number of tasks wait for each other, releasing them all once the last one arrives. Reusable across phases
+pub struct Barrier {
+	parties: usize,
+	state: cell::RefCell<BarrierState>,
+}
+
+struct BarrierState {
+	arrived: usize,
+	/// bumped each time the barrier releases its waiters
+	generation: usize,
+	wakers: Vec<Option<task::Waker>>,
+}
+
+/// Returned by [`Barrier::wait`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+	/// Exactly one task per phase is the leader, the one that arrived last
+	pub fn is_leader(&self) -> bool {
+		self.0
+	}
+}
+
+impl Barrier {
+	/// Creates a barrier releasing every `parties` waiters. A barrier of zero parties behaves like one of a single party
+	pub fn new(parties: usize) -> Self {
+		Barrier { parties: parties.max(1), state: cell::RefCell::new(BarrierState { arrived: 0, generation: 0, wakers: Vec::new() }) }
+	}
+
+	/// Waits until all parties have arrived. Dropping the future before then still counts the task as arrived
+	pub async fn wait(&self) -> BarrierWaitResult {
+		let (generation, slot) = {
+			let mut state = self.state.borrow_mut();
+			state.arrived += 1;
+
+			if state.arrived == self.parties {
+				state.arrived = 0;
+				state.generation = state.generation.wrapping_add(1);
+				state.wakers.drain(..).flatten().for_each(task::Waker::wake);
+
+				return BarrierWaitResult(true);
+			}
+
+			state.wakers.push(None);
+			(state.generation, state.wakers.len() - 1)
+		};
+
+		future::poll_fn(|cx| {
+			let mut state = self.state.borrow_mut();
+
+			match state.generation == generation {
+				true => {
+					state.wakers[slot] = Some(cx.waker().clone());
+					task::Poll::Pending
+				}
+				false => task::Poll::Ready(BarrierWaitResult(false)),
+			}
+		})
+		.await
+	}
+}
+
+/// A cell initialized at most once, by whichever task gets to it first, while other tasks wait for the result
+pub struct OnceCell<T> {
+	value: cell::OnceCell<T>,
+	initializing: cell::Cell<bool>,
+	waiters: cell::RefCell<Vec<task::Waker>>,
+}
+
+impl<T> Default for OnceCell<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T: fmt::Debug> fmt::Debug for OnceCell<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_tuple("OnceCell").field(&self.value.get()).finish()
+	}
+}
+
+impl<T> OnceCell<T> {
+	/// Creates an empty cell
+	pub const fn new() -> Self {
+		OnceCell { value: cell::OnceCell::new(), initializing: cell::Cell::new(false), waiters: cell::RefCell::new(Vec::new()) }
+	}
+
+	/// The value, if initialized
+	pub fn get(&self) -> Option<&T> {
+		self.value.get()
+	}
+
+	/// Initializes the cell with `value`, failing with it if the cell is initialized, or an initializer is running
+	pub fn set(&self, value: T) -> Result<(), T> {
+		if self.initializing.get() {
+			return Err(value);
+		}
+
+		self.value.set(value)?;
+		self.wake_waiters();
+		Ok(())
+	}
+
+	/// Returns the value, running `init` if the cell is empty. Concurrent callers wait for the running initializer instead of starting their own.
+	///
+	/// If the initializing task is cancelled, one of the waiting tasks runs its own initializer
+	pub async fn get_or_init<F: AsyncFnOnce() -> T>(&self, init: F) -> &T {
+		loop {
+			if let Some(value) = self.value.get() {
+				return value;
+			}
+
+			if !self.initializing.get() {
+				break;
+			}
+
+			// another task is initializing, wait for it to finish or be dropped
+			let mut registered = false;
+			future::poll_fn(|cx| match self.initializing.get() && !mem::replace(&mut registered, true) {
+				true => {
+					self.waiters.borrow_mut().push(cx.waker().clone());
+					task::Poll::Pending
+				}
+				false => task::Poll::Ready(()),
+			})
+			.await;
+		}
+
+		// released even if the initializer is cancelled, waking a waiter to take over
+		let _guard = Initializing::enter(self);
+		let value = init().await;
+
+		let _ = self.value.set(value);
+		self.value.get().unwrap()
+	}
+
+	/// Takes the value out of the cell
+	pub fn into_inner(self) -> Option<T> {
+		self.value.into_inner()
+	}
+
+	fn wake_waiters(&self) {
+		let waiters = mem::take(&mut *self.waiters.borrow_mut());
+		waiters.into_iter().for_each(task::Waker::wake);
+	}
+}
+
+/// Marks a [`OnceCell`] as being initialized, for as long as it lives
+struct Initializing<'a, T>(&'a OnceCell<T>);
+
+impl<'a, T> Initializing<'a, T> {
+	fn enter(cell: &'a OnceCell<T>) -> Self {
+		cell.initializing.set(true);
+		Initializing(cell)
+	}
+}
+
+impl<T> Drop for Initializing<'_, T> {
+	fn drop(&mut self) {
+		self.0.initializing.set(false);
+		self.0.wake_waiters();
+	}
+}
+
+/// Tasks waiting on a primitive, woken in the order they arrived
+#[derive(Default)]
+struct WaitList {
+	next: usize,
+	queue: VecDeque<(usize, task::Waker)>,
+}
+
+impl WaitList {
+	const fn new() -> Self {
+		WaitList { next: 0, queue: VecDeque::new() }
+	}
+
+	fn position(&self, key: usize) -> Option<usize> {
+		self.queue.iter().position(|(k, _)| *k == key)
+	}
+
+	/// Wakes the longest waiting task, if any
+	fn wake_one(&mut self) -> bool {
+		self.queue.pop_front().map(|(_, waker)| waker.wake()).is_some()
+	}
+
+	fn wake_all(&mut self) {
+		self.queue.drain(..).for_each(|(_, waker)| waker.wake());
+	}
+
+	/// Joins the back of the list under `key`, or refreshes the waker if already queued. A waiter woken for nothing, another task getting there first, keeps its place at the front
+	fn register(&mut self, key: &cell::Cell<Option<usize>>, waker: &task::Waker) {
+		let woken = match key.get() {
+			Some(key) => match self.position(key) {
+				Some(at) => return self.queue[at].1.clone_from(waker),
+				None => true,
+			},
+			None => false,
+		};
+
+		key.set(Some(self.next));
+		let entry = (self.next, waker.clone());
+		self.next = self.next.wrapping_add(1);
+
+		match woken {
+			true => self.queue.push_front(entry),
+			false => self.queue.push_back(entry),
+		}
+	}
+
+	/// `key` was registered, then woken since
+	fn is_woken(&self, key: &cell::Cell<Option<usize>>) -> bool {
+		key.get().is_some_and(|key| self.position(key).is_none())
+	}
+
+	/// Leaves the list, consuming any wake-up received
+	fn leave(&mut self, key: &cell::Cell<Option<usize>>) {
+		if let Some(at) = key.take().and_then(|key| self.position(key)) {
+			self.queue.remove(at);
+		}
+	}
+
+	/// Leaves the list, passing any wake-up received on to the next waiter
+	fn release(&mut self, key: &cell::Cell<Option<usize>>) {
+		if self.is_woken(key) {
+			self.wake_one();
+		}
+		self.leave(key);
+	}
+}
+
+/// A task's place in a [`WaitList`]. Dropping it after being woken, but before acting on it, passes the wake-up on to the next waiter
+struct Waiter<'a> {
+	list: &'a cell::RefCell<WaitList>,
+	key: cell::Cell<Option<usize>>,
+}
+
+impl<'a> Waiter<'a> {
+	fn new(list: &'a cell::RefCell<WaitList>) -> Self {
+		Waiter { list, key: cell::Cell::new(None) }
+	}
+
+	fn register(&self, waker: &task::Waker) {
+		self.list.borrow_mut().register(&self.key, waker);
+	}
+
+	fn is_woken(&self) -> bool {
+		self.list.borrow().is_woken(&self.key)
+	}
+
+	fn leave(&self) {
+		self.list.borrow_mut().leave(&self.key);
+	}
+}
+
+impl Drop for Waiter<'_> {
+	fn drop(&mut self) {
+		self.list.borrow_mut().release(&self.key);
+	}
+}
+
+/// An async mutual exclusion lock, for holding a value across `.await` points. Tasks waiting on it acquire it in arrival order
+pub struct Mutex<T> {
+	locked: cell::Cell<bool>,
+	waiters: cell::RefCell<WaitList>,
+	value: cell::UnsafeCell<T>,
+}
+
+impl<T: Default> Default for Mutex<T> {
+	fn default() -> Self {
+		Self::new(T::default())
+	}
+}
+
+impl<T: fmt::Debug> fmt::Debug for Mutex<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.try_lock() {
+			Some(guard) => f.debug_tuple("Mutex").field(&*guard).finish(),
+			None => f.write_str("Mutex(<locked>)"),
+		}
+	}
+}
+
+impl<T> Mutex<T> {
+	/// Creates an unlocked mutex holding `value`
+	pub const fn new(value: T) -> Self {
+		Mutex { locked: cell::Cell::new(false), waiters: cell::RefCell::new(WaitList::new()), value: cell::UnsafeCell::new(value) }
+	}
+
+	/// Waits until the lock is free, then acquires it
+	pub async fn lock(&self) -> MutexGuard<'_, T> {
+		let waiter = Waiter::new(&self.waiters);
+
+		future::poll_fn(|cx| match self.try_lock() {
+			Some(guard) => {
+				waiter.leave();
+				task::Poll::Ready(guard)
+			}
+			None => {
+				waiter.register(cx.waker());
+				task::Poll::Pending
+			}
+		})
+		.await
+	}
+
+	/// Acquires the lock if it is free
+	pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+		match self.locked.replace(true) {
+			true => None,
+			false => Some(MutexGuard { mutex: self }),
+		}
+	}
+
+	/// Mutable access to the value, no locking needed given exclusive access
+	pub fn get_mut(&mut self) -> &mut T {
+		self.value.get_mut()
+	}
+
+	/// Takes the value out of the mutex
+	pub fn into_inner(self) -> T {
+		self.value.into_inner()
+	}
+}
+
+/// Holds a [`Mutex`] locked, releasing it once dropped
+pub struct MutexGuard<'a, T> {
+	mutex: &'a Mutex<T>,
+}
+
+impl<'a, T> MutexGuard<'a, T> {
+	/// The mutex this guard holds locked
+	pub fn mutex(this: &Self) -> &'a Mutex<T> {
+		this.mutex
+	}
+}
+
+impl<T> ops::Deref for MutexGuard<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		// the guard is the only way to reach the value while locked
+		unsafe { &*self.mutex.value.get() }
+	}
+}
+
+impl<T> ops::DerefMut for MutexGuard<'_, T> {
+	fn deref_mut(&mut self) -> &mut T {
+		unsafe { &mut *self.mutex.value.get() }
+	}
+}
+
+impl<T: fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(&**self, f)
+	}
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+	fn drop(&mut self) {
+		self.mutex.locked.set(false);
+		self.mutex.waiters.borrow_mut().wake_one();
+	}
+}
+
+/// Lets tasks wait for a condition guarded by a [`Mutex`] to change, without polling for it
+#[derive(Default)]
+pub struct Condvar {
+	waiters: cell::RefCell<WaitList>,
+}
+
+impl fmt::Debug for Condvar {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Condvar").field("waiters", &self.waiters.borrow().queue.len()).finish()
+	}
+}
+
+impl Condvar {
+	/// Creates a condition variable with no waiters
+	pub const fn new() -> Self {
+		Condvar { waiters: cell::RefCell::new(WaitList::new()) }
+	}
+
+	/// Releases `guard` and waits to be notified, then locks the mutex again. Dropping the future after being notified passes the notification on
+	pub async fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+		let mutex = guard.mutex;
+		let waiter = Waiter::new(&self.waiters);
+
+		// registered before unlocking, so a notification sent by the next holder isn't missed
+		waiter.register(task::Waker::noop());
+		drop(guard);
+
+		future::poll_fn(|cx| match waiter.is_woken() {
+			true => {
+				waiter.leave();
+				task::Poll::Ready(())
+			}
+			false => {
+				waiter.register(cx.waker());
+				task::Poll::Pending
+			}
+		})
+		.await;
+
+		mutex.lock().await
+	}
+
+	/// Waits on the condition variable for as long as `condition` holds
+	pub async fn wait_while<'a, T>(&self, mut guard: MutexGuard<'a, T>, mut condition: impl FnMut(&mut T) -> bool) -> MutexGuard<'a, T> {
+		while condition(&mut guard) {
+			guard = self.wait(guard).await;
+		}
+
+		guard
+	}
+
+	/// Wakes the longest waiting task, returning whether there was one
+	pub fn notify_one(&self) -> bool {
+		self.waiters.borrow_mut().wake_one()
+	}
+
+	/// Wakes every waiting task
+	pub fn notify_all(&self) {
+		self.waiters.borrow_mut().wake_all();
+	}
+}
@@ -0,0 +1,329 @@
+use super::{WaitList, Waiter};
+use alloc::{collections::VecDeque, rc::Rc};
+use core::{cell, fmt, future, mem, pin::Pin, task};
+
+/// Creates an unbounded channel. Any number of tasks may send and receive, each message going to exactly one receiver
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+	channel(None)
+}
+
+/// Creates a channel holding at most `capacity` messages, senders wait for room once it is full. A capacity of zero behaves like one of a single message
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+	channel(Some(capacity.max(1)))
+}
+
+fn channel<T>(capacity: Option<usize>) -> (Sender<T>, Receiver<T>) {
+	let chan = Rc::new(Chan {
+		queue: cell::RefCell::new(VecDeque::new()),
+		capacity,
+		senders: cell::Cell::new(1),
+		receivers: cell::Cell::new(1),
+		recv_waiters: cell::RefCell::new(WaitList::new()),
+		send_waiters: cell::RefCell::new(WaitList::new()),
+	});
+
+	(Sender { chan: chan.clone() }, Receiver { chan, waiting: cell::Cell::new(None) })
+}
+
+/// State shared by every half of a channel
+struct Chan<T> {
+	queue: cell::RefCell<VecDeque<T>>,
+	capacity: Option<usize>,
+	senders: cell::Cell<usize>,
+	receivers: cell::Cell<usize>,
+	/// receivers waiting for a message, served in the order they started waiting
+	recv_waiters: cell::RefCell<WaitList>,
+	/// senders waiting for room in a bounded channel, also served in order
+	send_waiters: cell::RefCell<WaitList>,
+}
+
+/// Sending half of a channel, cloned to add producers
+pub struct Sender<T> {
+	chan: Rc<Chan<T>>,
+}
+
+impl<T> Sender<T> {
+	/// Waits for room in the channel, then queues `message`. Never waits on unbounded channels. Fails with the message if every receiver was dropped
+	pub async fn send(&self, message: T) -> Result<(), SendError<T>> {
+		let waiter = Waiter::new(&self.chan.send_waiters);
+		let mut message = Some(message);
+
+		future::poll_fn(|cx| match self.try_send(message.take().unwrap()) {
+			Ok(()) => {
+				waiter.leave();
+				task::Poll::Ready(Ok(()))
+			}
+			Err(TrySendError::Closed(message)) => {
+				waiter.leave();
+				task::Poll::Ready(Err(SendError(message)))
+			}
+			Err(TrySendError::Full(rejected)) => {
+				message = Some(rejected);
+				waiter.register(cx.waker());
+				task::Poll::Pending
+			}
+		})
+		.await
+	}
+
+	/// Queues `message` if there is room, waking a waiting receiver. Lets producers shed load instead of waiting
+	pub fn try_send(&self, message: T) -> Result<(), TrySendError<T>> {
+		if self.chan.receivers.get() == 0 {
+			return Err(TrySendError::Closed(message));
+		}
+
+		let mut queue = self.chan.queue.borrow_mut();
+		if self.chan.capacity.is_some_and(|capacity| queue.len() >= capacity) {
+			return Err(TrySendError::Full(message));
+		}
+
+		queue.push_back(message);
+		drop(queue);
+
+		self.chan.recv_waiters.borrow_mut().wake_one();
+		Ok(())
+	}
+
+	/// Most messages the channel holds at once, `None` if unbounded
+	pub fn capacity(&self) -> Option<usize> {
+		self.chan.capacity
+	}
+
+	/// Messages queued, not yet received
+	pub fn len(&self) -> usize {
+		self.chan.queue.borrow().len()
+	}
+
+	/// No messages are queued
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Every receiver was dropped, sending fails from now on
+	pub fn is_closed(&self) -> bool {
+		self.chan.receivers.get() == 0
+	}
+}
+
+impl<T> Clone for Sender<T> {
+	fn clone(&self) -> Self {
+		self.chan.senders.set(self.chan.senders.get() + 1);
+		Sender { chan: self.chan.clone() }
+	}
+}
+
+impl<T> fmt::Debug for Sender<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Sender").field("len", &self.len()).field("closed", &self.is_closed()).finish()
+	}
+}
+
+impl<T> Drop for Sender<T> {
+	fn drop(&mut self) {
+		self.chan.senders.set(self.chan.senders.get() - 1);
+
+		// receivers drain what's left, then see the channel closed
+		if self.chan.senders.get() == 0 {
+			self.chan.recv_waiters.borrow_mut().wake_all();
+		}
+	}
+}
+
+/// Receiving half of a channel, cloned to add consumers. Also a [`Stream`](futures_core::Stream) of the messages received
+pub struct Receiver<T> {
+	chan: Rc<Chan<T>>,
+	/// place among the waiting receivers while polled as a stream
+	waiting: cell::Cell<Option<usize>>,
+}
+
+impl<T> Receiver<T> {
+	/// Waits for a message, or `None` once every sender was dropped and the queue is drained. Waiting receivers are served in arrival order
+	pub async fn recv(&self) -> Option<T> {
+		let waiter = Waiter::new(&self.chan.recv_waiters);
+
+		future::poll_fn(|cx| match self.try_recv() {
+			Ok(message) => {
+				waiter.leave();
+				task::Poll::Ready(Some(message))
+			}
+			Err(TryRecvError::Disconnected) => {
+				waiter.leave();
+				task::Poll::Ready(None)
+			}
+			Err(TryRecvError::Empty) => {
+				waiter.register(cx.waker());
+				task::Poll::Pending
+			}
+		})
+		.await
+	}
+
+	/// Takes a queued message, if any, making room for a waiting sender
+	pub fn try_recv(&self) -> Result<T, TryRecvError> {
+		let message = self.chan.queue.borrow_mut().pop_front();
+
+		match message {
+			Some(message) => {
+				self.chan.send_waiters.borrow_mut().wake_one();
+				Ok(message)
+			}
+			None if self.chan.senders.get() == 0 => Err(TryRecvError::Disconnected),
+			None => Err(TryRecvError::Empty),
+		}
+	}
+
+	/// Messages queued, not yet received
+	pub fn len(&self) -> usize {
+		self.chan.queue.borrow().len()
+	}
+
+	/// No messages are queued
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Every sender was dropped, queued messages may still be received
+	pub fn is_closed(&self) -> bool {
+		self.chan.senders.get() == 0
+	}
+}
+
+impl<T> Clone for Receiver<T> {
+	fn clone(&self) -> Self {
+		self.chan.receivers.set(self.chan.receivers.get() + 1);
+		Receiver { chan: self.chan.clone(), waiting: cell::Cell::new(None) }
+	}
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Receiver").field("len", &self.len()).field("closed", &self.is_closed()).finish()
+	}
+}
+
+impl<T> futures_core::Stream for Receiver<T> {
+	type Item = T;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Option<T>> {
+		let mut waiters = self.chan.recv_waiters.borrow_mut();
+
+		match self.try_recv() {
+			Ok(message) => {
+				waiters.leave(&self.waiting);
+				task::Poll::Ready(Some(message))
+			}
+			Err(TryRecvError::Disconnected) => {
+				waiters.leave(&self.waiting);
+				task::Poll::Ready(None)
+			}
+			Err(TryRecvError::Empty) => {
+				waiters.register(&self.waiting, cx.waker());
+				task::Poll::Pending
+			}
+		}
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		match self.is_closed() {
+			true => (self.len(), Some(self.len())),
+			false => (self.len(), None),
+		}
+	}
+}
+
+impl<T> futures_core::FusedStream for Receiver<T> {
+	fn is_terminated(&self) -> bool {
+		self.is_closed() && self.is_empty()
+	}
+}
+
+impl<T> Drop for Receiver<T> {
+	fn drop(&mut self) {
+		self.chan.recv_waiters.borrow_mut().release(&self.waiting);
+		self.chan.receivers.set(self.chan.receivers.get() - 1);
+
+		// nobody is left to receive them, dropped outside the borrow as they might hold senders
+		if self.chan.receivers.get() == 0 {
+			let queue = mem::take(&mut *self.chan.queue.borrow_mut());
+			drop(queue);
+
+			self.chan.send_waiters.borrow_mut().wake_all();
+		}
+	}
+}
+
+/// Error returned by [`Sender::send`] when every receiver was dropped, handing the message back
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("SendError(..)")
+	}
+}
+
+impl<T> fmt::Display for SendError<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Channel has no receivers left")
+	}
+}
+
+impl<T> core::error::Error for SendError<T> {}
+
+/// Error returned by [`Sender::try_send`], handing the message back
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TrySendError<T> {
+	/// The channel is bounded, and holds as many messages as it can
+	Full(T),
+	/// Every receiver was dropped
+	Closed(T),
+}
+
+impl<T> TrySendError<T> {
+	/// The message that couldn't be sent
+	pub fn into_inner(self) -> T {
+		match self {
+			TrySendError::Full(message) | TrySendError::Closed(message) => message,
+		}
+	}
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			TrySendError::Full(_) => f.write_str("Full(..)"),
+			TrySendError::Closed(_) => f.write_str("Closed(..)"),
+		}
+	}
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			TrySendError::Full(_) => write!(f, "Channel is full"),
+			TrySendError::Closed(_) => write!(f, "Channel has no receivers left"),
+		}
+	}
+}
+
+impl<T> core::error::Error for TrySendError<T> {}
+
+/// Error returned by [`Receiver::try_recv`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+	/// No messages are queued right now
+	Empty,
+	/// No messages are queued, and every sender was dropped
+	Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			TryRecvError::Empty => write!(f, "Channel is empty"),
+			TryRecvError::Disconnected => write!(f, "Channel is empty, and has no senders left"),
+		}
+	}
+}
+
+impl core::error::Error for TryRecvError {}
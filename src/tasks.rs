@@ -1,36 +1,751 @@
-use std::{future::Future, pin::Pin, task};
+use crate::{
+	arena::{Allocator, Boxed, Counted},
+	oneshot,
+	primitives::{self, atomic},
+};
+use alloc::{collections, string::String, vec::Vec};
+use core::{alloc::Layout, cell, fmt, future::Future, pin::Pin, ptr::NonNull, task};
+
+/// Unique identifier of a task within its [`Runtime`](crate::rt::Runtime)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(pub(crate) usize);
+
+impl fmt::Display for TaskId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Display::fmt(&self.0, f)
+	}
+}
 
 /// A long running future, results can be polled using [`TaskMonitor`]
 pub(crate) struct Task {
-	pub(crate) inner: Pin<Box<dyn Future<Output = ()>>>,
+	/// `None` while checked out by the runtime for polling
+	pub(crate) inner: Option<TaskFuture>,
 	pub(crate) waker: task::Waker,
-	pub(crate) monitor_waker: Option<oneshot::Receiver<task::Waker>>,
+	/// `waker` as decorated by [`Builder::wrap_wakers`](crate::rt::Builder::wrap_wakers), the one the task is polled with
+	pub(crate) wrapped: Option<task::Waker>,
+	pub(crate) monitor_waker: Option<Counted<Lock<Option<task::Waker>>>>,
+	pub(crate) name: Option<String>,
+	/// group given to [`spawn_in_group`](crate::rt::Runtime::spawn_in_group), cancelled together
+	pub(crate) group: Option<String>,
+	/// woken while checked out, it has to be polled again once returned
+	pub(crate) rewake: bool,
+	/// aborted while checked out, it has to be dropped once returned
+	pub(crate) aborted: bool,
+	/// driven by `block_on`, whose caller receives its panics under any policy
+	#[cfg(feature = "std")]
+	pub(crate) root: bool,
+	#[cfg(feature = "std")]
+	pub(crate) stats: PollStats,
+	/// when the task was spawned, and how many wakes it received since
+	#[cfg(feature = "std")]
+	pub(crate) spawned: std::time::Instant,
+	#[cfg(feature = "std")]
+	pub(crate) wakes: u64,
+	/// what the task's latest poll left it waiting on
+	#[cfg(feature = "std")]
+	pub(crate) awaiting: Vec<Awaiting>,
+	/// interval declared through [`heartbeat`], and when the latest beat happened
+	#[cfg(feature = "std")]
+	pub(crate) heartbeat: Option<(std::time::Duration, std::time::Instant)>,
+	/// consecutive polls that ended with the task waking itself
+	#[cfg(feature = "std")]
+	pub(crate) self_woken: u32,
+}
+
+impl Task {
+	pub(crate) fn new(inner: TaskFuture, waker: task::Waker, monitor_waker: Option<Counted<Lock<Option<task::Waker>>>>, name: Option<String>) -> Self {
+		Task {
+			inner: Some(inner),
+			waker,
+			wrapped: None,
+			monitor_waker,
+			name,
+			group: None,
+			rewake: false,
+			aborted: false,
+			#[cfg(feature = "std")]
+			root: false,
+			#[cfg(feature = "std")]
+			stats: PollStats::default(),
+			#[cfg(feature = "std")]
+			spawned: std::time::Instant::now(),
+			#[cfg(feature = "std")]
+			wakes: 0,
+			#[cfg(feature = "std")]
+			awaiting: Vec::new(),
+			#[cfg(feature = "std")]
+			heartbeat: None,
+			#[cfg(feature = "std")]
+			self_woken: 0,
+		}
+	}
+
+	/// How long ago the task should have sent its next heartbeat, if it missed it
+	#[cfg(feature = "std")]
+	pub(crate) fn overdue(&self, now: std::time::Instant) -> Option<std::time::Duration> {
+		let (interval, last) = self.heartbeat?;
+		now.saturating_duration_since(last).checked_sub(interval).filter(|late| !late.is_zero())
+	}
+
+	/// Drops the future first, then wakes anyone awaiting the monitor so it observes the task as finished
+	pub(crate) fn close(self) {
+		drop(self.inner);
+
+		if let Some(waker) = self.monitor_waker.and_then(|w| w.with(Option::take)) {
+			waker.wake()
+		}
+	}
+}
+
+/// Type-erased future of a task, stored in memory recycled through a [`Pool`]
+pub(crate) struct TaskFuture {
+	ptr: NonNull<dyn Future<Output = ()>>,
+	pool: Counted<Pool>,
+}
+
+impl TaskFuture {
+	pub(crate) fn new<F: Future<Output = ()> + 'static>(fut: F, pool: &Counted<Pool>) -> Self {
+		let raw = pool.alloc(Layout::new::<F>()).cast::<F>();
+		unsafe { raw.as_ptr().write(fut) };
+
+		TaskFuture { ptr: raw, pool: pool.clone() }
+	}
+
+	pub(crate) fn poll(&mut self, cx: &mut task::Context<'_>) -> task::Poll<()> {
+		// the future is never moved out of its allocation
+		unsafe { Pin::new_unchecked(self.ptr.as_mut()) }.poll(cx)
+	}
+}
+
+impl Drop for TaskFuture {
+	fn drop(&mut self) {
+		let layout = Layout::for_value(unsafe { self.ptr.as_ref() });
+
+		unsafe {
+			self.ptr.as_ptr().drop_in_place();
+			self.pool.free(layout, self.ptr.cast());
+		}
+	}
+}
+
+/// Free list of task allocations, grouped by layout, so spawning similar tasks doesn't hit the allocator.
+///
+/// Futures fitting in [`SLOT`] are instead carved out of shared chunks, one allocation covers [`SLOTS_PER_CHUNK`] tiny tasks
+pub(crate) struct Pool {
+	free: cell::RefCell<Blocks>,
+	/// blocks kept around at most, further frees go back to the allocator
+	capacity: usize,
+	len: cell::Cell<usize>,
+	/// unused slots for small futures, never returned to the allocator until the pool drops
+	slots: cell::RefCell<Vec<NonNull<u8>>>,
+	chunks: cell::RefCell<Vec<NonNull<u8>>>,
+	/// memory for task futures comes from here
+	allocator: Allocator,
+}
+
+/// Layout of a slot for a small future, most async blocks capturing a few handles fit
+const SLOT: Layout = match Layout::from_size_align(64, 16) {
+	Ok(layout) => layout,
+	Err(_) => unreachable!(),
+};
+
+const SLOTS_PER_CHUNK: usize = 32;
+
+fn chunk_layout() -> Layout {
+	Layout::from_size_align(SLOT.size() * SLOTS_PER_CHUNK, SLOT.align()).unwrap()
+}
+
+fn fits_slot(layout: Layout) -> bool {
+	layout.size() <= SLOT.size() && layout.align() <= SLOT.align()
+}
+
+/// Free blocks, keyed by size and alignment
+type Blocks = collections::BTreeMap<(usize, usize), Vec<NonNull<u8>>>;
+
+impl Pool {
+	pub(crate) fn new(capacity: usize, allocator: Allocator) -> Self {
+		Pool { free: cell::RefCell::new(collections::BTreeMap::new()), capacity, len: cell::Cell::new(0), slots: cell::RefCell::new(Vec::new()), chunks: cell::RefCell::new(Vec::new()), allocator }
+	}
+
+	pub(crate) fn alloc(&self, layout: Layout) -> NonNull<u8> {
+		// zero sized futures need no memory, just an aligned address
+		if layout.size() == 0 {
+			return NonNull::new(layout.align() as *mut u8).unwrap();
+		}
+
+		if fits_slot(layout) {
+			let mut slots = self.slots.borrow_mut();
+
+			if slots.is_empty() {
+				let chunk = self.allocator.allocate(chunk_layout());
+				slots.extend((0..SLOTS_PER_CHUNK).rev().map(|i| unsafe { chunk.add(i * SLOT.size()) }));
+				self.chunks.borrow_mut().push(chunk);
+			}
+
+			return slots.pop().unwrap();
+		}
+
+		if let Some(block) = self.free.borrow_mut().get_mut(&(layout.size(), layout.align())).and_then(Vec::pop) {
+			self.len.set(self.len.get() - 1);
+			return block;
+		}
+
+		self.allocator.allocate(layout)
+	}
+
+	/// Safety: `block` must have been returned by [`alloc`](Pool::alloc) with the same `layout`
+	pub(crate) unsafe fn free(&self, layout: Layout, block: NonNull<u8>) {
+		if layout.size() == 0 {
+			return;
+		}
+
+		if fits_slot(layout) {
+			self.slots.borrow_mut().push(block);
+			return;
+		}
+
+		match self.len.get() < self.capacity {
+			true => {
+				self.free.borrow_mut().entry((layout.size(), layout.align())).or_default().push(block);
+				self.len.set(self.len.get() + 1);
+			}
+			false => unsafe { self.allocator.deallocate(block, layout) },
+		}
+	}
+}
+
+impl Drop for Pool {
+	fn drop(&mut self) {
+		for ((size, align), blocks) in self.free.take() {
+			let layout = Layout::from_size_align(size, align).unwrap();
+
+			for block in blocks {
+				unsafe { self.allocator.deallocate(block, layout) }
+			}
+		}
+
+		for chunk in self.chunks.take() {
+			unsafe { self.allocator.deallocate(chunk, chunk_layout()) }
+		}
+	}
+}
+
+/// Time spent polling a task, from [`Runtime::poll_stats`](crate::rt::Runtime::poll_stats)
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PollStats {
+	/// Number of times the task was polled
+	pub polls: u64,
+	/// Total time spent in the task's polls
+	pub total: std::time::Duration,
+	/// Duration of the task's longest poll
+	pub max: std::time::Duration,
+	/// Duration of the task's most recent poll
+	pub last: std::time::Duration,
+	/// Polls that ended with the task already woken again, by itself or while it was being polled
+	pub self_wakes: u64,
+}
+
+#[cfg(feature = "std")]
+impl PollStats {
+	pub(crate) fn record(&mut self, elapsed: std::time::Duration) {
+		self.polls += 1;
+		self.total += elapsed;
+		self.max = self.max.max(elapsed);
+		self.last = elapsed;
+	}
+}
+
+/// What a task was doing when a [`TaskDump`] was taken
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+	/// Being polled, the dump was taken from within a task
+	Running,
+	/// Woken, waiting for the runtime to poll it
+	Scheduled,
+	/// Waiting for a wake
+	Idle,
+}
+
+/// A live task, from [`Runtime::task_dump`](crate::rt::Runtime::task_dump)
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskDump {
+	/// Id of the task
+	pub id: TaskId,
+	/// Name given to [`spawn_named`](crate::rt::Runtime::spawn_named)
+	pub name: Option<String>,
+	/// What the task was doing
+	pub state: TaskState,
+	/// Time since the task was spawned
+	pub age: std::time::Duration,
+	/// Wakes the task received
+	pub wakes: u64,
+	/// Time spent polling the task
+	pub stats: PollStats,
+	/// Monitors and timers the task's latest poll is waiting on
+	pub awaiting: Vec<Awaiting>,
+	/// How late the task's next [`heartbeat`] is, `None` if it's on time or never sent one
+	pub overdue: Option<std::time::Duration>,
+}
+
+/// Something a task is blocked on, recorded when it returns pending while polling it
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Awaiting {
+	/// The [`TaskMonitor`] of another task
+	Task(TaskId),
+	/// A timer, due at the given instant
+	#[cfg(feature = "timers")]
+	Timer(std::time::Instant),
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for Awaiting {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Awaiting::Task(id) => write!(f, "task {}", id),
+			#[cfg(feature = "timers")]
+			Awaiting::Timer(due) => write!(f, "timer due in {:?}", due.saturating_duration_since(crate::timers::now())),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for TaskDump {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "task {}", self.id)?;
+
+		if let Some(name) = &self.name {
+			write!(f, " {:?}", name)?;
+		}
+
+		write!(f, ": {:?} for {:?}, {} wakes, {} polls, last poll took {:?}", self.state, self.age, self.wakes, self.stats.polls, self.stats.last)?;
+
+		if self.stats.self_wakes > 0 {
+			write!(f, ", {} self wakes", self.stats.self_wakes)?;
+		}
+
+		for (i, awaiting) in self.awaiting.iter().enumerate() {
+			write!(f, "{}{}", if i == 0 { ", awaiting " } else { " and " }, awaiting)?;
+		}
+
+		if let Some(overdue) = self.overdue {
+			write!(f, ", heartbeat overdue by {:?}", overdue)?;
+		}
+
+		Ok(())
+	}
 }
 
 /// Future that stays pending until [`Task`](crate::rt::Runtime::spawn) completes
-pub struct TaskMonitor<T> {
+pub struct TaskMonitor<T, S = ()> {
+	pub(crate) id: TaskId,
 	pub(crate) result_rx: oneshot::Receiver<T>,
-	pub(crate) waker_tx: Option<oneshot::Sender<task::Waker>>,
+	pub(crate) waker: Counted<Lock<Option<task::Waker>>>,
+	pub(crate) report: Option<Counted<Report<S>>>,
+	pub(crate) shared: Counted<crate::rt::Shared>,
+	pub(crate) abort_on_drop: bool,
+	pub(crate) terminated: bool,
 }
 
-impl<T> Unpin for TaskMonitor<T> {}
+impl<T, S> TaskMonitor<T, S> {
+	/// Id of the monitored task
+	pub fn id(&self) -> TaskId {
+		self.id
+	}
 
-impl<T> Future for TaskMonitor<T> {
-	type Output = Option<T>;
+	/// Checks whether the task has stopped running, either by completing or being dropped. Doesn't require polling the monitor
+	pub fn is_finished(&self) -> bool {
+		self.result_rx.has_message() || self.result_rx.is_closed()
+	}
+
+	/// Cancels the task, dropping its future the next time the runtime polls. Awaiting the monitor then returns `None`
+	pub fn abort(&self) {
+		if !self.is_finished() {
+			self.shared.abort(self.id);
+		}
+	}
+
+	/// Parks the calling thread until the task completes, returning `None` if it was dropped.
+	///
+	/// Meant for threads outside the runtime, calling this from the runtime's own thread will block forever
+	#[cfg(feature = "std")]
+	pub fn join_blocking(self) -> Option<T> {
+		self.result_rx.recv_blocking().ok()
+	}
+
+	/// Makes dropping this monitor [`abort`](TaskMonitor::abort) the task, instead of leaving it detached
+	pub fn abort_on_drop(mut self) -> Self {
+		self.abort_on_drop = true;
+		self
+	}
+
+	/// Latest fraction reported through [`Progress::report`], in the range `0.0..=1.0`
+	pub fn progress(&self) -> Option<f32> {
+		self.report.as_ref().and_then(|r| r.with(|(fraction, _)| *fraction))
+	}
 
-	fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
-		match self.result_rx.try_recv() {
-			Ok(v) => task::Poll::Ready(Some(v)),
+	/// Latest status reported through [`Progress::report_status`]
+	pub fn status(&self) -> Option<S>
+	where
+		S: Clone,
+	{
+		self.report.as_ref().and_then(|r| r.with(|(_, status)| status.clone()))
+	}
+
+	/// Transforms the task's result once it completes, `f` runs within whichever task awaits the returned future
+	pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Map<T, S, F> {
+		Map { monitor: self, f: Some(f) }
+	}
+
+	/// Spawns a follow-up task on `rt`, running the future returned by `f` once this task completes. Stages of a pipeline can then be chained without a wrapper task.
+	///
+	/// The follow-up's monitor resolves to `None` if this task is dropped, or the follow-up itself is
+	pub fn and_then<U: 'static, Fut: Future<Output = U> + 'static>(self, rt: &crate::rt::Runtime, f: impl FnOnce(T) -> Fut + 'static) -> TaskMonitor<U>
+	where
+		T: 'static,
+		S: 'static,
+	{
+		rt.spawn_chained(self, f).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	fn poll_result(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Option<T>> {
+		let result = match self.result_rx.try_recv() {
+			Ok(v) => Some(v),
+			Err(oneshot::TryRecvError::Disconnected) => None,
 			Err(oneshot::TryRecvError::Empty) => {
-				if let Some(tx) = self.waker_tx.take() {
-					if tx.send(cx.waker().clone()).is_err() {
-						return task::Poll::Ready(None);
-					};
+				// the latest waker always replaces older ones, monitors can move between tasks
+				self.waker.with(|waker| match waker {
+					Some(w) if w.will_wake(cx.waker()) => {}
+					_ => *waker = Some(cx.waker().clone()),
+				});
+
+				// the task may have completed before our waker was registered
+				match self.result_rx.try_recv() {
+					Ok(v) => Some(v),
+					Err(oneshot::TryRecvError::Disconnected) => None,
+					Err(oneshot::TryRecvError::Empty) => {
+						#[cfg(feature = "std")]
+						crate::rt::record_await(Awaiting::Task(self.id));
+
+						return task::Poll::Pending;
+					}
 				}
+			}
+		};
+
+		self.terminated = true;
+		task::Poll::Ready(result)
+	}
+}
+
+/// Handle given to tasks spawned by [`spawn_with_progress`](crate::rt::Runtime::spawn_with_progress), to report progress to their [`TaskMonitor`]
+pub struct Progress<S = ()> {
+	pub(crate) report: Counted<Report<S>>,
+}
+
+impl<S> Clone for Progress<S> {
+	fn clone(&self) -> Self {
+		Progress { report: self.report.clone() }
+	}
+}
+
+impl<S> Progress<S> {
+	/// Reports how much of the task is complete, clamped to `0.0..=1.0`
+	pub fn report(&self, fraction: f32) {
+		self.report.with(|(latest, _)| *latest = Some(fraction.clamp(0.0, 1.0)))
+	}
+
+	/// Reports a custom status value, replacing the previous one
+	pub fn report_status(&self, status: S) {
+		self.report.with(|(_, latest)| *latest = Some(status))
+	}
+}
 
-				task::Poll::Pending
+/// Latest progress reported by a task, shared with its [`TaskMonitor`]
+pub(crate) type Report<S> = Lock<(Option<f32>, Option<S>)>;
+
+/// Minimal spin lock for state shared between tasks and monitors
+pub(crate) struct Lock<T> {
+	locked: atomic::AtomicBool,
+	value: primitives::UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for Lock<T> {}
+
+impl<T> Lock<T> {
+	pub(crate) fn new(value: T) -> Self {
+		Lock { locked: atomic::AtomicBool::new(false), value: primitives::UnsafeCell::new(value) }
+	}
+
+	pub(crate) fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+		// critical sections only move values in and out, spinning avoids requiring `std` for a lock
+		while self.locked.compare_exchange_weak(false, true, atomic::Ordering::Acquire, atomic::Ordering::Relaxed).is_err() {
+			primitives::spin_loop()
+		}
+
+		let res = self.value.with_mut(|value| f(unsafe { &mut *value }));
+		self.locked.store(false, atomic::Ordering::Release);
+
+		res
+	}
+}
+
+impl<T, S> Drop for TaskMonitor<T, S> {
+	fn drop(&mut self) {
+		if self.abort_on_drop {
+			self.abort();
+		}
+	}
+}
+
+impl<T, S> Unpin for TaskMonitor<T, S> {}
+
+/// Resolves to `None` if the task was dropped. Polling again after completion also returns `None`
+impl<T, S> Future for TaskMonitor<T, S> {
+	type Output = Option<T>;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+		let this = self.get_mut();
+		crate::coop::poll_budgeted(cx, |cx| this.poll_result(cx))
+	}
+}
+
+impl<T, S> futures_core::FusedFuture for TaskMonitor<T, S> {
+	fn is_terminated(&self) -> bool {
+		self.terminated
+	}
+}
+
+/// Waits for the first of `monitors` to finish, returning its result and index, along with the remaining monitors in their original order.
+///
+/// The remaining tasks keep running, [`abort`](TaskMonitor::abort) them to cancel the losers of a race. Panics if `monitors` is empty
+pub async fn select_all<T, S, I: IntoIterator<Item = TaskMonitor<T, S>>>(monitors: I) -> (Option<T>, usize, Vec<TaskMonitor<T, S>>) {
+	let mut monitors = monitors.into_iter().collect::<Vec<_>>();
+	assert!(!monitors.is_empty(), "select_all requires at least one monitor");
+
+	core::future::poll_fn(|cx| {
+		for i in 0..monitors.len() {
+			if let task::Poll::Ready(result) = Pin::new(&mut monitors[i]).poll(cx) {
+				monitors.remove(i);
+				return task::Poll::Ready((result, i, core::mem::take(&mut monitors)));
 			}
-			Err(oneshot::TryRecvError::Disconnected) => task::Poll::Ready(None),
 		}
+
+		task::Poll::Pending
+	})
+	.await
+}
+
+/// A [`TaskMonitor`] whose result is transformed, from [`TaskMonitor::map`]
+pub struct Map<T, S, F> {
+	monitor: TaskMonitor<T, S>,
+	f: Option<F>,
+}
+
+impl<T, S, F> Map<T, S, F> {
+	/// The underlying monitor, e.g. to check on or abort the task
+	pub fn get_ref(&self) -> &TaskMonitor<T, S> {
+		&self.monitor
+	}
+}
+
+// `f` is never pinned, only moved out once the monitor completes
+impl<T, S, F> Unpin for Map<T, S, F> {}
+
+/// Resolves to `None` if the task was dropped, `f` is then never called
+impl<T, S, U, F: FnOnce(T) -> U> Future for Map<T, S, F> {
+	type Output = Option<U>;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+		let this = self.get_mut();
+		let result = task::ready!(Pin::new(&mut this.monitor).poll(cx));
+
+		task::Poll::Ready(result.zip(this.f.take()).map(|(res, f)| f(res)))
 	}
 }
+
+impl<T, S, U, F: FnOnce(T) -> U> futures_core::FusedFuture for Map<T, S, F> {
+	fn is_terminated(&self) -> bool {
+		self.monitor.terminated
+	}
+}
+
+/// Owns a group of spawned tasks, yielding their results as they complete. Remaining tasks are aborted when dropped
+pub struct JoinSet<T> {
+	monitors: Vec<TaskMonitor<T>>,
+}
+
+impl<T> Default for JoinSet<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T> JoinSet<T> {
+	/// Creates an empty set
+	pub fn new() -> Self {
+		JoinSet { monitors: Vec::new() }
+	}
+
+	/// Spawns `fut` on `rt`, as a member of this set
+	pub fn spawn<F: Future<Output = T> + 'static>(&mut self, rt: &crate::rt::Runtime, fut: F) -> TaskId
+	where
+		T: 'static,
+	{
+		let monitor = rt.spawn(fut).abort_on_drop();
+		let id = monitor.id();
+
+		self.monitors.push(monitor);
+		id
+	}
+
+	/// Number of tasks that haven't been joined yet
+	pub fn len(&self) -> usize {
+		self.monitors.len()
+	}
+
+	/// Checks whether all tasks have been joined
+	pub fn is_empty(&self) -> bool {
+		self.monitors.is_empty()
+	}
+
+	/// Waits for the next task to complete, returning `None` once the set is empty. Aborted tasks yield `Some(None)`
+	pub async fn join_next(&mut self) -> Option<Option<T>> {
+		core::future::poll_fn(|cx| {
+			if self.monitors.is_empty() {
+				return task::Poll::Ready(None);
+			}
+
+			for i in 0..self.monitors.len() {
+				if let task::Poll::Ready(result) = Pin::new(&mut self.monitors[i]).poll(cx) {
+					self.monitors.swap_remove(i);
+					return task::Poll::Ready(Some(result));
+				}
+			}
+
+			task::Poll::Pending
+		})
+		.await
+	}
+
+	/// Aborts every task that hasn't been joined yet
+	pub fn abort_all(&mut self) {
+		self.monitors.clear();
+	}
+}
+
+/// Nursery for child tasks, created by [`scope`]. Children may borrow anything that outlives the scope
+pub struct Scope<'env, E> {
+	children: cell::RefCell<Vec<Child<'env, E>>>,
+	/// children are allocated from the runtime driving the scope, if any
+	allocator: Allocator,
+}
+
+type Child<'env, E> = Pin<Boxed<dyn Future<Output = Result<(), E>> + 'env>>;
+
+impl<'env, E> Scope<'env, E> {
+	/// Adds a child task, polled alongside the scope's body until it completes or a sibling fails
+	pub fn spawn<F: Future<Output = Result<(), E>> + 'env>(&self, fut: F) {
+		let child = unsafe { Boxed::unsize(Boxed::new_in(fut, self.allocator), |fut| fut as _) };
+		self.children.borrow_mut().push(Boxed::into_pin(child));
+	}
+
+	/// Polls every child, removing completed ones. On error or panic, all remaining children are dropped
+	fn poll_children(&self, cx: &mut task::Context<'_>) -> Result<(), E> {
+		// children can't reach the scope, so nothing is spawned while they're taken out
+		let mut children = core::mem::take(&mut *self.children.borrow_mut());
+		let mut i = 0;
+
+		while i < children.len() {
+			match children[i].as_mut().poll(cx) {
+				task::Poll::Ready(Ok(())) => drop(children.swap_remove(i)),
+				task::Poll::Ready(Err(e)) => return Err(e),
+				task::Poll::Pending => i += 1,
+			}
+		}
+
+		*self.children.borrow_mut() = children;
+		Ok(())
+	}
+}
+
+/// Cancels a scope's children if its body panics, disarmed once the body's poll returns
+struct CancelOnUnwind<'a, 'env, E>(&'a Scope<'env, E>);
+
+impl<E> Drop for CancelOnUnwind<'_, '_, E> {
+	fn drop(&mut self) {
+		self.0.children.borrow_mut().clear();
+	}
+}
+
+/// Marks the calling task as alive, and expects it to call again within `interval`.
+///
+/// Meant for a task's main loop, tasks that miss their heartbeat are counted by [`Metrics::missed_heartbeats`](crate::metrics::Metrics::missed_heartbeats)
+/// and flagged in [`TaskDump`]s. Catches tasks stuck waiting on something that never happens, which slow poll detection can't see. Completes immediately
+#[cfg(feature = "std")]
+pub async fn heartbeat(interval: std::time::Duration) {
+	crate::rt::record_heartbeat(interval)
+}
+
+/// Measures `fut`, reporting its time to completion, time spent polling it and poll count as an [`Event::FutureCompleted`](crate::rt::Event::FutureCompleted).
+///
+/// Cheap ad hoc profiling, e.g. of a request handler in production. Reported through [`Runtime::events`](crate::rt::Runtime::events), if a stream is alive
+#[cfg(feature = "std")]
+pub async fn instrumented<F: Future>(fut: F, name: impl Into<String>) -> F::Output {
+	let mut fut = core::pin::pin!(fut);
+	let (mut started, mut busy, mut polls) = (None, std::time::Duration::ZERO, 0);
+
+	let output = core::future::poll_fn(|cx| {
+		let poll_started = std::time::Instant::now();
+		started.get_or_insert(poll_started);
+
+		let poll = fut.as_mut().poll(cx);
+		busy += poll_started.elapsed();
+		polls += 1;
+
+		poll
+	})
+	.await;
+
+	let elapsed = started.map_or(std::time::Duration::ZERO, |s| s.elapsed());
+	crate::rt::emit_future_completed(name.into(), elapsed, busy, polls);
+
+	output
+}
+
+/// Runs `f` with a [`Scope`], completing only once the body and every child spawned on it have finished.
+///
+/// The first child or body to fail or panic cancels all others, and its error is returned or panic resumed.
+/// Children are polled together with the scope's own task, whenever any of them is woken
+pub async fn scope<'env, T, E, F>(f: F) -> Result<T, E>
+where
+	F: AsyncFnOnce(&Scope<'env, E>) -> Result<T, E>,
+{
+	let scope = Scope { children: cell::RefCell::new(Vec::new()), allocator: crate::rt::current_allocator() };
+	let mut body = core::pin::pin!(f(&scope));
+	let mut output = None;
+
+	core::future::poll_fn(|cx| {
+		if output.is_none() {
+			let guard = CancelOnUnwind(&scope);
+			let poll = body.as_mut().poll(cx);
+			core::mem::forget(guard);
+
+			if let task::Poll::Ready(res) = poll {
+				output = Some(res?);
+			}
+		}
+
+		// children spawned by the body are polled for the first time right away
+		scope.poll_children(cx)?;
+
+		match output.is_some() && scope.children.borrow().is_empty() {
+			true => task::Poll::Ready(Ok(output.take().unwrap())),
+			false => task::Poll::Pending,
+		}
+	})
+	.await
+}
@@ -1,68 +1,2656 @@
 use super::*;
 
+#[cfg(all(feature = "io", unix))]
+use {
+	crate::io::{self, Async},
+	std::{io::Read, io::Write, os::unix::net::UnixStream},
+};
+
 #[cfg(feature = "timers")]
 use {
-	crate::timers::sleep,
+	crate::timers::{self, sleep},
 	std::{fmt, time},
 };
 
 #[test]
-fn simple() {
-	let fut_1 = async { 42 };
-	let fut_2 = async move { fut_1.await + 1 };
-	let fut_3 = async move { fut_2.await + 1 };
+fn simple() {
+	let fut_1 = async { 42 };
+	let fut_2 = async move { fut_1.await + 1 };
+	let fut_3 = async move { fut_2.await + 1 };
+
+	let rt = rt::Runtime::new();
+	let result = rt.block_on(fut_3);
+
+	assert_eq!(result, 44);
+}
+
+#[test]
+fn task_spawn() {
+	let fut_60 = async {
+		let mut counter = 60;
+
+		loop {
+			futures::future::ready(()).await;
+			counter -= 1;
+
+			println!("Counter = {}", counter);
+			if counter == 0 {
+				break;
+			}
+		}
+	};
+
+	let rt = rt::Runtime::new();
+	let monitor = rt.spawn(fut_60);
+
+	rt.block_on(monitor);
+}
+
+#[test]
+fn wake_latency_metrics() {
+	use std::time::Duration;
+
+	let rt = rt::Runtime::new();
+
+	// the second task waits for the first one's slow poll
+	rt.spawn(async { std::thread::sleep(Duration::from_millis(20)) });
+	rt.spawn(async {});
+	rt.block_on(async {});
+
+	let metrics = rt.metrics();
+	assert_eq!(metrics.polls, 3);
+	assert_eq!(metrics.wake_latency.count(), 3);
+	assert!(metrics.wake_latency.max() >= Duration::from_millis(20));
+	assert!(metrics.wake_latency.quantile(0.1) < Duration::from_millis(20));
+	assert_eq!(metrics.wake_latency.quantile(1.0), metrics.wake_latency.max());
+	assert_eq!(metrics.wake_latency.buckets().map(|(_, n)| n).sum::<u64>(), 3);
+	assert_eq!(metrics.tasks, 0);
+}
+
+#[test]
+fn shared_runtime() {
+	let rt = std::rc::Rc::new(rt::Runtime::new());
+
+	// callbacks capture the runtime, and spawn without exclusive access
+	let on_click = {
+		let rt = rt.clone();
+		move |n: u32| rt.spawn(async move { n * 2 })
+	};
+
+	let clicks = [on_click(1), on_click(2)];
+	let inner = rt.clone();
+	let nested = rt.block_on(async move { inner.spawn(async { 3 }).await });
+
+	assert_eq!(nested, Some(3));
+	assert_eq!(rt.block_on(futures::future::join_all(clicks)), [Some(2), Some(4)]);
+}
+
+#[test]
+fn detached_tasks() {
+	let rt = rt::Runtime::new();
+	let flushed = std::rc::Rc::new(std::cell::Cell::new(0));
+
+	let counter = flushed.clone();
+	rt.spawn_detached(async move { counter.set(counter.get() + 1) });
+
+	let counter = flushed.clone();
+	let stuck = rt.spawn_detached(async move {
+		futures::future::pending::<()>().await;
+		counter.set(counter.get() + 10)
+	});
+
+	rt.block_on(async {});
+	assert_eq!((flushed.get(), rt.task_dump().len()), (1, 1));
+
+	rt.abort(stuck);
+	rt.block_on(async {});
+	assert!(rt.task_dump().is_empty());
+}
+
+#[test]
+fn current_runtime() {
+	let rt = rt::Runtime::new();
+	assert!(rt::Runtime::current().is_none());
+
+	let nested = rt.spawn(async {
+		// reachable without plumbing the runtime through
+		let local = rt::Runtime::with_current(|rt| rt.spawn(async { std::rc::Rc::new(2) })).unwrap();
+		let sent = rt::Runtime::current().unwrap().spawn(async { 3 });
+
+		*local.await.unwrap() + sent.await.unwrap()
+	});
+
+	assert_eq!(rt.block_on(nested), Some(5));
+	assert!(rt::Runtime::with_current(|_| ()).is_none());
+}
+
+#[test]
+fn lifecycle_events() {
+	use futures::StreamExt;
+
+	let rt = rt::Runtime::new();
+	let mut events = rt.events();
+
+	let worker = rt.spawn_named("worker", async {
+		#[cfg(feature = "timers")]
+		sleep(time::Duration::from_millis(1)).await;
+	});
+	let id = worker.id();
+	rt.block_on(worker);
+
+	let mut seen = Vec::new();
+	while let Some(Some(event)) = futures::FutureExt::now_or_never(events.next()) {
+		seen.push(event);
+	}
+
+	assert!(seen.contains(&rt::Event::TaskSpawned { id, name: Some("worker".into()), group: None }));
+	assert!(seen.iter().any(|e| matches!(e, rt::Event::TaskCompleted { id: done, .. } if *done == id)));
+
+	#[cfg(feature = "timers")]
+	assert!(seen.iter().any(|e| matches!(e, rt::Event::TimerFired { task: Some(task), .. } if *task == id)));
+
+	// a newer stream ends this one
+	let _newer = rt.events();
+	assert_eq!(futures::FutureExt::now_or_never(events.next()), Some(None));
+}
+
+#[test]
+fn instrumented_futures() {
+	use futures::StreamExt;
+
+	let rt = rt::Runtime::new();
+	let mut events = rt.events();
+
+	let (tx, rx) = futures::channel::oneshot::channel();
+	let handler = rt.spawn(tasks::instrumented(rx, "handler"));
+	let id = handler.id();
+
+	rt.run_ready();
+	tx.send(5).unwrap();
+	assert_eq!(rt.block_on(handler), Some(Ok(5)));
+
+	let mut reported = Vec::new();
+	while let Some(Some(event)) = futures::FutureExt::now_or_never(events.next()) {
+		if let rt::Event::FutureCompleted { name, task, elapsed, busy, polls } = event {
+			reported.push((name, task, busy <= elapsed, polls));
+		}
+	}
+
+	assert_eq!(reported, [("handler".to_string(), Some(id), true, 2)]);
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "wasi")))]
+fn foreign_event_loop() {
+	let (parker, fd) = rt::event_fd().unwrap();
+	let rt = rt::Builder::new().parker(parker).build();
+	let readable = || {
+		let mut fds = [0; 1];
+		let file = std::mem::ManuallyDrop::new(unsafe { <std::fs::File as std::os::fd::FromRawFd>::from_raw_fd(std::os::fd::AsRawFd::as_raw_fd(&fd)) });
+		matches!(std::io::Read::read(&mut &*file, &mut fds), Ok(1..))
+	};
+
+	let (tx, rx) = futures::channel::oneshot::channel();
+	let task = rt.spawn(rx);
+
+	// the spawn itself signals the loop
+	assert!(readable());
+	fd.clear();
+	rt.run_ready();
+	assert!(!readable() && !task.is_finished());
+
+	std::thread::spawn(move || tx.send(7).unwrap()).join().unwrap();
+	assert!(readable());
+	fd.clear();
+	rt.run_ready();
+	assert!(task.is_finished());
+}
+
+#[test]
+#[cfg(not(target_os = "wasi"))]
+fn callback_parker() {
+	let (post, posted) = std::sync::mpsc::channel();
+	let rt = rt::Builder::new().parker(rt::CallbackParker::new(move || post.send(()).unwrap())).build();
+
+	let (tx, rx) = futures::channel::oneshot::channel();
+	let task = rt.spawn(rx);
+
+	// a host loop, running the runtime whenever a wake is posted
+	std::thread::spawn(move || tx.send(7).unwrap());
+	while !task.is_finished() {
+		posted.recv().unwrap();
+		rt.run_ready();
+	}
+
+	assert_eq!(rt.block_on(task), Some(Ok(7)));
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn core_pinning() {
+	assert_eq!(rt::pin_current_thread(usize::MAX).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+
+	// pinned on a fresh thread, so the test harness' thread keeps its affinity
+	let allowed = std::thread::spawn(|| {
+		let rt = rt::Builder::new().pin_to_core(0).build();
+		assert_eq!(rt.block_on(async { 1 }), 1);
+
+		let status = std::fs::read_to_string("/proc/thread-self/status").unwrap();
+		status.lines().find_map(|l| l.strip_prefix("Cpus_allowed_list:")).unwrap().trim().to_string()
+	});
+
+	assert_eq!(allowed.join().unwrap(), "0");
+}
+
+#[test]
+fn shutdown_signal() {
+	use futures::FutureExt;
+
+	let rt = rt::Runtime::new();
+	let (tx, rx) = futures::channel::mpsc::unbounded::<u32>();
+
+	// a worker draining its queue until told to stop
+	let signal = rt.shutdown_signal();
+	let worker = rt.spawn(async move {
+		use futures::StreamExt;
+
+		let (mut rx, mut signal, mut seen) = (rx, signal.fuse(), Vec::new());
+		loop {
+			futures::select! {
+				job = rx.next() => seen.extend(job),
+				_ = signal => return seen,
+			}
+		}
+	});
+
+	let waiters = (0..3).map(|_| rt.spawn(rt.shutdown_signal())).collect::<Vec<_>>();
+	tx.unbounded_send(1).unwrap();
+	rt.run_ready();
+
+	assert!(!rt.is_shutting_down() && !worker.is_finished());
+
+	// through a handle, like a signal handler would
+	assert!(rt.handle().initiate_shutdown());
+	assert!(!rt.initiate_shutdown());
+
+	assert_eq!(rt.block_on(worker), Some(vec![1]));
+	for waiter in waiters {
+		assert_eq!(rt.block_on(waiter), Some(()));
+	}
+
+	assert!(rt.is_shutting_down() && rt.shutdown_signal().now_or_never().is_some());
+}
+
+#[test]
+fn shutdown_cleanups() {
+	use std::{cell::RefCell, rc::Rc, time::Duration};
+
+	let flushed = Rc::new(RefCell::new(Vec::new()));
+	let rt = rt::Runtime::new();
+
+	let log = flushed.clone();
+	rt.on_shutdown(async move { log.borrow_mut().push("writer") });
+
+	// a task draining on the signal, registering its own cleanup
+	let signal = rt.shutdown_signal();
+	let log = flushed.clone();
+	rt.spawn(async move {
+		signal.await;
+		rt::Runtime::with_current(|rt| rt.on_shutdown(async move { log.borrow_mut().push("late") }));
+	});
+
+	assert!(rt.shutdown(Duration::from_secs(1)));
+	assert_eq!(*flushed.borrow(), ["writer"]);
+
+	// cleanups still pending when the timeout passes are aborted
+	rt.on_shutdown(std::future::pending());
+	assert!(!rt.shutdown(Duration::from_millis(10)));
+	assert_eq!(*flushed.borrow(), ["writer", "late"]);
+
+	// dropping the runtime runs what's left
+	let log = flushed.clone();
+	rt.on_shutdown(async move { log.borrow_mut().push("drop") });
+	drop(rt);
+	assert_eq!(*flushed.borrow(), ["writer", "late", "drop"]);
+}
+
+#[test]
+fn full_runtime_shutdown() {
+	use std::{cell::Cell, rc::Rc};
+
+	let rt = rt::Builder::new().max_tasks(1).build();
+	let flushed = Rc::new(Cell::new(0));
+
+	let _busy = rt.spawn(std::future::pending::<()>());
+	assert!(rt.try_spawn(async {}).is_err());
+
+	// cleanups run past the task limit, on shutdown and on drop
+	let log = flushed.clone();
+	rt.on_shutdown(async move { log.set(log.get() + 1) });
+	assert!(rt.shutdown(std::time::Duration::from_secs(1)));
+
+	let log = flushed.clone();
+	rt.on_shutdown(async move { log.set(log.get() + 1) });
+	drop(rt);
+
+	assert_eq!(flushed.get(), 2);
+}
+
+#[test]
+fn pending_wakes_on_drop() {
+	use std::{
+		cell::RefCell,
+		rc::Rc,
+		sync::{Arc, atomic},
+	};
+
+	// dropped along with the runtime's shared state
+	struct DropParker(std::thread::Thread, Arc<atomic::AtomicBool>);
+
+	impl rt::Parker for DropParker {
+		fn park(&self) {
+			std::thread::park()
+		}
+
+		fn unpark(&self) {
+			self.0.unpark()
+		}
+	}
+
+	impl Drop for DropParker {
+		fn drop(&mut self) {
+			self.1.store(true, atomic::Ordering::Relaxed)
+		}
+	}
+
+	let wakers = Rc::new(RefCell::new(Vec::new()));
+
+	for late in [false, true] {
+		let dropped = Arc::new(atomic::AtomicBool::new(false));
+		let rt = rt::Builder::new().parker(DropParker(std::thread::current(), dropped.clone())).build();
+
+		let slot = wakers.clone();
+		rt.spawn(std::future::poll_fn(move |cx| {
+			slot.borrow_mut().push(cx.waker().clone());
+			std::task::Poll::<()>::Pending
+		}));
+
+		rt.block_on(async {});
+		let waker = wakers.borrow_mut().pop().unwrap();
+
+		// woken while the runtime is still alive, or only once it's gone
+		if !late {
+			waker.wake_by_ref();
+		}
+
+		drop(rt);
+		waker.wake();
+		assert!(dropped.load(atomic::Ordering::Relaxed));
+	}
+}
+
+#[test]
+fn task_groups() {
+	let rt = rt::Runtime::new();
+
+	let connection = [rt.spawn_in_group("connection-42", std::future::pending::<()>()), rt.spawn_in_group("connection-42", std::future::pending::<()>())];
+	let other = rt.spawn_in_group("connection-7", async { 7 });
+	let ungrouped = rt.spawn(std::future::pending::<()>());
+
+	assert_eq!(rt.cancel_group("connection-42"), 2);
+	assert_eq!(rt.cancel_group("connection-0"), 0);
+
+	for monitor in connection {
+		assert_eq!(rt.block_on(monitor), None);
+	}
+
+	assert_eq!(rt.block_on(other), Some(7));
+	assert!(!ungrouped.is_finished());
+}
+
+#[test]
+fn group_spawn_hooks() {
+	use futures::StreamExt;
+
+	// tasks joining a quarantined group are cancelled as soon as they spawn
+	let rt = rt::Builder::new()
+		.on_spawn(|_, _| {
+			rt::Runtime::with_current(|rt| rt.cancel_group("quarantined"));
+		})
+		.build();
+	let mut events = rt.events();
+
+	let quarantined = rt.block_on(async { rt::Runtime::with_current(|rt| rt.spawn_in_group("quarantined", std::future::pending::<()>())) }).unwrap();
+	let id = quarantined.id();
+	assert_eq!(rt.block_on(quarantined), None);
+
+	let mut seen = Vec::new();
+	while let Some(Some(event)) = futures::FutureExt::now_or_never(events.next()) {
+		seen.push(event);
+	}
+
+	assert!(seen.contains(&rt::Event::TaskSpawned { id, name: None, group: Some("quarantined".into()) }));
+}
+
+#[test]
+fn weighted_groups() {
+	use std::{cell::RefCell, rc::Rc};
+
+	let rt = rt::Builder::new().group_weight("simulation", 4).group_weight("io", 1).build();
+	let order = Rc::new(RefCell::new(Vec::new()));
+
+	// the io tasks are woken last, but still get their share
+	for group in ["simulation"; 8].into_iter().chain(["io"; 2]) {
+		let order = order.clone();
+		rt.spawn_in_group(
+			group,
+			std::future::poll_fn(move |_| {
+				order.borrow_mut().push(group);
+				std::task::Poll::<()>::Pending
+			}),
+		);
+	}
+
+	rt.run_ready();
+	let order = order.take();
+
+	for polls in order.chunks(5) {
+		assert_eq!(polls.iter().filter(|g| **g == "io").count(), 1);
+	}
+}
+
+#[test]
+fn heartbeats() {
+	let rt = rt::Runtime::new();
+	let (stuck_tx, stuck_rx) = futures::channel::oneshot::channel::<()>();
+
+	let healthy = rt.spawn(async {
+		tasks::heartbeat(std::time::Duration::from_secs(60)).await;
+	});
+
+	// beats once, then waits on something that never happens
+	let stuck = rt.spawn(async {
+		tasks::heartbeat(std::time::Duration::from_millis(1)).await;
+		let _ = stuck_rx.await;
+	});
+
+	rt.block_on(healthy);
+	std::thread::sleep(std::time::Duration::from_millis(5));
+
+	assert_eq!(rt.metrics().missed_heartbeats, 1);
+	let dump = rt.task_dump();
+	assert_eq!(dump.len(), 1);
+	assert_eq!(dump[0].id, stuck.id());
+	assert!(dump[0].overdue.is_some() && dump[0].to_string().contains("heartbeat overdue"));
+
+	drop(stuck_tx);
+	rt.block_on(stuck);
+	assert_eq!(rt.metrics().missed_heartbeats, 0);
+}
+
+#[test]
+fn fifo_order() {
+	use std::{cell::RefCell, rc::Rc};
+
+	let rt = rt::Builder::new().fifo(true).build();
+	let order = Rc::new(RefCell::new(Vec::new()));
+
+	let ids = (0..3)
+		.map(|i| {
+			let order = order.clone();
+			rt.spawn(std::future::poll_fn(move |_| {
+				order.borrow_mut().push(i);
+				std::task::Poll::<()>::Pending
+			}))
+			.id()
+		})
+		.collect::<Vec<_>>();
+
+	// first polls follow spawn order
+	rt.run_ready();
+	assert_eq!(order.take(), [0, 1, 2]);
+
+	// later polls follow wake order, repeated wakes are collapsed
+	for i in [2, 0, 2, 1, 0] {
+		rt.notifier(ids[i]).notify();
+	}
+
+	rt.run_ready();
+	assert_eq!(order.take(), [2, 0, 1]);
+}
+
+#[test]
+fn shuffled_wakes() {
+	use std::{cell::RefCell, rc::Rc};
+
+	let run = |builder: rt::Builder| {
+		let rt = builder.build();
+		let log = Rc::new(RefCell::new(Vec::new()));
+
+		let tasks = (0..16).map(|i| {
+			let log = log.clone();
+
+			rt.spawn(async move {
+				// polled again once the first batch is drained
+				let mut yielded = false;
+				std::future::poll_fn(|cx| match std::mem::replace(&mut yielded, true) {
+					true => std::task::Poll::Ready(()),
+					false => {
+						cx.waker().wake_by_ref();
+						std::task::Poll::Pending
+					}
+				})
+				.await;
+
+				log.borrow_mut().push(i);
+			})
+		});
+
+		let tasks = tasks.collect::<Vec<_>>();
+		rt.block_on(futures::future::join_all(tasks));
+		log.take()
+	};
+
+	let ordered = run(rt::Builder::new());
+	assert_eq!(ordered, (0..16).collect::<Vec<_>>());
+
+	// the same seed reproduces the same order
+	let shuffled = run(rt::Builder::new().shuffle(7));
+	assert_ne!(shuffled, ordered);
+	assert_eq!(shuffled, run(rt::Builder::new().shuffle(7)));
+	assert_ne!(shuffled, run(rt::Builder::new().shuffle(8)));
+	assert_eq!(run(rt::Builder::new().shuffle(7).fifo(true)), ordered);
+}
+
+#[test]
+fn wrapped_wakers() {
+	use std::{
+		cell::RefCell,
+		collections::BTreeMap,
+		rc::Rc,
+		sync::{Arc, atomic},
+	};
+
+	struct Counted(std::task::Waker, Arc<atomic::AtomicUsize>);
+
+	impl std::task::Wake for Counted {
+		fn wake(self: Arc<Self>) {
+			self.wake_by_ref()
+		}
+
+		fn wake_by_ref(self: &Arc<Self>) {
+			self.1.fetch_add(1, atomic::Ordering::Relaxed);
+			self.0.wake_by_ref()
+		}
+	}
+
+	let counters = Rc::new(RefCell::new(BTreeMap::new()));
+	let rt = rt::Builder::new()
+		.wrap_wakers({
+			let counters = counters.clone();
+			move |id, waker| {
+				let counter = Arc::new(atomic::AtomicUsize::new(0));
+				counters.borrow_mut().insert(id, counter.clone());
+				std::task::Waker::from(Arc::new(Counted(waker, counter)))
+			}
+		})
+		.build();
+
+	let (tx, rx) = futures::channel::mpsc::unbounded::<u8>();
+	let receiver = rt.spawn(async move { futures::StreamExt::collect::<Vec<_>>(rx).await });
+	let id = receiver.id();
+
+	rt.block_on(async {});
+	for i in 0..3 {
+		tx.unbounded_send(i).unwrap();
+		rt.block_on(async {});
+	}
+
+	drop(tx);
+	assert_eq!(rt.block_on(receiver), Some(vec![0, 1, 2]));
+
+	// every wake went through the decorated waker, each task was decorated once
+	assert_eq!(counters.borrow()[&id].load(atomic::Ordering::Relaxed), 4);
+	assert_eq!(counters.borrow().len(), 6);
+}
+
+#[test]
+fn batched_wakes() {
+	use std::{cell::RefCell, rc::Rc};
+
+	let polled_in = |rt: rt::Runtime| {
+		let order = Rc::new(RefCell::new(Vec::new()));
+
+		let ids = (0..3)
+			.map(|i| {
+				let order = order.clone();
+				rt.spawn(std::future::poll_fn(move |_| {
+					order.borrow_mut().push(i);
+					std::task::Poll::<()>::Pending
+				}))
+				.id()
+			})
+			.collect::<Vec<_>>();
+
+		rt.run_ready();
+		order.take();
+
+		for i in [2, 0, 2, 1, 0] {
+			rt.notifier(ids[i]).notify();
+		}
+
+		rt.run_ready();
+		order.take()
+	};
+
+	// deep enough queues are polled in id order, each task once
+	assert_eq!(polled_in(rt::Builder::new().batch_threshold(4).build()), [0, 1, 2]);
+	assert_eq!(polled_in(rt::Builder::new().batch_threshold(4).fifo(true).build()), [2, 0, 1]);
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn runtime_snapshot() {
+	let rt = rt::Runtime::new();
+
+	let sleeper = rt.spawn(sleep(time::Duration::from_millis(100)));
+	let _pending = rt.spawn(std::future::pending::<()>());
+
+	let snapshot = rt.snapshot();
+	assert_eq!((snapshot.tasks, snapshot.scheduled, snapshot.next_timer), (2, 2, None));
+
+	rt.run_ready();
+	let snapshot = rt.snapshot();
+	assert_eq!((snapshot.tasks, snapshot.scheduled), (2, 0));
+	assert!(snapshot.next_timer.is_some());
+
+	let report = rt.to_string();
+	assert!(report.starts_with("runtime up for ") && report.contains(": 2 tasks, 0 scheduled, next timer due in "), "{}", report);
+	assert!(format!("{:?}", rt).starts_with("Runtime { tasks: 2, scheduled: 0, next_timer: Some("));
+
+	rt.block_on(sleeper);
+}
+
+#[test]
+fn coalesced_wakes() {
+	let rt = rt::Runtime::new();
+	let polls = std::rc::Rc::new(core::cell::Cell::new(0));
+
+	let counter = polls.clone();
+	let task = rt.spawn(core::future::poll_fn(move |cx| {
+		counter.set(counter.get() + 1);
+
+		match counter.get() {
+			1 => {
+				// wakes queued before the task is polled again collapse into one
+				let wakers = (0..64).map(|_| cx.waker().clone()).collect::<Vec<_>>();
+				wakers.iter().for_each(|w| w.wake_by_ref());
+				wakers.into_iter().for_each(|w| w.wake());
+				core::task::Poll::Pending
+			}
+			_ => core::task::Poll::Ready(()),
+		}
+	}));
+
+	rt.block_on(task);
+	assert_eq!(polls.get(), 2);
+}
+
+#[test]
+fn task_listing() {
+	let rt = rt::Runtime::new();
+	assert!(rt.is_empty() && rt.is_idle());
+
+	let named = rt.spawn_named("listener", std::future::pending::<()>());
+	let unnamed = rt.spawn(std::future::pending::<()>());
+
+	assert_eq!(rt.len(), 2);
+	assert_eq!(rt.tasks().collect::<Vec<_>>(), vec![(named.id(), Some("listener".to_string())), (unnamed.id(), None)]);
+
+	// freshly spawned tasks are waiting for their first poll
+	assert!(!rt.is_idle());
+	rt.run_ready();
+	assert!(rt.is_idle());
+
+	rt.notifier(unnamed.id()).notify();
+	assert!(!rt.is_idle());
+
+	rt.abort(named.id());
+	rt.run_ready();
+	assert_eq!(rt.tasks().map(|(id, _)| id).collect::<Vec<_>>(), vec![unnamed.id()]);
+}
+
+#[test]
+fn task_dump() {
+	let rt = rt::Runtime::new();
+	let idle = rt.spawn_named("listener", futures::future::pending::<()>());
+	rt.block_on(async {});
+
+	let dump = rt.task_dump();
+	assert_eq!(dump.len(), 1);
+	assert_eq!((dump[0].id, dump[0].state, dump[0].wakes, dump[0].stats.polls), (idle.id(), tasks::TaskState::Idle, 1, 1));
+	assert!(dump[0].to_string().starts_with(&format!("task {} \"listener\": Idle for ", idle.id())));
+
+	rt.notifier(idle.id()).notify();
+	assert_eq!(rt.task_dump()[0].state, tasks::TaskState::Scheduled);
+}
+
+#[test]
+fn await_chains() {
+	let rt = rt::Runtime::new();
+	let done = std::rc::Rc::new(std::cell::Cell::new(false));
+	let flag = done.clone();
+	let leaf = rt.spawn(std::future::poll_fn(move |_| match flag.get() {
+		true => std::task::Poll::Ready(()),
+		false => std::task::Poll::Pending,
+	}));
+	let leaf_id = leaf.id();
+
+	let waiter = rt.spawn(async move {
+		leaf.await;
+		futures::future::pending::<()>().await
+	});
+	rt.block_on(async {});
+
+	assert_eq!(rt.await_chain(waiter.id()), vec![tasks::Awaiting::Task(leaf_id)]);
+	assert!(rt.task_dump().iter().any(|t| t.id == waiter.id() && t.to_string().ends_with(&format!(", awaiting task {}", leaf_id))));
+
+	// edges are dropped once the task stops waiting
+	done.set(true);
+	rt.notifier(leaf_id).notify();
+	rt.block_on(async {});
+
+	// the waiter is woken by the leaf completing, and polled on the next pass
+	rt.block_on(async {});
+	assert!(rt.await_chain(waiter.id()).is_empty());
+
+	#[cfg(feature = "timers")]
+	{
+		let sleeper = rt.spawn(timers::sleep(time::Duration::from_millis(100)));
+		let sleeper_id = sleeper.id();
+		let top = rt.spawn(sleeper);
+		rt.block_on(async {});
+
+		assert!(matches!(rt.await_chain(top.id())[..], [tasks::Awaiting::Task(id), tasks::Awaiting::Timer(_)] if id == sleeper_id));
+	}
+}
+
+#[test]
+#[cfg(feature = "prometheus")]
+fn prometheus_export() {
+	let rt = rt::Runtime::new();
+	let _pending = rt.spawn(futures::future::pending::<()>());
+	rt.block_on(async {});
+
+	let mut text = String::new();
+	rt.metrics().write_prometheus(&mut text, "game").unwrap();
+
+	assert!(text.contains("# TYPE game_tasks gauge\ngame_tasks 1\n"));
+	assert!(text.contains("game_polls_total 2\n"));
+	assert!(text.contains("game_wake_latency_seconds_bucket{le=\"+Inf\"} 2\n"));
+	assert!(text.ends_with("game_wake_latency_seconds_count 2\n"));
+}
+
+#[test]
+fn histogram_bounds() {
+	use std::time::Duration;
+
+	let mut histogram = crate::metrics::Histogram::default();
+
+	// bounds are inclusive, values between powers of two round up
+	for micros in [0, 1, 2, 3, 4] {
+		histogram.record(Duration::from_micros(micros));
+	}
+	histogram.record(Duration::from_nanos(1500));
+
+	let buckets = histogram.buckets().take(4).collect::<Vec<_>>();
+	assert_eq!(buckets, [(Duration::from_micros(1), 2), (Duration::from_micros(2), 2), (Duration::from_micros(4), 2), (Duration::from_micros(8), 0)]);
+}
+
+#[test]
+fn block_on_pinned() {
+	let rt = rt::Runtime::new();
+	let words = String::from("pinned in place");
+
+	// borrows from the stack, which `block_on` wouldn't allow
+	let monitor = rt.spawn(async { 2 });
+	let fut = std::pin::pin!(async { words.split(' ').count() * monitor.await.unwrap() });
+
+	assert_eq!(rt.block_on_pinned(fut), 6);
+}
+
+#[test]
+fn task_ids() {
+	let rt = rt::Runtime::new();
+	let (tx, rx) = futures::channel::oneshot::channel::<()>();
+
+	let first = rt.spawn(async { 1 });
+	let second = rt.spawn(async move { rx.await.map(|_| 2).unwrap_or(0) });
+
+	assert_eq!(rt.block_on(first), Some(1));
+	assert!(!second.is_finished());
+
+	// ids of completed tasks are never reused
+	let third = rt.spawn(async { 3 });
+	assert!(third.id() != second.id());
+
+	tx.send(()).unwrap();
+	assert_eq!(rt.block_on(futures::future::join(second, third)), (Some(2), Some(3)));
+}
+
+#[test]
+fn task_abort_on_drop() {
+	use std::{cell::Cell, rc::Rc};
+
+	// flags when the task's future is dropped
+	struct Guard(Rc<Cell<bool>>);
+
+	impl Drop for Guard {
+		fn drop(&mut self) {
+			self.0.set(true);
+		}
+	}
+
+	let rt = rt::Runtime::new();
+	let (dropped, detached) = (Rc::new(Cell::new(false)), Rc::new(Cell::new(false)));
+
+	let guard = Guard(dropped.clone());
+	let aborted = rt.spawn(async move {
+		let _guard = guard;
+		futures::future::pending::<()>().await
+	});
+
+	// dropping a plain monitor leaves its task running to completion
+	let flag = detached.clone();
+	drop(rt.spawn(async move { flag.set(true) }));
+
+	drop(aborted.abort_on_drop());
+	rt.block_on(async {});
+
+	assert!(dropped.get());
+	assert!(detached.get());
+}
+
+#[test]
+#[cfg(not(target_os = "wasi"))]
+fn task_join_blocking() {
+	let rt = rt::Runtime::new();
+	let (tx, rx) = futures::channel::oneshot::channel::<usize>();
+
+	let monitor = rt.spawn(async move { rx.await.unwrap() * 2 });
+	let joiner = std::thread::spawn(move || monitor.join_blocking());
+
+	tx.send(21).unwrap();
+	rt.block_on(async {});
+
+	assert_eq!(joiner.join().unwrap(), Some(42));
+}
+
+#[test]
+fn task_monitor_fused() {
+	use futures::FutureExt;
+
+	let rt = rt::Runtime::new();
+	let (tx, rx) = futures::channel::oneshot::channel::<()>();
+
+	let mut monitor = rt.spawn(async move { rx.await.map(|_| 7).unwrap_or(0) });
+
+	// polling with a throwaway waker first mustn't lose the real one
+	assert!((&mut monitor).now_or_never().is_none());
+
+	let result = rt.block_on(async move {
+		let mut stalled = futures::future::pending::<()>().fuse();
+		tx.send(()).unwrap();
+
+		let result = futures::select! {
+			result = monitor => result,
+			_ = stalled => unreachable!(),
+		};
+
+		(result, futures::future::FusedFuture::is_terminated(&monitor))
+	});
+
+	assert_eq!(result, (Some(7), true));
+}
+
+#[test]
+#[cfg(not(target_os = "wasi"))]
+fn named_handles() {
+	let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+	let (stop_tx, stop_rx) = futures::channel::oneshot::channel::<()>();
+
+	// a subsystem running its own runtime on a dedicated thread
+	let subsystem = std::thread::spawn(move || {
+		let rt = rt::Runtime::new();
+		rt.register("named_handles");
+		ready_tx.send(()).unwrap();
+
+		rt.block_on(stop_rx).unwrap();
+	});
+
+	ready_rx.recv().unwrap();
+	let handle = rt::Handle::named("named_handles").unwrap();
+	let owner = std::thread::current().id();
+
+	let monitor = handle.spawn(async move { std::thread::current().id() != owner });
+	assert_eq!(monitor.join_blocking(), Some(true));
+
+	stop_tx.send(()).unwrap();
+	subsystem.join().unwrap();
+	assert!(rt::Handle::named("named_handles").is_none());
+}
+
+#[test]
+fn closed_handles() {
+	let rt = rt::Runtime::new();
+	let handle = rt.handle();
+	assert!(handle.is_alive());
+
+	// never inserted, the runtime is dropped before polling again
+	let pending = handle.spawn(async { 1 });
+	drop(rt);
+
+	assert!(!handle.is_alive());
+	assert_eq!(pending.join_blocking(), None);
+	assert!(matches!(handle.try_spawn(async {}), Err(rt::RuntimeError::Closed)));
+}
+
+#[test]
+#[cfg(not(target_os = "wasi"))]
+fn external_notifier() {
+	use std::sync::{Arc, atomic};
+
+	let rt = rt::Runtime::new();
+	let filled = Arc::new(atomic::AtomicBool::new(false));
+
+	// waits on a flag set by e.g. an audio callback, without registering a waker anywhere
+	let flag = filled.clone();
+	let monitor = rt.spawn(std::future::poll_fn(move |_| match flag.load(atomic::Ordering::Acquire) {
+		true => std::task::Poll::Ready("filled"),
+		false => std::task::Poll::Pending,
+	}));
+
+	let notifier = rt.notifier(monitor.id());
+	assert_eq!(notifier.task(), monitor.id());
+
+	std::thread::spawn(move || {
+		std::thread::sleep(std::time::Duration::from_millis(10));
+		filled.store(true, atomic::Ordering::Release);
+		notifier.notify();
+	});
+
+	assert_eq!(rt.block_on(monitor), Some("filled"));
+}
+
+#[test]
+#[cfg(not(target_os = "wasi"))]
+fn idle_strategy() {
+	use std::{
+		cell::Cell,
+		rc::Rc,
+		sync::{Arc, atomic},
+	};
+
+	// parks the thread, counting how often
+	struct CountingParker(std::thread::Thread, Arc<atomic::AtomicUsize>);
+
+	impl rt::Parker for CountingParker {
+		fn park(&self) {
+			self.1.fetch_add(1, atomic::Ordering::Relaxed);
+			std::thread::park()
+		}
+
+		fn unpark(&self) {
+			self.0.unpark()
+		}
+	}
+
+	let parks = Arc::new(atomic::AtomicUsize::new(0));
+	let idles = Rc::new(Cell::new(0));
+	let counter = idles.clone();
+
+	// spins until the wake arrives, never parking
+	let rt = rt::Builder::new().parker(CountingParker(std::thread::current(), parks.clone())).idle_spins(u32::MAX).on_idle(move || counter.set(counter.get() + 1)).build();
+
+	let (tx, rx) = futures::channel::oneshot::channel();
+	std::thread::spawn(move || {
+		std::thread::sleep(std::time::Duration::from_millis(10));
+		tx.send(7).unwrap();
+	});
+
+	assert_eq!(rt.block_on(rx), Ok(7));
+	assert_eq!(parks.load(atomic::Ordering::Relaxed), 0);
+	assert!(idles.get() >= 1);
+}
+
+#[test]
+#[cfg(not(target_os = "wasi"))]
+fn oneshot_threads() {
+	use crate::arena::Allocator;
+	use std::sync::{Arc, atomic};
+
+	// counts drops, so lost or doubly dropped messages show up
+	struct Counted(Arc<atomic::AtomicUsize>);
+
+	impl Drop for Counted {
+		fn drop(&mut self) {
+			self.0.fetch_add(1, atomic::Ordering::Relaxed);
+		}
+	}
+
+	let drops = Arc::new(atomic::AtomicUsize::new(0));
+
+	for i in 0..200 {
+		let (tx, rx) = oneshot::channel(Allocator::GLOBAL);
+		let message = Counted(drops.clone());
+		let sender = std::thread::spawn(move || tx.send(message).is_ok());
+
+		// race the receiver's drop against the send on every other round
+		let received = match i % 2 {
+			0 => rx.recv_blocking().is_ok(),
+			_ => {
+				drop(rx);
+				false
+			}
+		};
+
+		let sent = sender.join().unwrap();
+		assert!(sent || !received);
+	}
+
+	assert_eq!(drops.load(atomic::Ordering::Relaxed), 200);
+
+	// a sender dropped from another thread disconnects a blocked receiver
+	let (tx, rx) = oneshot::channel::<()>(Allocator::GLOBAL);
+	std::thread::spawn(move || drop(tx));
+	assert_eq!(rx.recv_blocking(), Err(oneshot::TryRecvError::Disconnected));
+}
+
+/// Models of the oneshot channel's state machine, exploring every interleaving of its halves.
+///
+/// Run with `RUSTFLAGS="--cfg loom" cargo test --release --lib loom_oneshot`, other tests don't run under loom
+#[cfg(loom)]
+mod loom_oneshot {
+	use crate::{
+		arena::Allocator,
+		oneshot::{self, TryRecvError},
+	};
+	use loom::{sync::Arc, thread};
+
+	// messages are loom `Arc`s, so loom reports any that leak. Senders unparking a receiver aren't joined, loom can't model an unpark reaching a thread blocked in `join`
+	#[test]
+	fn send_drop_receiver() {
+		loom::model(|| {
+			let (tx, rx) = oneshot::channel(Allocator::GLOBAL);
+			let message = Arc::new(());
+
+			let sender = thread::spawn(move || tx.send(message));
+			drop(rx);
+
+			// either way, exactly one side ends up owning the message
+			let _ = sender.join().unwrap();
+		});
+	}
+
+	#[test]
+	fn send_try_recv() {
+		loom::model(|| {
+			let (tx, rx) = oneshot::channel(Allocator::GLOBAL);
+			let sender = thread::spawn(move || tx.send(Arc::new(7)).is_ok());
+
+			let early = rx.try_recv();
+			assert!(sender.join().unwrap());
+
+			match early {
+				Ok(message) => assert_eq!(*message, 7),
+				Err(e) => {
+					assert_eq!(e, TryRecvError::Empty);
+					assert_eq!(*rx.try_recv().unwrap(), 7);
+				}
+			}
+
+			assert!(matches!(rx.try_recv(), Err(TryRecvError::Disconnected)));
+		});
+	}
+
+	#[test]
+	fn drop_sender_try_recv() {
+		loom::model(|| {
+			let (tx, rx) = oneshot::channel::<Arc<()>>(Allocator::GLOBAL);
+			let sender = thread::spawn(move || drop(tx));
+
+			assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty | TryRecvError::Disconnected)));
+			sender.join().unwrap();
+
+			assert!(rx.is_closed());
+			assert!(matches!(rx.try_recv(), Err(TryRecvError::Disconnected)));
+		});
+	}
+
+	#[test]
+	fn send_recv_blocking() {
+		loom::model(|| {
+			let (tx, rx) = oneshot::channel(Allocator::GLOBAL);
+			thread::spawn(move || tx.send(Arc::new(7)).unwrap());
+
+			// a send racing the receiver going to sleep is never lost
+			assert_eq!(*rx.recv_blocking().unwrap(), 7);
+		});
+	}
+
+	#[test]
+	fn drop_sender_recv_blocking() {
+		loom::model(|| {
+			let (tx, rx) = oneshot::channel::<Arc<()>>(Allocator::GLOBAL);
+			thread::spawn(move || drop(tx));
+
+			assert!(matches!(rx.recv_blocking(), Err(TryRecvError::Disconnected)));
+		});
+	}
+}
+
+/// Models of the ready queue and the locks tasks share with their wakers, a runtime woken from another thread.
+///
+/// Run with `RUSTFLAGS="--cfg loom" cargo test --release --lib loom_runtime`. Runtimes are built without timers, which fire from a thread loom doesn't control
+#[cfg(loom)]
+mod loom_runtime {
+	use crate::{rt, tasks};
+	use loom::{
+		sync::{
+			Arc,
+			atomic::{AtomicBool, Ordering},
+		},
+		thread,
+	};
+	use std::task::{Poll, Waker};
+
+	/// A flag set by another thread, which wakes the task waiting on it
+	#[derive(Clone)]
+	struct Flag(Arc<(AtomicBool, tasks::Lock<Option<Waker>>)>);
+
+	impl Flag {
+		fn new() -> Self {
+			Flag(Arc::new((AtomicBool::new(false), tasks::Lock::new(None))))
+		}
+
+		fn set(&self) {
+			self.0.0.store(true, Ordering::Release);
+
+			if let Some(waker) = self.0.1.with(Option::take) {
+				waker.wake()
+			}
+		}
+
+		async fn wait(self) {
+			std::future::poll_fn(|cx| {
+				if self.0.0.load(Ordering::Acquire) {
+					return Poll::Ready(());
+				}
+
+				// checked again once the waker is in place, the flag may have been set in between
+				self.0.1.with(|slot| *slot = Some(cx.waker().clone()));
+
+				match self.0.0.load(Ordering::Acquire) {
+					true => Poll::Ready(()),
+					false => Poll::Pending,
+				}
+			})
+			.await
+		}
+	}
+
+	// a lost wake leaves the runtime parked forever, which loom reports as a deadlock
+	#[test]
+	fn remote_wake() {
+		loom::model(|| {
+			let rt = rt::Runtime::new();
+			let flag = Flag::new();
+
+			let waiting = rt.spawn(flag.clone().wait());
+			let setter = flag.clone();
+			thread::spawn(move || setter.set());
+
+			assert_eq!(rt.block_on(waiting), Some(()));
+		});
+	}
+
+	// wakers are loom `Arc`s, so loom reports any the ready queue leaks, whether woken before or after the runtime is dropped
+	#[test]
+	fn wake_during_drop() {
+		loom::model(|| {
+			let rt = rt::Runtime::new();
+			let flag = Flag::new();
+
+			rt.spawn(flag.clone().wait());
+			rt.block_on(async {});
+
+			let setter = flag.clone();
+			let woken = thread::spawn(move || setter.set());
+
+			drop(rt);
+			woken.join().unwrap();
+		});
+	}
+}
+
+#[test]
+fn select_monitors() {
+	let rt = rt::Runtime::new();
+	let (tx, rx) = futures::channel::oneshot::channel::<u32>();
+
+	let work = rt.spawn(async move { rx.await.unwrap() });
+	let stuck = rt.spawn(std::future::pending::<u32>());
+	let quick = rt.spawn(async { 7 });
+
+	let (result, index, rest) = rt.block_on(tasks::select_all([work, stuck, quick]));
+	assert_eq!((result, index, rest.len()), (Some(7), 2, 2));
+
+	// losers keep running until aborted
+	rest[1].abort();
+	tx.send(3).unwrap();
+
+	let (result, index, rest) = rt.block_on(tasks::select_all(rest));
+	assert_eq!((result, index), (Some(3), 0));
+	assert_eq!(rt.block_on(tasks::select_all(rest)).0, None);
+}
+
+#[test]
+fn chained_monitors() {
+	let rt = rt::Runtime::new();
+
+	let parsed = rt.spawn(async { "42" }).map(|s| s.parse::<u32>().unwrap());
+	assert_eq!(rt.block_on(parsed), Some(42));
+
+	// each stage runs as its own task
+	let doubled = rt.spawn(async { 21 }).and_then(&rt, |v| async move { v * 2 });
+	let printed = doubled.and_then(&rt, |v| async move { format!("{}!", v) });
+	assert_eq!(rt.block_on(printed), Some("42!".to_string()));
+
+	// a dropped stage drops the rest of the pipeline
+	let aborted = rt.spawn(std::future::pending::<u32>());
+	aborted.abort();
+
+	let follow_up = aborted.and_then(&rt, |v| async move { v + 1 });
+	assert_eq!(rt.block_on(follow_up), None);
+}
+
+#[test]
+fn join_set() {
+	let rt = rt::Runtime::new();
+	let mut set = tasks::JoinSet::new();
+
+	let (first_tx, first_rx) = futures::channel::oneshot::channel::<()>();
+	let (second_tx, second_rx) = futures::channel::oneshot::channel::<()>();
+	let (stuck_tx, stuck_rx) = futures::channel::oneshot::channel::<()>();
+
+	// each task releases the next one, completing in reverse order of spawning
+	set.spawn(&rt, async move {
+		first_rx.await.unwrap();
+		1
+	});
+
+	set.spawn(&rt, async move {
+		second_rx.await.unwrap();
+		first_tx.send(()).unwrap();
+		2
+	});
+
+	set.spawn(&rt, async move {
+		second_tx.send(()).unwrap();
+		3
+	});
+
+	set.spawn(&rt, async move { stuck_rx.await.map(|_| 4).unwrap_or(0) });
+
+	let (mut results, set) = rt.block_on(async move {
+		let mut results = Vec::new();
+
+		while set.len() > 1 {
+			results.push(set.join_next().await.unwrap().unwrap());
+		}
+
+		(results, set)
+	});
+
+	// tasks completing within the same poll may be joined in any order
+	assert_eq!(results[0], 3);
+	results.sort();
+	assert_eq!(results, [1, 2, 3]);
+
+	// dropping the set aborts the stuck task, dropping its receiver
+	drop(set);
+	rt.block_on(async {});
+	assert!(stuck_tx.is_canceled());
+}
+
+#[test]
+fn task_scope() {
+	let rt = rt::Runtime::new();
+
+	// children may borrow from outside the scope, and all of them complete before it does
+	let total = rt.block_on(async {
+		let counter = std::cell::Cell::new(0);
+
+		let res = tasks::scope(async |s: &tasks::Scope<'_, ()>| {
+			for i in 1..=3 {
+				let counter = &counter;
+
+				s.spawn(async move {
+					futures::future::ready(()).await;
+					counter.set(counter.get() + i);
+					Ok(())
+				});
+			}
+
+			Ok(())
+		})
+		.await;
+
+		res.map(|_| counter.get())
+	});
+
+	assert_eq!(total, Ok(6));
+
+	// the first error cancels every sibling
+	let (stuck_tx, stuck_rx) = futures::channel::oneshot::channel::<()>();
+
+	let res = rt.scope(async move |s| {
+		s.spawn(async move { stuck_rx.await.map_err(|_| "canceled") });
+		s.spawn(async { Err("failed") });
+		Ok(())
+	});
+
+	assert_eq!(res, Err("failed"));
+	assert!(stuck_tx.is_canceled());
+}
+
+#[test]
+fn coop_budget() {
+	use std::{cell::Cell, rc::Rc};
+
+	let rt = rt::Runtime::new();
+	let (done, polls) = (Rc::new(Cell::new(false)), Rc::new(Cell::new(0)));
+
+	// never awaits anything that's pending, it only yields once its budget runs out
+	let (flag, count) = (done.clone(), polls.clone());
+	let busy = rt.spawn(async move {
+		while !flag.get() {
+			coop::consume_budget().await;
+			count.set(count.get() + 1);
+		}
+	});
+
+	let flag = done.clone();
+	let other = rt.spawn(async move { flag.set(true) });
+
+	rt.block_on(futures::future::join(busy, other));
+	assert!(polls.get() > 0);
+}
+
+#[test]
+fn task_progress() {
+	let rt = rt::Runtime::new();
+	let (tx, rx) = futures::channel::oneshot::channel::<()>();
+
+	let monitor = rt.spawn_with_progress(|progress| async move {
+		progress.report(0.5);
+		progress.report_status("downloading");
+
+		rx.await.unwrap();
+		progress.report(1.0);
+		42
+	});
+
+	let result = rt.block_on(async move {
+		let seen = (monitor.progress(), monitor.status());
+		tx.send(()).unwrap();
+
+		(seen, monitor.await)
+	});
+
+	assert_eq!(result, ((Some(0.5), Some("downloading")), Some(42)));
+}
+
+#[test]
+fn custom_parker() {
+	use std::sync::{Arc, atomic};
+
+	// spins instead of parking, counting every wake-up
+	struct SpinParker(Arc<atomic::AtomicUsize>);
+
+	impl rt::Parker for SpinParker {
+		fn park(&self) {
+			std::hint::spin_loop()
+		}
+
+		fn unpark(&self) {
+			self.0.fetch_add(1, atomic::Ordering::Relaxed);
+		}
+	}
+
+	let unparks = Arc::new(atomic::AtomicUsize::new(0));
+	let rt = rt::Runtime::with_parker(SpinParker(unparks.clone()));
+
+	let monitor = rt.spawn(async { futures::future::ready(21).await * 2 });
+	assert_eq!(rt.block_on(monitor), Some(42));
+	assert!(unparks.load(atomic::Ordering::Relaxed) >= 1);
+
+	// a burst of wakes before the runtime polls only unparks the host once
+	unparks.store(0, atomic::Ordering::Relaxed);
+	let monitors = (0..100).map(|i| rt.spawn(async move { i })).collect::<Vec<_>>();
+	assert_eq!(unparks.load(atomic::Ordering::Relaxed), 1);
+
+	let sum = rt.block_on(async move { futures::future::join_all(monitors).await.into_iter().flatten().sum::<i32>() });
+	assert_eq!(sum, 4950);
+}
+
+#[test]
+#[cfg(panic = "unwind")]
+fn isolated_panics() {
+	let panicked = std::rc::Rc::new(core::cell::Cell::new(None));
+	let record = panicked.clone();
+
+	let rt = rt::Builder::new().panic_policy(rt::PanicPolicy::Isolate).on_panic(move |id, _| record.set(Some(id))).build();
+
+	let faulty = rt.spawn(async { panic!("task failed") });
+	let id = faulty.id();
+	let healthy = rt.spawn(async { 1 });
+
+	// only the faulty task is removed
+	assert_eq!(rt.block_on(async { (faulty.await, healthy.await) }), (None, Some(1)));
+	assert_eq!(panicked.get(), Some(id));
+
+	// the future given to `block_on` still reaches its caller
+	let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| rt.block_on(async { panic!("root failed") })));
+	assert!(res.is_err());
+	assert_eq!(rt.block_on(async { 2 }), 2);
+}
+
+#[test]
+#[cfg(panic = "unwind")]
+fn lifecycle_hooks() {
+	use std::{cell::RefCell, rc::Rc};
+
+	let events = Rc::new(RefCell::new(Vec::new()));
+	let record = |event: &'static str| {
+		let events = events.clone();
+		move |id: tasks::TaskId, name: Option<&str>| events.borrow_mut().push((event, id, name.map(String::from)))
+	};
+
+	let rt = rt::Builder::new().on_spawn(record("spawn")).on_complete(record("complete")).on_panic(record("panic")).build();
+
+	let worker = rt.spawn_named("worker", async { 1 });
+	let id = worker.id();
+	assert_eq!(rt.block_on(worker), Some(1));
+
+	let faulty = rt.spawn_named("faulty", async { panic!("task failed") }).id();
+	let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| rt.block_on(async {})));
+	assert!(res.is_err());
+
+	let events = events.borrow();
+	let named = |event, id, name: &str| (event, id, Some(name.to_string()));
+
+	assert!(events.contains(&named("spawn", id, "worker")));
+	assert!(events.contains(&named("complete", id, "worker")));
+	assert!(events.contains(&named("panic", faulty, "faulty")));
+	assert!(!events.contains(&named("complete", faulty, "faulty")));
+
+	// futures passed to `block_on` are unnamed tasks
+	assert!(events.iter().any(|(event, _, name)| *event == "spawn" && name.is_none()));
+}
+
+#[test]
+fn spawning_hooks() {
+	use std::{cell::Cell, rc::Rc};
+
+	let (spawned, restarts) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+	let (spawned_clone, restarts_clone) = (spawned.clone(), restarts.clone());
+
+	// workers spawn with a companion, and are restarted once when they complete
+	let rt = rt::Builder::new()
+		.on_spawn(move |_, name| {
+			spawned_clone.set(spawned_clone.get() + 1);
+
+			if name == Some("worker") {
+				rt::Runtime::with_current(|rt| rt.spawn_named("companion", async {}));
+			}
+		})
+		.on_complete(move |_, name| {
+			if name == Some("worker") && restarts_clone.get() == 0 {
+				restarts_clone.set(1);
+				rt::Runtime::with_current(|rt| rt.spawn_named("worker", async {}));
+			}
+		})
+		.build();
+
+	let worker = rt.spawn_named("worker", async {});
+	rt.block_on(worker);
+	rt.block_on(async {});
+
+	// companions are spawned from within the hook, so they don't reach it
+	assert_eq!(restarts.get(), 1);
+	assert_eq!(spawned.get(), 4);
+	assert!(rt.is_empty());
+}
+
+#[test]
+#[cfg(panic = "unwind")]
+fn try_block_on() {
+	let rt = rt::Runtime::new();
+
+	let faulty = rt.try_spawn(async { panic!("task failed") }).unwrap();
+	let id = faulty.id();
+
+	// the panic is returned instead of unwinding, and the runtime stays usable
+	assert_eq!(rt.try_block_on(async {}), Err(rt::RuntimeError::Panicked(id)));
+	assert_eq!(rt.try_block_on(faulty), Ok(None));
+	assert_eq!(rt.try_block_on(async { 42 }), Ok(42));
+}
+
+#[test]
+fn block_in_place() {
+	let rt = rt::Runtime::new();
+	let (tx, rx) = futures::channel::oneshot::channel::<usize>();
+
+	// sync code within a task, waiting on a sibling that hasn't been polled yet
+	let blocked = rt.spawn(async move { crate::block_in_place(rx).unwrap() * 2 });
+	let sender = rt.spawn(async move { tx.send(21).unwrap() });
+
+	assert_eq!(rt.block_on(futures::future::join(blocked, sender)), (Some(42), Some(())));
+
+	// outside of a runtime, the thread itself is blocked
+	assert_eq!(crate::block_in_place(async { 7 }), 7);
+}
+
+#[test]
+fn run_for_deadline() {
+	use std::time::{Duration, Instant};
+
+	let rt = rt::Runtime::new();
+	let (tx, rx) = futures::channel::oneshot::channel::<usize>();
+
+	// control returns once the deadline passes, even though the future is still pending
+	let started = Instant::now();
+	let monitor = rt.block_on_timeout(async move { rx.await.unwrap() }, Duration::from_millis(20)).unwrap().unwrap_err();
+
+	assert!(started.elapsed() >= Duration::from_millis(20));
+	assert!(!monitor.is_finished());
+
+	rt.run_for(Duration::from_millis(10));
+	assert!(!monitor.is_finished());
+
+	// with nothing left to run, `run_for` returns right away
+	tx.send(5).unwrap();
+	assert_eq!(rt.block_on_timeout(monitor, Duration::from_secs(5)).unwrap().ok(), Some(Some(5)));
+
+	let started = Instant::now();
+	rt.run_for(Duration::from_secs(5));
+	assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+#[cfg(not(target_os = "wasi"))]
+fn stall_watchdog() {
+	use std::{sync::mpsc, time::Duration};
+
+	let (tx, rx) = mpsc::channel();
+	let rt = rt::Builder::new().on_stall(Duration::from_millis(20), move |stall| tx.send(stall).unwrap()).build();
+
+	let blocking = rt.spawn(async { std::thread::sleep(Duration::from_millis(200)) });
+	let id = blocking.id();
+	rt.block_on(blocking);
+
+	// reported once, while the task was still blocking
+	let stall = rx.try_recv().unwrap();
+	assert_eq!(stall.task, id);
+	assert!(stall.stuck_for >= Duration::from_millis(20) && stall.stuck_for < Duration::from_millis(200));
+	assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn barrier_phases() {
+	use std::{cell::RefCell, rc::Rc};
+
+	let rt = rt::Runtime::new();
+	let barrier = Rc::new(sync::Barrier::new(3));
+	let log = Rc::new(RefCell::new(Vec::new()));
+
+	let workers = (0..3).map(|_| {
+		let (barrier, log) = (barrier.clone(), log.clone());
+
+		rt.spawn(async move {
+			let mut leaders = 0;
+
+			for phase in 0..2 {
+				log.borrow_mut().push(phase);
+				leaders += barrier.wait().await.is_leader() as usize;
+			}
+
+			leaders
+		})
+	});
+
+	let workers = workers.collect::<Vec<_>>();
+	let leaders = rt.block_on(futures::future::join_all(workers));
+
+	// nobody starts a phase before everyone finished the previous one
+	assert_eq!(*log.borrow(), [0, 0, 0, 1, 1, 1]);
+	assert_eq!(leaders.into_iter().flatten().sum::<usize>(), 2);
+}
+
+#[test]
+fn once_cell_init() {
+	use std::{
+		cell::{Cell, RefCell},
+		rc::Rc,
+	};
+
+	let rt = rt::Runtime::new();
+	let cell = Rc::new(sync::OnceCell::new());
+	let inits = Rc::new(Cell::new(0));
+	let (tx, rx) = futures::channel::oneshot::channel::<()>();
+	let rx = Rc::new(RefCell::new(Some(rx)));
+
+	let getters = (0..3).map(|i| {
+		let (cell, inits, rx) = (cell.clone(), inits.clone(), rx.clone());
+
+		rt.spawn(async move {
+			*cell
+				.get_or_init(async || {
+					inits.set(inits.get() + 1);
+
+					// the first initializer is cancelled while waiting, the next one takes over
+					let rx = rx.borrow_mut().take();
+					match rx {
+						Some(rx) => rx.await.map(|_| 0).unwrap_or(-1),
+						None => i,
+					}
+				})
+				.await
+		})
+	});
+
+	let getters = getters.collect::<Vec<_>>();
+	rt.block_on(async {});
+	assert_eq!(inits.get(), 1);
+
+	getters[0].abort();
+	drop(tx);
+
+	let values = rt.block_on(futures::future::join_all(getters));
+	assert_eq!(values, [None, Some(1), Some(1)]);
+	assert_eq!(inits.get(), 2);
+	assert_eq!(cell.set(5), Err(5));
+}
+
+#[test]
+fn mutex_condvar() {
+	use std::{collections::VecDeque, rc::Rc};
+
+	let rt = rt::Runtime::new();
+	let state = Rc::new((sync::Mutex::new(VecDeque::new()), sync::Condvar::new()));
+
+	let consumers = (0..2).map(|_| {
+		let state = state.clone();
+
+		rt.spawn(async move {
+			let (jobs, ready) = &*state;
+			let mut taken = Vec::new();
+
+			loop {
+				let mut jobs = ready.wait_while(jobs.lock().await, |jobs| jobs.is_empty()).await;
+				match jobs.pop_front() {
+					Some(None) | None => return taken,
+					Some(Some(job)) => taken.push(job),
+				}
+			}
+		})
+	});
+
+	let consumers = consumers.collect::<Vec<_>>();
+	rt.block_on(async {});
+
+	// the lock is held across an await, waiters queue up behind it
+	let producer = rt.spawn({
+		let state = state.clone();
+
+		async move {
+			let (jobs, ready) = &*state;
+			let mut jobs = jobs.lock().await;
+
+			futures::future::ready(()).await;
+			jobs.extend([Some(1), Some(2), Some(3), None, None]);
+			ready.notify_all();
+		}
+	});
+
+	let mut taken = rt.block_on(futures::future::join_all(consumers)).into_iter().flatten().flatten().collect::<Vec<_>>();
+	taken.sort();
+
+	assert!(producer.is_finished());
+	assert_eq!(taken, [1, 2, 3]);
+	assert!(state.0.try_lock().is_some_and(|jobs| jobs.is_empty()));
+	assert!(!state.1.notify_one());
+}
+
+#[test]
+fn mpmc_channel() {
+	let rt = rt::Runtime::new();
+	let (tx, rx) = sync::mpmc::unbounded();
+
+	let workers = (0..3).map(|_| {
+		let rx = rx.clone();
+
+		rt.spawn(async move {
+			let mut jobs = Vec::new();
+
+			while let Some(job) = rx.recv().await {
+				jobs.push(job);
+
+				// hand the queue over to the other workers
+				let mut yielded = false;
+				std::future::poll_fn(|cx| match std::mem::replace(&mut yielded, true) {
+					true => std::task::Poll::Ready(()),
+					false => {
+						cx.waker().wake_by_ref();
+						std::task::Poll::Pending
+					}
+				})
+				.await;
+			}
+
+			jobs
+		})
+	});
+
+	let workers = workers.collect::<Vec<_>>();
+	rt.block_on(async {});
+	assert!(rx.try_recv().is_err_and(|e| e == sync::mpmc::TryRecvError::Empty));
+
+	(0..6).for_each(|job| tx.clone().try_send(job).unwrap());
+	drop(tx);
+
+	let jobs = rt.block_on(futures::future::join_all(workers)).into_iter().flatten().collect::<Vec<_>>();
+	assert!(jobs.iter().all(|jobs| jobs.len() == 2));
+
+	let mut all = jobs.concat();
+	all.sort();
+	assert_eq!(all, [0, 1, 2, 3, 4, 5]);
+	assert_eq!(rx.try_recv(), Err(sync::mpmc::TryRecvError::Disconnected));
+
+	let (tx, rx) = sync::mpmc::unbounded::<u8>();
+	drop(rx);
+	assert_eq!(rt.block_on(async move { tx.send(1).await }), Err(sync::mpmc::SendError(1)));
+}
+
+#[test]
+fn bounded_channel() {
+	use sync::mpmc::TrySendError;
+
+	let rt = rt::Runtime::new();
+	let (tx, rx) = sync::mpmc::bounded(2);
+
+	assert_eq!(tx.capacity(), Some(2));
+	assert!(tx.try_send(1).is_ok() && tx.try_send(2).is_ok());
+	assert_eq!(tx.try_send(3), Err(TrySendError::Full(3)));
+
+	// senders queue up behind the full channel, and go through in order as room is made
+	let producers = (3..6).map(|i| {
+		let tx = tx.clone();
+		rt.spawn(async move { tx.send(i).await })
+	});
+
+	let producers = producers.collect::<Vec<_>>();
+	rt.block_on(async {});
+	assert!(producers.iter().all(|producer| !producer.is_finished()));
+	assert_eq!(tx.len(), 2);
+
+	let receiver = rx.clone();
+	let received = rt.block_on(async move {
+		let mut received = Vec::new();
+		while received.len() < 5 {
+			received.push(receiver.recv().await.unwrap());
+		}
+		received
+	});
+
+	assert_eq!(received, [1, 2, 3, 4, 5]);
+	assert!(rt.block_on(futures::future::join_all(producers)).into_iter().all(|sent| sent == Some(Ok(()))));
+
+	// a cancelled sender passes its turn on
+	tx.try_send(6).unwrap();
+	tx.try_send(7).unwrap();
+	let (first, second) = (tx.clone(), tx.clone());
+	let cancelled = rt.spawn(async move { first.send(8).await });
+	let waiting = rt.spawn(async move { second.send(9).await });
+
+	rt.block_on(async {});
+	assert_eq!(rx.try_recv(), Ok(6));
+	cancelled.abort();
+
+	assert_eq!(rt.block_on(waiting), Some(Ok(())));
+	drop(rx);
+	assert_eq!(tx.try_send(10), Err(TrySendError::Closed(10)));
+}
+
+#[test]
+fn channel_streams() {
+	use futures::{StreamExt, stream::FusedStream};
+
+	let rt = rt::Runtime::new();
+	let (jobs, job_rx) = sync::mpmc::bounded(1);
+	let (events, event_rx) = sync::mpmc::unbounded();
+
+	let merged = rt.spawn(async move { futures::stream::select(job_rx, event_rx).collect::<Vec<_>>().await });
+	let producer = rt.spawn(async move {
+		for i in 0..3 {
+			jobs.send(i).await.unwrap();
+			events.try_send(i * 10).unwrap();
+		}
+	});
+
+	let mut merged = rt.block_on(merged).unwrap();
+	merged.sort();
+
+	assert!(producer.is_finished());
+	assert_eq!(merged, [0, 0, 1, 2, 10, 20]);
+
+	let (tx, mut rx) = sync::mpmc::unbounded();
+	tx.try_send(1).unwrap();
+	drop(tx);
+
+	assert!(!rx.is_terminated());
+	assert_eq!(rt.block_on(async move { (rx.next().await, rx.next().await, rx.is_terminated()) }), (Some(1), None, true));
+}
+
+#[test]
+fn task_limit() {
+	let rt = rt::Builder::new().max_tasks(2).build();
+	let (tx, rx) = futures::channel::oneshot::channel::<()>();
+
+	let waiting = rt.spawn(async move { rx.await.unwrap() });
+	let quick = rt.try_spawn(async {}).unwrap();
+	assert!(matches!(rt.try_spawn(async {}), Err(rt::RuntimeError::TaskLimitReached)));
+	assert!(matches!(rt.block_on_timeout(async {}, std::time::Duration::ZERO), Err(rt::RuntimeError::TaskLimitReached)));
+
+	#[cfg(feature = "timers")]
+	assert!(matches!(rt.spawn_periodic(std::time::Duration::from_millis(10), || async {}), Err(rt::RuntimeError::TaskLimitReached)));
+
+	// the slot freed by `quick` is taken by the root future, so the spawn waits on `waiting` to complete
+	let result = rt.block_on(async move {
+		tx.send(()).unwrap();
+		rt::spawn_when_available(async { 3 }).await.await
+	});
+
+	assert_eq!(result, Some(3));
+	assert!(waiting.is_finished() && quick.is_finished());
+}
+
+#[test]
+fn task_pool() {
+	use core::alloc::Layout;
+
+	let pool = tasks::Pool::new(1, arena::Allocator::GLOBAL);
+	let layout = Layout::new::<[u64; 16]>();
+	let (first, second) = (pool.alloc(layout), pool.alloc(layout));
+
+	// only one block is kept, the other goes back to the allocator
+	unsafe {
+		pool.free(layout, first);
+		pool.free(layout, second);
+	}
+
+	let reused = pool.alloc(layout);
+	assert_eq!(reused, first);
+	unsafe { pool.free(layout, reused) };
+
+	// small futures share slabs, handing out adjacent slots
+	let tiny = Layout::new::<[u64; 2]>();
+	let (a, b) = (pool.alloc(tiny), pool.alloc(tiny));
+	assert_eq!(unsafe { b.offset_from(a) }.unsigned_abs(), 64);
+
+	unsafe {
+		pool.free(tiny, a);
+		pool.free(tiny, b);
+	}
+
+	// tasks run the same with pooling disabled
+	let rt = rt::Builder::new().pool_capacity(0).build();
+	let monitor = rt.spawn(async { 1 });
+	assert_eq!(rt.block_on(monitor), Some(1));
+}
+
+#[test]
+fn custom_allocator() {
+	use std::{
+		alloc::{GlobalAlloc, Layout, System},
+		sync::atomic::{AtomicUsize, Ordering},
+	};
+
+	/// Counts live allocations, as an arena would track its budget
+	struct Counting(AtomicUsize);
+
+	unsafe impl GlobalAlloc for Counting {
+		unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+			self.0.fetch_add(1, Ordering::Relaxed);
+			unsafe { System.alloc(layout) }
+		}
+
+		unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+			self.0.fetch_sub(1, Ordering::Relaxed);
+			unsafe { System.dealloc(ptr, layout) }
+		}
+	}
+
+	static ARENA: Counting = Counting(AtomicUsize::new(0));
+
+	let rt = rt::Builder::new().allocator(&ARENA).build();
+	let buf = [1u8; 256];
+
+	// the runtime's shared state and task pool
+	assert_eq!(ARENA.0.load(Ordering::Relaxed), 2);
+
+	// each task's result channel, monitor waker and waker, plus a slab for small futures and a block of its own for the large one
+	let small = rt.spawn(async { 1 });
+	let large = rt.spawn(async move { buf.iter().map(|b| *b as usize).sum::<usize>() });
+	assert_eq!(ARENA.0.load(Ordering::Relaxed), 2 + 2 * 3 + 2);
+
+	assert_eq!(rt.block_on(small), Some(1));
+	assert_eq!(rt.block_on(large), Some(256));
+
+	drop(rt);
+	assert_eq!(ARENA.0.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn slow_poll_hook() {
+	use std::{cell::RefCell, rc::Rc, time::Duration};
+
+	let slow = Rc::new(RefCell::new(Vec::new()));
+	let log = slow.clone();
+
+	let rt = rt::Builder::new().on_slow_poll(Duration::from_millis(20), move |id, elapsed| log.borrow_mut().push((id, elapsed))).build();
+	let (tx, rx) = futures::channel::oneshot::channel::<()>();
+
+	// blocks the runtime thread during its first poll
+	let blocking = rt.spawn(async move {
+		std::thread::sleep(Duration::from_millis(40));
+		rx.await.unwrap()
+	});
+
+	let id = blocking.id();
+	rt.block_on(async {});
+
+	let stats = rt.poll_stats(id).unwrap();
+	assert_eq!(stats.polls, 1);
+	assert!(stats.max >= Duration::from_millis(40));
+
+	tx.send(()).unwrap();
+	rt.block_on(blocking);
+
+	let slow = slow.borrow();
+	assert_eq!(slow.len(), 1);
+	assert_eq!(slow[0].0, id);
+	assert!(rt.poll_stats(id).is_none());
+}
+
+#[test]
+fn reentrant_slow_poll_hook() {
+	use std::{cell::Cell, rc::Rc, task::Poll, time::Duration};
+
+	let calls = Rc::new(Cell::new(0));
+	let count = calls.clone();
+
+	// the hook drives the runtime, polling the second slow task while the first is still being reported
+	let rt = rt::Builder::new()
+		.on_slow_poll(Duration::from_millis(10), move |_, _| {
+			count.set(count.get() + 1);
+
+			let mut yielded = false;
+			crate::block_in_place(futures::future::poll_fn(|cx| {
+				if std::mem::replace(&mut yielded, true) {
+					Poll::Ready(())
+				} else {
+					cx.waker().wake_by_ref();
+					Poll::Pending
+				}
+			}));
+		})
+		.build();
+
+	let first = rt.spawn(async { std::thread::sleep(Duration::from_millis(20)) });
+	let second = rt.spawn(async { std::thread::sleep(Duration::from_millis(20)) });
+
+	assert_eq!(rt.block_on(futures::future::join(first, second)), (Some(()), Some(())));
+	assert_eq!(calls.get(), 1);
+
+	// restored once its call returns
+	rt.block_on(rt.spawn(async { std::thread::sleep(Duration::from_millis(20)) }));
+	assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn busy_wakes() {
+	use std::{cell::RefCell, rc::Rc};
+
+	let busy = Rc::new(RefCell::new(Vec::new()));
+	let log = busy.clone();
+
+	// the hook may inspect the runtime it runs in
+	let rt = rt::Builder::new()
+		.on_busy_wake(4, move |id, name| {
+			assert!(rt::Runtime::with_current(|rt| rt.len()).unwrap() > 0);
+			log.borrow_mut().push((id, name.map(str::to_string)));
+		})
+		.build();
+
+	// wakes itself in each of its first ten polls
+	let mut polls = 0;
+	let spinner = rt.spawn_named(
+		"spinner",
+		core::future::poll_fn(move |cx| {
+			polls += 1;
+			cx.waker().wake_by_ref();
+
+			match polls {
+				..10 => core::task::Poll::Pending,
+				_ => core::task::Poll::Ready(()),
+			}
+		}),
+	);
+
+	let (tx, rx) = futures::channel::oneshot::channel::<()>();
+	let waiting = rt.spawn(rx);
+
+	let id = spinner.id();
+	rt.run_ready();
+	assert_eq!(rt.poll_stats(waiting.id()).unwrap().self_wakes, 0);
+
+	rt.block_on(spinner);
+	tx.send(()).unwrap();
+	rt.block_on(waiting).unwrap().unwrap();
+
+	assert_eq!(*busy.borrow(), [(id, Some("spinner".to_string()))]);
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn sleep_tasks() {
+	let rt = rt::Runtime::new();
+
+	let sleep_5s = async {
+		println!("Sleeping for 5s");
+
+		for i in 0..5 {
+			println!("{}s left", 5 - i);
+			sleep(time::Duration::from_secs(1)).await;
+		}
+
+		println!("Done sleeping");
+	};
+
+	rt.block_on(sleep_5s);
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn priority_timers() {
+	use std::{cell::RefCell, rc::Rc};
+
+	let log = Rc::new(RefCell::new(Vec::new()));
+
+	for fifo in [false, true] {
+		let rt = rt::Builder::new().fifo(fifo).build();
+		log.borrow_mut().clear();
+
+		let (ordinary, urgent) = (sleep(time::Duration::from_millis(10)), sleep(time::Duration::from_millis(20)).high_priority());
+		let (log_1, log_2) = (log.clone(), log.clone());
+
+		rt.spawn(async move {
+			ordinary.await;
+			log_1.borrow_mut().push("ordinary");
+		});
+		rt.spawn(async move {
+			urgent.await;
+			log_2.borrow_mut().push("urgent");
+		});
+
+		// both timers fire while the runtime is busy, the urgent one is polled first despite waking last
+		rt.block_on(async { std::thread::sleep(time::Duration::from_millis(40)) });
+		rt.block_on(sleep(time::Duration::from_millis(5)));
+
+		// unless polling in wake order
+		match fifo {
+			false => assert_eq!(*log.borrow(), ["urgent", "ordinary"]),
+			true => assert_eq!(*log.borrow(), ["ordinary", "urgent"]),
+		}
+	}
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn cancellable_sleep() {
+	use std::{cell::Cell, rc::Rc};
+
+	let rt = rt::Runtime::new();
+	let start = time::Instant::now();
+
+	// pulled in from another task, long before the original deadline
+	let (watchdog, handle) = timers::sleep_cancellable(time::Duration::from_secs(60));
+	rt.spawn(async move {
+		sleep(time::Duration::from_millis(5)).await;
+		handle.reset(time::Duration::from_millis(5));
+	});
+
+	rt.block_on(watchdog);
+	assert!(start.elapsed() < time::Duration::from_secs(30));
+
+	// cancelled sleeps stay pending, until rescheduled
+	let (watchdog, handle) = timers::sleep_cancellable(time::Duration::from_millis(5));
+	handle.cancel();
+	assert!(handle.is_cancelled());
+
+	let fired = Rc::new(Cell::new(false));
+	let fired_clone = fired.clone();
+	rt.spawn(async move {
+		watchdog.await;
+		fired_clone.set(true);
+	});
+
+	rt.block_on(sleep(time::Duration::from_millis(20)));
+	assert!(!fired.get());
+
+	#[cfg(not(target_os = "wasi"))]
+	std::thread::spawn(move || handle.reset(time::Duration::ZERO)).join().unwrap();
+
+	#[cfg(target_os = "wasi")]
+	handle.reset(time::Duration::ZERO);
+
+	rt.block_on(sleep(time::Duration::from_millis(10)));
+	assert!(fired.get());
+}
+
+#[test]
+#[cfg(all(feature = "timers", not(target_os = "wasi")))]
+fn lazy_timers() {
+	std::thread::spawn(|| {
+		let rt = rt::Runtime::new();
+		rt.block_on(async {});
+		assert!(!timers::is_running());
+
+		// the first timer spawns the sleeping thread
+		rt.block_on(sleep(time::Duration::from_millis(1)));
+		assert!(timers::is_running());
+	})
+	.join()
+	.unwrap();
+
+	std::thread::spawn(|| {
+		let rt = rt::Runtime::new_without_timers();
+		assert!(std::panic::catch_unwind(timers::now).is_err());
+
+		timers::init_with_clock(timers::ManualClock::new());
+		let start = timers::now();
+		assert_eq!(rt.block_on(async move { timers::now() }), start);
+	})
+	.join()
+	.unwrap();
+}
+
+#[test]
+#[cfg(all(feature = "timers", not(target_os = "wasi")))]
+fn timers_teardown() {
+	std::thread::spawn(|| {
+		let (first, second) = (rt::Runtime::new(), rt::Runtime::new());
+		first.block_on(sleep(time::Duration::from_millis(1)));
+
+		// kept alive by the other runtime
+		drop(first);
+		assert!(timers::is_running());
+		second.block_on(sleep(time::Duration::from_millis(1)));
+
+		drop(second);
+		assert!(!timers::is_running());
+		assert!(std::panic::catch_unwind(timers::now).is_err());
+
+		// explicitly initialized timers outlive runtimes, until shut down
+		timers::init();
+		rt::Runtime::new().block_on(sleep(time::Duration::from_millis(1)));
+		assert!(timers::is_running());
+
+		timers::shutdown();
+		assert!(!timers::is_running());
+	})
+	.join()
+	.unwrap();
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn deadlines() {
+	let rt = rt::Runtime::new();
+	let deadline = timers::Deadline::after(time::Duration::from_millis(20));
+
+	assert!(!deadline.is_elapsed());
+	assert!(deadline.remaining() <= time::Duration::from_millis(20));
+	assert_eq!(timers::Deadline::from(deadline.instant()), deadline);
+
+	// awaiting a copy leaves the original usable
+	let fired = rt.block_on(async move { deadline.await });
+	assert!(fired >= deadline.instant());
+	assert!(deadline.is_elapsed() && deadline.remaining().is_zero());
+	assert!(rt.block_on(deadline.sleep()) >= deadline.instant());
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn sleep_accessors() {
+	let rt = rt::Runtime::new();
+	let mut timer = sleep(time::Duration::from_millis(20));
+
+	assert!(!timer.is_elapsed());
+	assert!(timer.remaining() <= time::Duration::from_millis(20));
+
+	// polled again before it's due, e.g. by a spurious wake, it stays pending
+	let early = rt.block_on(std::future::poll_fn(move |cx| {
+		let polls = [std::pin::Pin::new(&mut timer).poll(cx).is_pending(), std::pin::Pin::new(&mut timer).poll(cx).is_pending()];
+		std::task::Poll::Ready((polls, timer.deadline()))
+	}));
+
+	assert_eq!(early.0, [true, true]);
+	assert!(rt.block_on(sleep(time::Duration::from_millis(30))) >= early.1);
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn compat_timers() {
+	use futures::StreamExt;
+	use timers::compat::{Delay, Timer};
+
+	let rt = rt::Runtime::new();
+	let started = time::Instant::now();
+
+	rt.block_on(async {
+		let mut delay = Delay::new(time::Duration::from_secs(60));
+		delay.reset(time::Duration::from_millis(5));
+		delay.await;
+
+		let fired = Timer::after(time::Duration::from_millis(5)).await;
+		let ticks = Timer::interval(time::Duration::from_millis(5)).take(3).collect::<Vec<_>>().await;
+		assert!(ticks.windows(2).all(|w| w[1] - w[0] == time::Duration::from_millis(5)) && ticks[0] >= fired);
+	});
+
+	assert!(started.elapsed() >= time::Duration::from_millis(25));
+}
+
+#[test]
+#[cfg(all(feature = "timers", not(target_os = "wasi")))]
+fn shared_timers() {
+	let runtimes = (0..4).map(|i| {
+		std::thread::spawn(move || {
+			let rt = rt::Builder::new().shared_timers().build();
+			let started = time::Instant::now();
+
+			rt.block_on(sleep(time::Duration::from_millis(10 * (4 - i))));
+			started.elapsed()
+		})
+	});
+
+	for (i, runtime) in runtimes.collect::<Vec<_>>().into_iter().enumerate() {
+		assert!(runtime.join().unwrap() >= time::Duration::from_millis(10 * (4 - i as u64)));
+	}
+}
+
+#[test]
+#[cfg(all(feature = "timers", not(target_os = "wasi")))]
+fn spinning_timers() {
+	let rt = rt::Runtime::new();
+	timers::set_spin(time::Duration::from_millis(2));
+
+	// due within the spin, and past it
+	for dur in [time::Duration::from_micros(300), time::Duration::from_millis(5)] {
+		let started = time::Instant::now();
+		rt.block_on(sleep(dur));
+
+		assert!(started.elapsed() >= dur);
+	}
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn delayed_spawn() {
+	let rt = rt::Runtime::new();
+	let started = timers::now();
 
-	let mut rt = rt::Runtime::new();
-	let result = rt.block_on(fut_3);
+	let later = rt.spawn_after(time::Duration::from_millis(20), async move { timers::now() });
+	let earlier = rt.spawn_at(started + time::Duration::from_millis(10), async move { timers::now() });
+	let id = later.id();
 
-	assert_eq!(result, 44);
+	// nothing is polled before the deadline
+	rt.block_on(async {});
+	assert_eq!(rt.poll_stats(id).unwrap().polls, 0);
+
+	let (later, earlier) = rt.block_on(async move { futures::join!(later, earlier) });
+	assert!(earlier.unwrap() >= started + time::Duration::from_millis(10));
+	assert!(later.unwrap() >= started + time::Duration::from_millis(20));
 }
 
 #[test]
-fn task_spawn() {
-	let fut_60 = async {
-		let mut counter = 60;
+#[cfg(feature = "timers")]
+fn periodic_tasks() {
+	use futures::StreamExt;
 
-		loop {
-			futures::future::ready(()).await;
-			counter -= 1;
+	let rt = rt::Runtime::new();
+	let (runs_tx, runs_rx) = futures::channel::mpsc::unbounded();
 
-			println!("Counter = {}", counter);
-			if counter == 0 {
-				break;
+	let started = timers::now();
+	let periodic = rt
+		.spawn_periodic(time::Duration::from_millis(10), move || {
+			let runs_tx = runs_tx.clone();
+
+			async move {
+				let _ = runs_tx.unbounded_send(timers::now());
+				// work doesn't push back later runs
+				sleep(time::Duration::from_millis(3)).await;
 			}
+		})
+		.unwrap();
+
+	let runs = rt.block_on(runs_rx.take(4).collect::<Vec<_>>());
+	periodic.abort();
+
+	assert!(runs.iter().enumerate().all(|(i, run)| *run >= started + time::Duration::from_millis(10) * i as u32));
+	assert!(runs.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn retry_backoff() {
+	let rt = rt::Runtime::new();
+	let started = timers::now();
+
+	let policy = timers::RetryPolicy::exponential(time::Duration::from_millis(2)).max_delay(time::Duration::from_millis(5)).max_attempts(4);
+	let mut attempts = 0;
+
+	// delays of 2, 4 and 5ms
+	let res = rt.block_on(timers::retry(policy, async move || {
+		attempts += 1;
+		if attempts < 4 { Err(attempts) } else { Ok(attempts) }
+	}));
+
+	assert_eq!(res, Ok(4));
+	assert!(timers::now() - started >= time::Duration::from_millis(11));
+
+	let jittered = timers::RetryPolicy::fixed(time::Duration::from_millis(1)).jitter(0.5).max_attempts(3);
+	assert_eq!(rt.block_on(timers::retry(jittered, async || Err::<(), _>("down"))), Err("down"));
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn batch_timers() {
+	let rt = rt::Runtime::new();
+	let sleeps = timers::sleep_batch((1..=100).map(|i| time::Duration::from_micros(100 * i)));
+
+	// every deadline is measured from the same instant
+	assert!(sleeps.windows(2).all(|w| w[1].due - w[0].due == time::Duration::from_micros(100)));
+	assert!(timers::sleep_batch(None).is_empty());
+
+	let fired = rt.block_on(futures::future::join_all(sleeps));
+	assert!(fired.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn dropped_timers() {
+	let rt = rt::Runtime::new();
+
+	let waiter = rt.spawn(async {
+		// armed, then abandoned before it's due
+		let mut timeout = sleep(time::Duration::from_millis(20));
+		assert!(futures::poll!(&mut timeout).is_pending());
+		drop(timeout);
+
+		futures::future::pending::<()>().await
+	});
+
+	rt.run_for(time::Duration::from_millis(60));
+	assert_eq!(rt.task_dump()[0].wakes, 1, "cancelled timer woke its task");
+	assert!(!waiter.is_finished());
+}
+
+#[test]
+#[cfg(feature = "coarse-timers")]
+fn coarse_timers() {
+	let rt = rt::Runtime::new();
+	let tick = time::Duration::from_millis(50);
+	timers::set_coarse_tick(Some(tick));
+
+	let started = timers::now();
+	let (short, long) = (sleep(time::Duration::from_millis(1)), sleep(time::Duration::from_millis(2)));
+	assert!(short.due > started && short.due <= started + tick);
+
+	// deadlines land on the tick grid, almost always the same tick
+	assert_eq!((long.due - short.due).as_nanos() % tick.as_nanos(), 0);
+	rt.block_on(async move { futures::join!(short, long) });
+
+	timers::set_coarse_tick(None);
+	assert_ne!(sleep(time::Duration::from_millis(1)).due, sleep(time::Duration::from_millis(2)).due);
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn boot_clock() {
+	use timers::Clock;
+
+	let clock = timers::BootClock::new();
+	let (started, boot) = (time::Instant::now(), clock.now());
+
+	std::thread::sleep(time::Duration::from_millis(5));
+	let (elapsed, boot_elapsed) = (started.elapsed(), clock.now() - boot);
+
+	// without a suspend, both clocks advance together
+	assert!(boot_elapsed >= time::Duration::from_millis(5));
+	assert!(boot_elapsed.abs_diff(elapsed) < time::Duration::from_millis(2));
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn manual_clock() {
+	let clock = timers::ManualClock::new();
+	timers::init_with_clock(clock.clone());
+
+	let rt = rt::Runtime::new();
+	let start = timers::now();
+	let nap = sleep(time::Duration::from_secs(3 * 3600));
+
+	// hours pass in virtual time, while only milliseconds pass in real time
+	std::thread::spawn(move || {
+		for _ in 0..10 {
+			std::thread::sleep(time::Duration::from_millis(10));
+			clock.advance(time::Duration::from_secs(3600));
 		}
-	};
+	});
 
-	let mut rt = rt::Runtime::new();
-	let monitor = rt.spawn(fut_60);
+	let woke = rt.block_on(nap);
+	assert_eq!(woke - start, time::Duration::from_secs(3 * 3600));
 
-	rt.block_on(monitor);
+	// advanced between reading the time and parking, on a thread the clock never unparked before
+	let clock = timers::ManualClock::new();
+	let due = timers::Clock::now(&clock) + time::Duration::from_secs(1);
+	clock.advance(time::Duration::from_secs(2));
+
+	std::thread::spawn(move || timers::Clock::park_until(&clock, due)).join().unwrap();
 }
 
 #[test]
 #[cfg(feature = "timers")]
-fn sleep_tasks() {
-	let mut rt = rt::Runtime::new();
+fn interval_missed_ticks() {
+	use futures::FutureExt;
 
-	let sleep_5s = async {
-		println!("Sleeping for 5s");
+	let clock = timers::ManualClock::new();
+	timers::init_with_clock(clock.clone());
 
-		for i in 0..5 {
-			println!("{}s left", 5 - i);
-			sleep(time::Duration::from_secs(1)).await;
+	let period = time::Duration::from_secs(10);
+	let start = timers::now();
+
+	let mut burst = timers::interval(period);
+	let mut delay = timers::interval(period);
+	let mut skip = timers::interval(period);
+	delay.set_missed_tick_behavior(timers::MissedTickBehavior::Delay);
+	skip.set_missed_tick_behavior(timers::MissedTickBehavior::Skip);
+
+	for interval in [&mut burst, &mut delay, &mut skip] {
+		assert_eq!(interval.tick().now_or_never(), Some(start));
+	}
+
+	// the consumer falls behind by three and a half periods
+	clock.advance(time::Duration::from_secs(35));
+
+	let ticks = |interval: &mut timers::Interval| std::iter::from_fn(|| interval.tick().now_or_never()).map(|t| (t - start).as_secs()).collect::<Vec<_>>();
+	assert_eq!(ticks(&mut burst), [10, 20, 30]);
+	assert_eq!(ticks(&mut delay), [10]);
+	assert_eq!(ticks(&mut skip), [10]);
+
+	clock.advance(time::Duration::from_secs(5));
+	assert_eq!(ticks(&mut burst), [40]);
+	assert_eq!(ticks(&mut delay), []);
+	assert_eq!(ticks(&mut skip), [40]);
+
+	clock.advance(time::Duration::from_secs(5));
+	assert_eq!(ticks(&mut delay), [45]);
+
+	// more missed periods than fit a u32
+	let mut fine = timers::interval(time::Duration::from_nanos(1));
+	fine.set_missed_tick_behavior(timers::MissedTickBehavior::Skip);
+	assert!(fine.tick().now_or_never().is_some());
+
+	clock.advance(time::Duration::from_secs(5));
+	assert_eq!(fine.tick().now_or_never().map(|t| timers::now() - t), Some(time::Duration::from_secs(5) - time::Duration::from_nanos(1)));
+	assert_eq!(fine.tick().now_or_never(), None);
+}
+
+#[test]
+#[cfg(all(feature = "timers", not(target_os = "wasi")))]
+fn interval_reset() {
+	use futures::FutureExt;
+
+	let clock = timers::ManualClock::new();
+	timers::init_with_clock(clock.clone());
+
+	let start = timers::now();
+	let mut keepalive = timers::interval(time::Duration::from_secs(10));
+	assert_eq!(keepalive.tick().now_or_never(), Some(start));
+
+	// activity at 8s pushes the next tick back to 18s
+	clock.advance(time::Duration::from_secs(8));
+	keepalive.reset();
+
+	clock.advance(time::Duration::from_secs(5));
+	assert_eq!(keepalive.tick().now_or_never(), None);
+
+	clock.advance(time::Duration::from_secs(5));
+	assert_eq!(keepalive.tick().now_or_never(), Some(start + time::Duration::from_secs(18)));
+
+	keepalive.reset_at(start + time::Duration::from_secs(20));
+	assert_eq!(keepalive.tick().now_or_never(), None);
+
+	clock.advance(time::Duration::from_secs(2));
+	assert_eq!(keepalive.tick().now_or_never(), Some(start + time::Duration::from_secs(20)));
+	assert_eq!(keepalive.tick().now_or_never(), None);
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn stream_throttle_debounce() {
+	use futures::StreamExt as _;
+	use timers::StreamExt as _;
+
+	let rt = rt::Runtime::new();
+
+	let start = time::Instant::now();
+	let throttled = rt.block_on(futures::stream::iter(0..3).throttle(time::Duration::from_millis(50)).collect::<Vec<_>>());
+	assert_eq!(throttled, [0, 1, 2]);
+	assert!(start.elapsed() >= time::Duration::from_millis(100));
+
+	// a burst settles into its last item, then a lone item after a pause
+	let (tx, rx) = futures::channel::mpsc::unbounded();
+	rt.spawn(async move {
+		for i in 1..=3 {
+			tx.unbounded_send(i).unwrap();
 		}
 
-		println!("Done sleeping");
-	};
+		sleep(time::Duration::from_millis(100)).await;
+		tx.unbounded_send(4).unwrap();
+	});
 
-	rt.block_on(sleep_5s);
+	let debounced = rt.block_on(rx.debounce(time::Duration::from_millis(30)).collect::<Vec<_>>());
+	assert_eq!(debounced, [3, 4]);
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn stream_timeout() {
+	use futures::StreamExt as _;
+	use timers::StreamExt as _;
+
+	let rt = rt::Runtime::new();
+	let (tx, rx) = futures::channel::mpsc::unbounded();
+
+	// the peer goes quiet for a while, then recovers
+	rt.spawn(async move {
+		tx.unbounded_send(1).unwrap();
+		sleep(time::Duration::from_millis(100)).await;
+		tx.unbounded_send(2).unwrap();
+	});
+
+	let items = rt.block_on(rx.timeout(time::Duration::from_millis(40)).collect::<Vec<_>>());
+
+	assert_eq!(items.first(), Some(&Ok(1)));
+	assert_eq!(items.last(), Some(&Ok(2)));
+	assert!(items[1..items.len() - 1].iter().all(Result::is_err));
+	assert!(items.len() > 2);
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn rate_limiter() {
+	let rt = rt::Runtime::new();
+	let limiter = timers::RateLimiter::new(2, time::Duration::from_millis(100));
+	let start = time::Instant::now();
+
+	// a burst of two, then one permit every 50ms
+	rt.block_on(async move {
+		for _ in 0..4 {
+			limiter.acquire().await;
+		}
+	});
+
+	let elapsed = start.elapsed();
+	assert!(elapsed >= time::Duration::from_millis(100) && elapsed < time::Duration::from_millis(500), "{elapsed:?}");
+
+	let limiter = timers::RateLimiter::new(1, time::Duration::from_secs(60));
+	assert!(limiter.try_acquire());
+	assert!(!limiter.try_acquire());
+}
+
+#[test]
+#[cfg(feature = "timers")]
+fn wall_clock_sleep() {
+	let rt = rt::Runtime::new();
+	let deadline = time::SystemTime::now() + time::Duration::from_millis(50);
+
+	assert_eq!(rt.block_on(timers::sleep_until_wall(deadline)), deadline);
+	assert!(time::SystemTime::now() >= deadline);
+
+	// deadlines in the past complete right away
+	let past = time::SystemTime::UNIX_EPOCH;
+	assert_eq!(rt.block_on(timers::sleep_until_wall(past)), past);
 }
 
 #[test]
 #[cfg(feature = "timers")]
 fn green_threads() {
-	let mut rt = rt::Runtime::new();
+	let rt = rt::Runtime::new();
 
 	fn task<R: fmt::Display>(id: R) -> impl Future<Output = ()> {
 		async move {
@@ -87,7 +2675,7 @@ fn green_threads() {
 #[test]
 #[cfg(feature = "timers")]
 fn green_threads_wait() {
-	let mut rt = rt::Runtime::new();
+	let rt = rt::Runtime::new();
 
 	// start sleeping task
 	let (tx, rx) = futures::channel::oneshot::channel::<()>();
@@ -120,3 +2708,396 @@ fn green_threads_wait() {
 	let results = rt.block_on(join);
 	assert!(results.len() == 2);
 }
+
+#[test]
+#[cfg(all(feature = "io", unix))]
+fn async_io() {
+	let rt = rt::Runtime::new();
+
+	let (reader, mut writer) = UnixStream::pair().unwrap();
+	reader.set_nonblocking(true).unwrap();
+	let reader = Async::new(reader);
+
+	// write from a foreign thread, after the runtime has started waiting
+	std::thread::spawn(move || {
+		std::thread::sleep(std::time::Duration::from_millis(100));
+		writer.write_all(b"ping").unwrap();
+	});
+
+	let fut = async move {
+		let mut buf = [0; 4];
+		let read = reader.read_with(|mut io| io.read(&mut buf)).await.unwrap();
+
+		(read, buf)
+	};
+
+	assert_eq!(rt.block_on(fut), (4, *b"ping"));
+}
+
+#[test]
+#[cfg(all(feature = "io", unix))]
+fn reactor_teardown() {
+	std::thread::spawn(|| {
+		let (first, second) = (rt::Runtime::new(), rt::Runtime::new());
+		assert!(!io::is_running());
+
+		// the first source spawns the reactor thread, kept alive by the other runtime
+		let (reader, mut writer) = UnixStream::pair().unwrap();
+		reader.set_nonblocking(true).unwrap();
+		let reader = Async::new(reader);
+		assert!(io::is_running());
+
+		drop(first);
+		assert!(io::is_running());
+
+		writer.write_all(b"ping").unwrap();
+		let mut buf = [0; 4];
+		assert_eq!(second.block_on(async move { reader.read_with(|mut io| io.read(&mut buf)).await.unwrap() }), 4);
+
+		drop(second);
+		assert!(!io::is_running());
+		assert!(std::panic::catch_unwind(|| Async::new(UnixStream::pair().unwrap().0)).is_err());
+	})
+	.join()
+	.unwrap();
+}
+
+#[test]
+#[cfg(all(feature = "io", unix))]
+fn async_io_combinators() {
+	let rt = rt::Runtime::new();
+
+	let pair = || {
+		let (a, b) = UnixStream::pair().unwrap();
+		a.set_nonblocking(true).unwrap();
+		b.set_nonblocking(true).unwrap();
+
+		(Async::new(a), Async::new(b))
+	};
+
+	let (source, upstream) = pair();
+	let (downstream, sink) = pair();
+	let payload: Vec<u8> = (0..32 * 1024).map(|i| i as u8).collect();
+
+	// write payload in small chunks, dropping `source` afterwards signals EOF
+	let expected = payload.clone();
+	let writer = rt.spawn(async move {
+		let mut writer = io::BufWriter::new(source);
+
+		for chunk in payload.chunks(100) {
+			io::write_all(&mut writer, chunk).await.unwrap();
+		}
+
+		futures::AsyncWriteExt::flush(&mut writer).await.unwrap();
+	});
+
+	let reader = rt.spawn(async move {
+		let (mut read_half, _write_half) = io::split(sink);
+		let mut buf = vec![0; expected.len()];
+
+		io::read_exact(&mut read_half, &mut buf).await.unwrap();
+		buf == expected
+	});
+
+	let copy = rt.spawn(async move {
+		let mut upstream = io::BufReader::new(upstream);
+		let mut downstream = downstream;
+
+		io::copy(&mut upstream, &mut downstream).await.unwrap()
+	});
+
+	let results = rt.block_on(futures::future::join3(writer, reader, copy));
+	assert_eq!(results, (Some(()), Some(true), Some(32 * 1024)));
+}
+
+#[test]
+#[cfg(all(feature = "io", unix))]
+fn buffered_lines() {
+	use futures::StreamExt;
+
+	let rt = rt::Runtime::new();
+
+	let (reader, mut writer) = UnixStream::pair().unwrap();
+	reader.set_nonblocking(true).unwrap();
+
+	// lines split across writes, and a final one without an ending
+	std::thread::spawn(move || {
+		for chunk in [&b"HELO there\r\nMAIL"[..], b" FROM:<x>\nDATA\nbye", b"\xff\n", b"last"] {
+			writer.write_all(chunk).unwrap();
+			std::thread::sleep(std::time::Duration::from_millis(5));
+		}
+	});
+
+	let fut = async move {
+		let mut reader = io::BufReader::with_capacity(8, Async::new(reader));
+		let mut line = String::new();
+
+		io::read_line(&mut reader, &mut line).await.unwrap();
+		io::read_line(&mut reader, &mut line).await.unwrap();
+
+		let mut data = Vec::new();
+		io::read_until(&mut reader, b'\n', &mut data).await.unwrap();
+
+		let rest = reader.lines().map(|line| line.map_err(|e| e.kind())).collect::<Vec<_>>().await;
+		(line, data, rest)
+	};
+
+	let (line, data, rest) = rt.block_on(fut);
+	assert_eq!(line, "HELO there\r\nMAIL FROM:<x>\n");
+	assert_eq!(data, b"DATA\n");
+	assert_eq!(rest, [Err(std::io::ErrorKind::InvalidData), Ok("last".to_string())]);
+}
+
+#[test]
+#[cfg(all(feature = "io", unix))]
+fn codec_framing() {
+	use futures::StreamExt;
+	use io::codec::{Framed, LengthDelimitedCodec, LinesCodec};
+
+	let rt = rt::Runtime::new();
+
+	let pair = || {
+		let (a, b) = UnixStream::pair().unwrap();
+		a.set_nonblocking(true).unwrap();
+		b.set_nonblocking(true).unwrap();
+
+		(Async::new(a), Async::new(b))
+	};
+
+	let fut = async move {
+		let (a, b) = pair();
+		let (mut tx, rx) = (Framed::new(a, LengthDelimitedCodec::new()), Framed::new(b, LengthDelimitedCodec::with_max_frame_length(16)));
+
+		// frames are batched into a single write
+		for frame in [&b"hello"[..], b"", &[7; 16]] {
+			tx.feed(frame).unwrap();
+		}
+		tx.send([0; 17]).await.unwrap();
+		drop(tx);
+
+		let frames = rx.map(|f| f.map_err(|e| e.kind())).collect::<Vec<_>>().await;
+
+		let (a, b) = pair();
+		let (mut tx, rx) = (Framed::new(a, LinesCodec::new()), Framed::new(b, LinesCodec::new()));
+
+		tx.send("first").await.unwrap();
+		io::write_all(tx.get_mut(), b"second\r\nthird").await.unwrap();
+		drop(tx);
+
+		let lines = rx.map(Result::unwrap).collect::<Vec<_>>().await;
+		(frames, lines)
+	};
+
+	let (frames, lines) = rt.block_on(fut);
+	assert_eq!(frames, [Ok(b"hello".to_vec()), Ok(vec![]), Ok(vec![7; 16]), Err(std::io::ErrorKind::InvalidData)]);
+	assert_eq!(lines, ["first", "second", "third"]);
+}
+
+#[test]
+#[cfg(all(feature = "io", unix))]
+fn bidirectional_copy() {
+	let rt = rt::Runtime::new();
+
+	let pair = || {
+		let (a, b) = UnixStream::pair().unwrap();
+		a.set_nonblocking(true).unwrap();
+		b.set_nonblocking(true).unwrap();
+
+		(Async::new(a), Async::new(b))
+	};
+
+	// client <-> proxy <-> server
+	let (mut client, mut near) = pair();
+	let (mut far, mut server) = pair();
+
+	let proxy = rt.spawn(async move { io::copy_bidirectional(&mut near, &mut far).await.unwrap() });
+
+	let server = rt.spawn(async move {
+		let mut buf = [0; 4];
+		io::read_exact(&mut server, &mut buf).await.unwrap();
+		io::write_all(&mut server, b"pong!").await.unwrap();
+		server.get_ref().shutdown(std::net::Shutdown::Write).unwrap();
+
+		buf
+	});
+
+	let client = rt.spawn(async move {
+		io::write_all(&mut client, b"ping").await.unwrap();
+		client.get_ref().shutdown(std::net::Shutdown::Write).unwrap();
+
+		let mut buf = [0; 5];
+		io::read_exact(&mut client, &mut buf).await.unwrap();
+		buf
+	});
+
+	assert_eq!(rt.block_on(futures::future::join3(proxy, server, client)), (Some((4, 5)), Some(*b"ping"), Some(*b"pong!")));
+}
+
+#[test]
+#[cfg(all(feature = "io", unix))]
+fn tcp_incoming() {
+	use futures::StreamExt;
+
+	let rt = rt::Runtime::new();
+	let listener = io::TcpListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap();
+
+	let clients = std::thread::spawn(move || {
+		for i in 0..3u8 {
+			std::net::TcpStream::connect(addr).unwrap().write_all(&[i]).unwrap();
+		}
+	});
+
+	let fut = async move {
+		let mut received = listener
+			.incoming()
+			.take(3)
+			.then(|stream| async {
+				let mut buf = [0];
+				io::read_exact(&mut stream.unwrap(), &mut buf).await.unwrap();
+				buf[0]
+			})
+			.collect::<Vec<_>>()
+			.await;
+
+		received.sort();
+		received
+	};
+
+	assert_eq!(rt.block_on(fut), [0, 1, 2]);
+	clients.join().unwrap();
+}
+
+#[test]
+#[cfg(all(feature = "io", feature = "timers", any(target_os = "linux", target_os = "android")))]
+fn tcp_connect() {
+	let rt = rt::Runtime::new();
+	let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap();
+
+	// a port nobody listens on, freed right after binding
+	let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+	let fut = async move {
+		let mut stream = io::TcpStream::connect_timeout(addr, time::Duration::from_secs(5)).await.unwrap();
+		io::write_all(&mut stream, b"hi").await.unwrap();
+
+		let refused = io::TcpStream::connect(closed).await.err().map(|e| e.kind());
+
+		// the refused address fails first, letting the next one start right away
+		let started = time::Instant::now();
+		io::TcpStream::connect_racing(&[closed, addr][..]).await.unwrap();
+
+		(refused, started.elapsed() < io::ATTEMPT_DELAY)
+	};
+
+	assert_eq!(rt.block_on(fut), (Some(std::io::ErrorKind::ConnectionRefused), true));
+
+	let mut buf = [0; 2];
+	listener.accept().unwrap().0.read_exact(&mut buf).unwrap();
+	assert_eq!(&buf, b"hi");
+}
+
+#[test]
+#[cfg(all(feature = "io", feature = "timers", unix))]
+fn io_timeouts() {
+	let rt = rt::Runtime::new();
+
+	let (reader, writer) = UnixStream::pair().unwrap();
+	reader.set_nonblocking(true).unwrap();
+	writer.set_nonblocking(true).unwrap();
+
+	let mut reader = io::TimeoutReader::new(Async::new(reader), time::Duration::from_millis(20));
+	let mut writer = Async::new(writer);
+
+	let fut = async move {
+		let mut buf = [0; 4];
+
+		// the peer is silent
+		let silent = futures::AsyncReadExt::read(&mut reader, &mut buf).await.unwrap_err().kind();
+
+		io::write_all(&mut writer, b"ping").await.unwrap();
+		let read = futures::AsyncReadExt::read(&mut reader, &mut buf).await.unwrap();
+
+		let timed_out = io::read_timeout(reader.get_mut(), &mut buf, time::Duration::from_millis(20)).await.unwrap_err().kind();
+		(silent, read, timed_out)
+	};
+
+	assert_eq!(rt.block_on(fut), (std::io::ErrorKind::TimedOut, 4, std::io::ErrorKind::TimedOut));
+}
+
+#[test]
+#[cfg(all(feature = "process", feature = "timers", any(target_os = "linux", target_os = "android")))]
+fn child_processes() {
+	use std::process::Command;
+
+	let rt = rt::Runtime::new();
+
+	let fut = async {
+		let done = crate::process::spawn(&mut Command::new("true")).unwrap().wait().await.unwrap();
+
+		let mut sleeper = crate::process::spawn(Command::new("sleep").arg("10")).unwrap();
+		let still_running = sleeper.wait_with_timeout(time::Duration::from_millis(20)).await.unwrap();
+		let killed = sleeper.kill().await.unwrap();
+
+		// dropping the handle of a running process kills it
+		let mut orphan = crate::process::spawn(Command::new("sleep").arg("10")).unwrap();
+		orphan.kill_on_drop(true);
+		let pid = orphan.id();
+		drop(orphan);
+
+		(done.success(), still_running, killed.success(), std::path::Path::new(&format!("/proc/{pid}")).exists())
+	};
+
+	assert_eq!(rt.block_on(fut), (true, None, false, false));
+}
+
+#[test]
+#[cfg(all(feature = "process", any(target_os = "linux", target_os = "android")))]
+fn child_output_lines() {
+	use futures::StreamExt;
+	use std::process::{Command, Stdio};
+
+	let rt = rt::Runtime::new();
+
+	let fut = async {
+		let mut command = Command::new("sh");
+		command.args(["-c", "echo one; echo two >&2; printf 'three\\r\\nfour'"]).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+		let mut child = crate::process::spawn(&mut command).unwrap();
+		let stdout = child.stdout_lines().unwrap().unwrap().map(|line| ("out", line.unwrap()));
+		let stderr = child.stderr_lines().unwrap().unwrap().map(|line| ("err", line.unwrap()));
+
+		let mut lines = futures::stream::select(stdout, stderr).collect::<Vec<_>>().await;
+		child.wait().await.unwrap();
+
+		lines.sort();
+		lines
+	};
+
+	let expected = [("err", "two"), ("out", "four"), ("out", "one"), ("out", "three")].map(|(pipe, line)| (pipe, line.to_string()));
+	assert_eq!(rt.block_on(fut), expected);
+}
+
+#[test]
+#[cfg(all(feature = "fs", any(target_os = "linux", target_os = "android")))]
+fn fs_watch() {
+	use futures::StreamExt;
+
+	let rt = rt::Runtime::new();
+
+	let dir = std::env::temp_dir().join(format!("pinokkio-fs-watch-{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+
+	let mut watch = crate::fs::watch(&dir).unwrap();
+	let file = dir.join("hot-reload.txt");
+
+	let written = file.clone();
+	let event = rt.block_on(async move {
+		std::fs::write(&written, b"reload").unwrap();
+		watch.next().await.unwrap().unwrap()
+	});
+
+	std::fs::remove_dir_all(&dir).unwrap();
+	assert_eq!(event, crate::fs::Event { path: file, kind: crate::fs::EventKind::Created });
+}
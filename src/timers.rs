@@ -1,71 +1,658 @@
-use std::{cell, collections, future::Future, marker, pin::Pin, sync::mpsc, task, thread, time};
+use crate::oneshot;
+use std::{cell, collections, future::Future, marker, pin::Pin, sync, sync::atomic, task, thread, time};
+
+#[cfg(not(target_os = "wasi"))]
+use std::sync::mpsc;
+
+/// Timers mirroring the APIs of `futures-timer` and `async-io`, for code written against those crates. Like any [`Sleep`], they're bound to the thread that created them
+pub mod compat;
 
 thread_local! {
 	/// Used by `sleep` to queue new timer futures. If a queue exists, then the thread-id of the sleeping thread is known
-	static SLEEPING_THREAD: cell::RefCell<Option<(thread::Thread, mpsc::Sender<TimerTracker>)>> = cell::RefCell::new(None);
+	static SLEEPING_THREAD: cell::RefCell<Option<SleepingThread>> = const { cell::RefCell::new(None) };
+}
+
+/// Handle to the sleeping thread, owned by the runtime thread
+struct SleepingThread {
+	/// `None` until the first timer is registered, commands sent until then are queued for it
+	#[cfg(not(target_os = "wasi"))]
+	thread: Option<thread::Thread>,
+	#[cfg(not(target_os = "wasi"))]
+	sender: mpsc::Sender<Command>,
+	/// handed to the sleeping thread once spawned
+	#[cfg(not(target_os = "wasi"))]
+	receiver: Option<mpsc::Receiver<Command>>,
+	/// the thread this thread's timers spawned, `None` for the shared one. Joined once the timers are torn down
+	#[cfg(not(target_os = "wasi"))]
+	owned: Option<thread::JoinHandle<()>>,
+	/// runtimes alive that set these timers up, `None` if set up explicitly, which keeps them alive until [`shutdown`]
+	runtimes: Option<usize>,
+	/// WASI has no threads, so timers are kept and fired on the runtime thread itself
+	#[cfg(target_os = "wasi")]
+	timers: TimerQueue,
+	clock: sync::Arc<dyn Clock>,
+	/// Deadlines are rounded up to a multiple of the tick, counting from `epoch`
+	#[cfg(feature = "coarse-timers")]
+	coarse: Option<(time::Instant, time::Duration)>,
+}
+
+impl SleepingThread {
+	/// When a timer for `due` should fire
+	fn round(&self, due: time::Instant) -> time::Instant {
+		#[cfg(feature = "coarse-timers")]
+		if let Some((epoch, tick)) = self.coarse {
+			let ticks = due.saturating_duration_since(epoch).as_nanos().div_ceil(tick.as_nanos());
+			return epoch + time::Duration::from_nanos((ticks * tick.as_nanos()) as u64);
+		}
+
+		due
+	}
+
+	fn register(&mut self, trackers: Vec<TimerTracker>) {
+		#[cfg(not(target_os = "wasi"))]
+		{
+			self.sender.send(Command::Register(trackers)).unwrap();
+			self.start();
+
+			// unpark sleeping thread
+			if let Some(thread) = &self.thread {
+				thread.unpark();
+			}
+		}
+
+		#[cfg(target_os = "wasi")]
+		self.timers.apply(Command::Register(trackers));
+	}
+
+	/// Creates a timer due `dur` from `now`, returning the tracker for the sleeping thread
+	fn timer(&self, now: time::Instant, dur: time::Duration) -> (Sleep, TimerTracker) {
+		let (sender, waker_rx) = oneshot::channel(crate::arena::Allocator::GLOBAL);
+		let id = NEXT_TIMER.fetch_add(1, atomic::Ordering::Relaxed);
+		let due = self.round(now + dur);
+
+		(Sleep { id, due, sender: Some(sender), high_priority: false, control: None, _marker: marker::PhantomData }, TimerTracker { id, due, waker_rx })
+	}
+
+	/// Spawns the sleeping thread, if it isn't running yet. Called by the first timer, programs that never sleep don't pay for the thread
+	fn start(&mut self) {
+		#[cfg(not(target_os = "wasi"))]
+		if let Some(receiver) = self.receiver.take() {
+			let sleeper = spawn_sleeper(self.clock.clone(), receiver);
+			self.thread = Some(sleeper.thread().clone());
+			self.owned = Some(sleeper);
+		}
+	}
+
+	/// Passes `command` on, unparking the sleeping thread if it's running
+	#[cfg(not(target_os = "wasi"))]
+	fn command(&self, command: Command) {
+		let _ = self.sender.send(command);
+
+		if let Some(thread) = &self.thread {
+			thread.unpark();
+		}
+	}
+
+	/// Forgets a timer that's no longer awaited. The sleeping thread isn't unparked, it catches up the next time it wakes
+	fn cancel(&mut self, id: u64) {
+		#[cfg(not(target_os = "wasi"))]
+		let _ = self.sender.send(Command::Cancel(id));
+
+		#[cfg(target_os = "wasi")]
+		self.timers.apply(Command::Cancel(id));
+	}
+}
+
+impl Drop for SleepingThread {
+	fn drop(&mut self) {
+		// the shared thread serves other threads, and lives on
+		#[cfg(not(target_os = "wasi"))]
+		if let Some(sleeper) = self.owned.take() {
+			let _ = self.sender.send(Command::Shutdown);
+			sleeper.thread().unpark();
+			let _ = sleeper.join();
+		}
+	}
+}
+
+/// Sent to the sleeping thread, in the order timers are created and dropped
+enum Command {
+	Register(Vec<TimerTracker>),
+	Cancel(u64),
+	/// applied by the sleeping thread itself, so it pins itself
+	#[cfg(not(target_os = "wasi"))]
+	Pin(usize),
+	#[cfg(not(target_os = "wasi"))]
+	Spin(time::Duration),
+	Shuffle(Option<u64>),
+	/// the timers were torn down, stops the sleeping thread
+	#[cfg(not(target_os = "wasi"))]
+	Shutdown,
+}
+
+/// Ids of timers, unique across threads sharing a sleeping thread
+static NEXT_TIMER: atomic::AtomicU64 = atomic::AtomicU64::new(0);
+
+/// Pending timers, ordered by due time
+#[derive(Default)]
+struct TimerQueue {
+	timers: collections::BinaryHeap<TimerTracker>,
+	/// Timers that are overdue, but haven't been polled yet. Thus no waker is available
+	zombies: Vec<oneshot::Receiver<task::Waker>>,
+	/// Timers dropped before they were due, still in `timers`
+	cancelled: collections::BTreeSet<u64>,
+	/// The sleeping thread spins instead of parking, for timers due within it. See [`set_spin`]
+	#[cfg(not(target_os = "wasi"))]
+	spin: time::Duration,
+	/// Shuffles timers firing at the same deadline, see [`set_shuffle`]
+	shuffle: Option<crate::rt::Rng>,
+}
+
+impl TimerQueue {
+	fn apply(&mut self, command: Command) {
+		match command {
+			Command::Register(trackers) => self.timers.extend(trackers),
+			Command::Cancel(id) => {
+				self.cancelled.insert(id);
+
+				// sweep once most trackers are dead, so long cancelled timeouts don't pile up
+				if self.cancelled.len() * 2 > self.timers.len() {
+					self.timers.retain(|t| !self.cancelled.contains(&t.id));
+					self.cancelled.clear();
+				}
+			}
+			#[cfg(not(target_os = "wasi"))]
+			Command::Pin(core) => {
+				let _ = crate::rt::pin_current_thread(core);
+			}
+			#[cfg(not(target_os = "wasi"))]
+			Command::Spin(spin) => self.spin = spin,
+			Command::Shuffle(seed) => self.shuffle = seed.map(crate::rt::Rng::new),
+			// handled by the sleeping thread's loop
+			#[cfg(not(target_os = "wasi"))]
+			Command::Shutdown => (),
+		}
+	}
+
+	/// Wakes every timer due by `now`, returns `true` if any tasks were woken
+	fn fire(&mut self, now: time::Instant) -> bool {
+		let mut fired = false;
+		let mut due = Vec::<(time::Instant, task::Waker)>::new();
+
+		// pop due overdue timers from queue
+		while self.timers.peek().map(|t| t.due <= now).unwrap_or(false) {
+			if let Some(TimerTracker { waker_rx, id, due: at }) = self.timers.pop() {
+				// its task no longer waits on it
+				if self.cancelled.remove(&id) {
+					continue;
+				}
+
+				match waker_rx.try_recv() {
+					Ok(waker) => due.push((at, waker)),
+					// timer is due, but hasn't been polled yet
+					Err(oneshot::TryRecvError::Empty) => self.zombies.push(waker_rx),
+					// timer is due, but was dropped. either dropped itself or dropped prematurely
+					Err(oneshot::TryRecvError::Disconnected) => (),
+				}
+			}
+		}
+
+		// only timers sharing a deadline trade places, earlier ones still fire first
+		if let Some(rng) = &mut self.shuffle {
+			due.chunk_by_mut(|a, b| a.0 == b.0).for_each(|timers| rng.shuffle(timers));
+		}
+
+		for (_, waker) in due {
+			waker.wake();
+			fired = true;
+		}
+
+		// attempt to poll zombie timers
+		self.zombies.retain(|waker_rx| match waker_rx.try_recv() {
+			Ok(waker) => {
+				waker.wake();
+				fired = true;
+				false
+			}
+			Err(oneshot::TryRecvError::Empty) => true,
+			Err(oneshot::TryRecvError::Disconnected) => false,
+		});
+
+		fired
+	}
+
+	/// Due time of the next live timer, so cancelled ones don't wake the sleeping thread
+	fn next_due(&mut self) -> Option<time::Instant> {
+		while let Some(head) = self.timers.peek()
+			&& self.cancelled.remove(&head.id)
+		{
+			self.timers.pop();
+		}
+
+		self.timers.peek().map(|t| t.due)
+	}
+}
+
+/// A source of time for timers, allowing virtual or hardware time to replace the system's monotonic clock
+pub trait Clock: Send + Sync {
+	/// The current time, must never go backwards
+	fn now(&self) -> time::Instant;
+
+	/// Blocks the sleeping thread until roughly `due`. Must return early if the sleeping thread is unparked
+	fn park_until(&self, due: time::Instant) {
+		let dur = due.saturating_duration_since(self.now());
+
+		// nothing can unpark a thread on WASI, sleep is implemented with `poll_oneoff`
+		#[cfg(target_os = "wasi")]
+		thread::sleep(dur);
+
+		#[cfg(not(target_os = "wasi"))]
+		thread::park_timeout(dur)
+	}
+}
+
+/// The system's monotonic clock, used by default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+	fn now(&self) -> time::Instant {
+		time::Instant::now()
+	}
+}
+
+/// A clock that keeps counting while the system is suspended, so timers spanning a suspend fire on time after resuming.
+///
+/// Uses `CLOCK_BOOTTIME` on Linux and Android, and the continuous `CLOCK_MONOTONIC` on Apple platforms. Elsewhere it behaves like [`SystemClock`].
+/// Install it with [`init_with_clock`] before building the runtime
+#[derive(Debug, Clone, Copy)]
+pub struct BootClock {
+	/// an instant, and the boot clock's reading taken along with it
+	base: (time::Instant, time::Duration),
+}
+
+/// How long the sleeping thread parks at most with a [`BootClock`], parking itself stops counting while suspended
+const BOOT_CLOCK_RECHECK: time::Duration = time::Duration::from_secs(1);
+
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+mod boottime {
+	use std::ffi::{c_int, c_long};
+
+	#[repr(C)]
+	struct Timespec {
+		tv_sec: c_long,
+		tv_nsec: c_long,
+	}
+
+	unsafe extern "C" {
+		fn clock_gettime(clock: c_int, tp: *mut Timespec) -> c_int;
+	}
+
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	const CLOCK: c_int = 7;
+	#[cfg(target_vendor = "apple")]
+	const CLOCK: c_int = 6;
+
+	pub(super) fn now() -> std::time::Duration {
+		let mut ts = Timespec { tv_sec: 0, tv_nsec: 0 };
+		assert_eq!(unsafe { clock_gettime(CLOCK, &mut ts) }, 0, "Unable to read the boot clock");
+
+		std::time::Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+	}
+}
+
+impl Default for BootClock {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl BootClock {
+	/// Creates a clock, starting from the current instant
+	pub fn new() -> Self {
+		BootClock { base: (time::Instant::now(), Self::elapsed()) }
+	}
+
+	/// Reading of the underlying clock, from an arbitrary starting point
+	fn elapsed() -> time::Duration {
+		#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+		return boottime::now();
+
+		#[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+		return time::Instant::now().duration_since(*FALLBACK_BASE.get_or_init(time::Instant::now));
+	}
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+static FALLBACK_BASE: sync::OnceLock<time::Instant> = sync::OnceLock::new();
+
+impl Clock for BootClock {
+	fn now(&self) -> time::Instant {
+		let (instant, boot) = self.base;
+		instant + Self::elapsed().saturating_sub(boot)
+	}
+
+	fn park_until(&self, due: time::Instant) {
+		let dur = due.saturating_duration_since(self.now()).min(BOOT_CLOCK_RECHECK);
+
+		#[cfg(target_os = "wasi")]
+		thread::sleep(dur);
+
+		#[cfg(not(target_os = "wasi"))]
+		thread::park_timeout(dur)
+	}
+}
+
+/// An externally stepped clock, time only moves forward by calling [`ManualClock::advance`]
+#[derive(Clone)]
+pub struct ManualClock {
+	inner: sync::Arc<sync::Mutex<(time::Instant, Vec<thread::Thread>)>>,
+}
+
+impl Default for ManualClock {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
-/// Spawns a dedicated lightweight sleeping thread for OS preemption of sleeping futures
+impl ManualClock {
+	/// Creates a clock frozen at the current instant
+	pub fn new() -> Self {
+		ManualClock { inner: sync::Arc::new(sync::Mutex::new((time::Instant::now(), Vec::new()))) }
+	}
+
+	/// Moves time forward by `dur`, firing any timers that become due
+	pub fn advance(&self, dur: time::Duration) {
+		let mut inner = self.inner.lock().unwrap();
+		inner.0 += dur;
+
+		// sleeping threads re-evaluate their timers against the new time
+		inner.1.iter().for_each(thread::Thread::unpark);
+	}
+}
+
+impl Clock for ManualClock {
+	fn now(&self) -> time::Instant {
+		self.inner.lock().unwrap().0
+	}
+
+	fn park_until(&self, due: time::Instant) {
+		{
+			let mut inner = self.inner.lock().unwrap();
+
+			// advanced past `due` since the caller read the time, before we could be unparked
+			if inner.0 >= due {
+				return;
+			}
+
+			let current = thread::current();
+
+			if !inner.1.iter().any(|t| t.id() == current.id()) {
+				inner.1.push(current);
+			}
+		}
+
+		// only `advance`, or new timers, wakes us
+		thread::park()
+	}
+}
+
+/// The current time according to the timers' [`Clock`]
+pub fn now() -> time::Instant {
+	SLEEPING_THREAD.with_borrow(|s| match s {
+		Some(s) => s.clock.now(),
+		None => panic!("Sleeping thread has not been initialized"),
+	})
+}
+
+/// Rounds deadlines of timers created afterwards up to a multiple of `tick`, or restores precise deadlines with `None`.
+///
+/// Timers due within the same tick then fire together, waking the sleeping thread and runtime once. Meant for power constrained hosts with many loose timers
+#[cfg(feature = "coarse-timers")]
+pub fn set_coarse_tick(tick: Option<time::Duration>) {
+	SLEEPING_THREAD.with_borrow_mut(|s| match s {
+		Some(s) => s.coarse = tick.filter(|t| !t.is_zero()).map(|t| (s.clock.now(), t)),
+		None => panic!("Sleeping thread has not been initialized"),
+	})
+}
+
+/// Sets up timers for this thread. A dedicated lightweight sleeping thread, for OS preemption of sleeping futures, is spawned once the first timer is created.
+///
+/// On WASI, no thread is spawned. The runtime instead fires timers itself, sleeping until the next one is due while idle
 pub fn init() {
+	init_with_clock(SystemClock)
+}
+
+/// Like [`init`], but timers measure time using `clock`. Has no effect if the sleeping thread is already running on this thread
+pub fn init_with_clock<C: Clock + 'static>(clock: C) {
+	SLEEPING_THREAD.with_borrow_mut(|queue| {
+		if queue.is_none() {
+			let clock: sync::Arc<dyn Clock> = sync::Arc::new(clock);
+
+			#[cfg(target_os = "wasi")]
+			{
+				*queue = Some(SleepingThread {
+					timers: TimerQueue::default(),
+					clock,
+					runtimes: None,
+					#[cfg(feature = "coarse-timers")]
+					coarse: None,
+				});
+			}
+
+			#[cfg(not(target_os = "wasi"))]
+			{
+				let (sender, receiver) = mpsc::channel();
+
+				*queue = Some(SleepingThread {
+					thread: None,
+					sender,
+					receiver: Some(receiver),
+					owned: None,
+					runtimes: None,
+					clock,
+					#[cfg(feature = "coarse-timers")]
+					coarse: None,
+				});
+			}
+		}
+	});
+}
+
+/// Like [`init`], but this thread's timers share a single process-wide sleeping thread with every other thread initialized this way.
+///
+/// Wakes are still routed to the runtime owning each timer. Reduces thread count in apps running many small runtimes.
+/// Has no effect if this thread's timers are already initialized, on WASI it's the same as [`init`]
+pub fn init_shared() {
+	#[cfg(target_os = "wasi")]
+	init();
+
+	#[cfg(not(target_os = "wasi"))]
 	SLEEPING_THREAD.with_borrow_mut(|queue| {
-		if let None = queue {
-			// init sleeping thread and current thread state
-			let (sender, receiver) = mpsc::channel::<TimerTracker>();
-
-			// start sleeping thread
-			let sleeper = thread::spawn(move || {
-				let mut timers = collections::BinaryHeap::<TimerTracker>::new();
-				// Timers that are overdue, but haven't been polled yet. Thus no waker is available
-				let mut zombie_timers = Vec::new();
-
-				loop {
-					let now = time::Instant::now();
-
-					// insert new timer futures
-					timers.extend(receiver.try_iter());
-
-					// pop due overdue timers from queue
-					while timers.peek().map(|t| t.due <= now).unwrap_or(false) {
-						if let Some(TimerTracker { waker_rx, .. }) = timers.pop() {
-							match waker_rx.try_recv() {
-								Ok(waker) => waker.wake(),
-								// timer is due, but hasn't been polled yet
-								Err(oneshot::TryRecvError::Empty) => zombie_timers.push(waker_rx),
-								// timer is due, but was dropped. either dropped itself or dropped prematurely
-								Err(oneshot::TryRecvError::Disconnected) => (),
-							}
+		static SHARED: sync::OnceLock<(thread::Thread, mpsc::Sender<Command>)> = sync::OnceLock::new();
+
+		if queue.is_none() {
+			let (thread, sender) = SHARED.get_or_init(|| {
+				let (sender, receiver) = mpsc::channel();
+				(spawn_sleeper(sync::Arc::new(SystemClock), receiver).thread().clone(), sender)
+			});
+
+			*queue = Some(SleepingThread {
+				thread: Some(thread.clone()),
+				sender: sender.clone(),
+				receiver: None,
+				owned: None,
+				runtimes: None,
+				clock: sync::Arc::new(SystemClock),
+				#[cfg(feature = "coarse-timers")]
+				coarse: None,
+			});
+		}
+	});
+}
+
+/// Sets up this thread's timers for a runtime. Timers set up by runtimes are torn down once the last of them is dropped
+pub(crate) fn acquire(shared: bool) {
+	let fresh = SLEEPING_THREAD.with_borrow(Option::is_none);
+
+	match shared {
+		true => init_shared(),
+		false => init(),
+	}
+
+	SLEEPING_THREAD.with_borrow_mut(|s| {
+		if let Some(s) = s {
+			match &mut s.runtimes {
+				Some(runtimes) => *runtimes += 1,
+				runtimes if fresh => *runtimes = Some(1),
+				None => (),
+			}
+		}
+	});
+}
+
+/// Releases timers acquired by a runtime being dropped
+pub(crate) fn release() {
+	// dropped outside the borrow, stopping the sleeping thread
+	let _teardown = SLEEPING_THREAD.try_with(|s| {
+		let mut s = s.try_borrow_mut().ok()?;
+		let runtimes = s.as_mut()?.runtimes.as_mut()?;
+		*runtimes -= 1;
+
+		match *runtimes {
+			0 => s.take(),
+			_ => None,
+		}
+	});
+}
+
+/// Tears down this thread's timers, stopping its sleeping thread unless shared. Pending timers never fire, and new ones panic until timers are set up again.
+///
+/// Timers set up by runtimes are torn down once the last of them is dropped, explicitly initialized ones live until this is called
+pub fn shutdown() {
+	let timers = SLEEPING_THREAD.with_borrow_mut(Option::take);
+	drop(timers);
+}
+
+/// This thread's sleeping thread was spawned
+#[cfg(all(test, not(target_os = "wasi")))]
+pub(crate) fn is_running() -> bool {
+	SLEEPING_THREAD.with_borrow(|s| s.as_ref().is_some_and(|s| s.thread.is_some()))
+}
+
+/// Pins this thread's sleeping thread to CPU `core`, best effort. Does nothing on WASI.
+///
+/// The sleeping thread serves every runtime on this thread, and after [`init_shared`] every thread sharing it, so all of their timers are fired from `core`
+pub fn pin_to_core(core: usize) {
+	SLEEPING_THREAD.with_borrow(|s| match s {
+		#[cfg(not(target_os = "wasi"))]
+		Some(s) => s.command(Command::Pin(core)),
+		#[cfg(target_os = "wasi")]
+		Some(_) => {
+			let _ = core;
+		}
+		None => panic!("Sleeping thread has not been initialized"),
+	})
+}
+
+/// Makes this thread's sleeping thread spin for the last `spin` before each timer is due, instead of parking through it.
+///
+/// Parking oversleeps by the OS' timer slack, often tens of microseconds, spinning keeps sub-millisecond timers accurate. The thread still parks while
+/// the next timer is further off, so coarse timers don't burn a core. Zero, the default, never spins. Does nothing on WASI.
+///
+/// Like [`pin_to_core`], it applies to every runtime using the sleeping thread, including other threads' after [`init_shared`]
+pub fn set_spin(spin: time::Duration) {
+	SLEEPING_THREAD.with_borrow(|s| match s {
+		#[cfg(not(target_os = "wasi"))]
+		Some(s) => s.command(Command::Spin(spin)),
+		#[cfg(target_os = "wasi")]
+		Some(_) => {
+			let _ = spin;
+		}
+		None => panic!("Sleeping thread has not been initialized"),
+	})
+}
+
+/// Shuffles timers firing at the same deadline with a generator seeded by `seed`, or restores their creation order with `None`.
+///
+/// The timer counterpart of [`Builder::shuffle`](crate::rt::Builder::shuffle). The order is shared by every runtime using this thread's sleeping thread,
+/// including other threads' after [`init_shared`], and lasts until the timers are torn down
+pub fn set_shuffle(seed: Option<u64>) {
+	SLEEPING_THREAD.with_borrow_mut(|s| match s {
+		#[cfg(not(target_os = "wasi"))]
+		Some(s) => s.command(Command::Shuffle(seed)),
+		#[cfg(target_os = "wasi")]
+		Some(s) => s.timers.apply(Command::Shuffle(seed)),
+		None => panic!("Sleeping thread has not been initialized"),
+	})
+}
+
+/// Starts a sleeping thread, firing timers sent through `receiver`
+#[cfg(not(target_os = "wasi"))]
+fn spawn_sleeper(clock: sync::Arc<dyn Clock>, receiver: mpsc::Receiver<Command>) -> thread::JoinHandle<()> {
+	thread::spawn(move || {
+		let mut timers = TimerQueue::default();
+
+		loop {
+			// insert new timer futures, and forget dropped ones
+			for command in receiver.try_iter() {
+				match command {
+					Command::Shutdown => return,
+					command => timers.apply(command),
+				}
+			}
+
+			timers.fire(clock.now());
+
+			// if we have any timers pending, sleep and wake task
+			match timers.next_due() {
+				Some(due) => match due.saturating_duration_since(clock.now()) {
+					// bounded by real time, so virtual clocks that don't advance can't keep it spinning
+					left if left <= timers.spin => {
+						let started = time::Instant::now();
+
+						while clock.now() < due && started.elapsed() <= timers.spin {
+							std::hint::spin_loop()
 						}
-					}
 
-					// attempt to poll zombie timers
-					let mut old_zombies = Vec::with_capacity(zombie_timers.len());
-					for waker_rx in zombie_timers.drain(..) {
-						match waker_rx.try_recv() {
-							Ok(waker) => waker.wake(),
-							Err(oneshot::TryRecvError::Empty) => old_zombies.push(waker_rx),
-							Err(oneshot::TryRecvError::Disconnected) => (),
+						if clock.now() < due {
+							clock.park_until(due)
 						}
 					}
+					// wake early, then spin the rest of the way
+					left => clock.park_until(due - timers.spin.min(left)),
+				},
+				// runtime thread will unpark sleeping thread to process any new timers
+				None => thread::park(),
+			}
+		}
+	})
+}
 
-					zombie_timers.append(&mut old_zombies);
+/// Fires due timers on the runtime thread, sleeping until the next one is due, or `timeout` passes, if none are. Used in place of parking on WASI
+#[cfg(target_os = "wasi")]
+pub(crate) fn park(timeout: Option<time::Duration>) {
+	SLEEPING_THREAD.with_borrow_mut(|s| {
+		let Some(SleepingThread { timers, clock, .. }) = s else { return };
+		let now = clock.now();
 
-					// if we have any timers pending, sleep and wake task
-					if let Some(e) = timers.peek() {
-						thread::sleep(e.due - time::Instant::now());
-					} else {
-						// runtime thread will unpark sleeping thread to process any new timers
-						thread::park();
-					}
-				}
-			});
+		if !timers.fire(now) {
+			let wake_at = match (timers.next_due(), timeout.map(|t| now + t)) {
+				(Some(due), Some(deadline)) => Some(due.min(deadline)),
+				(due, deadline) => due.or(deadline),
+			};
 
-			*queue = Some((sleeper.thread().clone(), sender));
+			if let Some(wake_at) = wake_at {
+				clock.park_until(wake_at);
+				timers.fire(clock.now());
+			}
 		}
-	});
+	})
 }
 
 /// Keeps track of when a timer is due, as well as a waker to poll the adjacent future.
 struct TimerTracker {
+	id: u64,
 	due: time::Instant,
 	waker_rx: oneshot::Receiver<task::Waker>,
 }
@@ -80,7 +667,7 @@ impl Eq for TimerTracker {}
 
 impl PartialOrd for TimerTracker {
 	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-		other.due.partial_cmp(&self.due)
+		Some(self.cmp(other))
 	}
 }
 
@@ -92,50 +679,717 @@ impl Ord for TimerTracker {
 
 /// Creates a new [`Sleep`] future
 pub fn sleep(dur: time::Duration) -> Sleep {
-	let due = time::Instant::now() + dur;
-	let (sender, waker_rx) = oneshot::channel();
-
-	SLEEPING_THREAD.with_borrow(|s| match s {
-		Some((thread, sender)) => {
-			sender.send(TimerTracker { due, waker_rx }).unwrap();
-			// unpark sleeping thread
-			thread.unpark();
+	SLEEPING_THREAD.with_borrow_mut(|s| match s {
+		Some(s) => {
+			// before reading the clock, so spawning the thread doesn't eat into the timer
+			s.start();
+			let (sleep, tracker) = s.timer(s.clock.now(), dur);
+			s.register(vec![tracker]);
+			sleep
 		}
 		None => panic!("Sleeping thread has not been initialized"),
+	})
+}
+
+/// Wakes `waker` once `due` passes, without a [`Sleep`] to poll. Returns the timer's id
+pub(crate) fn wake_at(due: time::Instant, waker: task::Waker) -> u64 {
+	let (sender, waker_rx) = oneshot::channel(crate::arena::Allocator::GLOBAL);
+	let _ = sender.send(waker);
+	let id = NEXT_TIMER.fetch_add(1, atomic::Ordering::Relaxed);
+
+	SLEEPING_THREAD.with_borrow_mut(|s| match s {
+		Some(s) => s.register(vec![TimerTracker { id, due: s.round(due), waker_rx }]),
+		None => panic!("Sleeping thread has not been initialized"),
 	});
 
-	Sleep { due, sender: Some(sender), _marker: marker::PhantomData }
+	id
+}
+
+/// Creates a [`Sleep`] along with a [`SleepHandle`], which cancels or reschedules it from any task or thread, without dropping and re-arming the future.
+///
+/// Meant for resettable watchdogs, pushed back by each sign of life
+pub fn sleep_cancellable(dur: time::Duration) -> (Sleep, SleepHandle) {
+	let mut sleep = sleep(dur);
+	let clock = SLEEPING_THREAD.with_borrow(|s| s.as_ref().map(|s| s.clock.clone())).unwrap();
+
+	let control = sync::Arc::new(SleepControl { state: sync::Mutex::new((Some(sleep.due), None)), clock });
+	sleep.control = Some(control.clone());
+
+	(sleep, SleepHandle { control })
+}
+
+/// State shared by a [`Sleep`] and its [`SleepHandle`]s
+struct SleepControl {
+	/// the latest deadline, `None` once cancelled, and the waker of the latest poll
+	state: sync::Mutex<(Option<time::Instant>, Option<task::Waker>)>,
+	/// the clock of the sleep's thread, reschedules are measured from it
+	clock: sync::Arc<dyn Clock>,
+}
+
+/// Cancels or reschedules a [`Sleep`] created by [`sleep_cancellable`]. Can be cloned and sent to other threads, changes reach the sleep once it's polled again
+#[derive(Clone)]
+pub struct SleepHandle {
+	control: sync::Arc<SleepControl>,
+}
+
+impl SleepHandle {
+	/// Stops the sleep from ever completing, unless rescheduled later on
+	pub fn cancel(&self) {
+		self.update(None)
+	}
+
+	/// Reschedules the sleep to complete `dur` from now, even if it was cancelled
+	pub fn reset(&self, dur: time::Duration) {
+		self.update(Some(self.control.clock.now() + dur))
+	}
+
+	/// Reschedules the sleep to complete at `deadline`, even if it was cancelled
+	pub fn reset_at(&self, deadline: time::Instant) {
+		self.update(Some(deadline))
+	}
+
+	/// The sleep's latest deadline, `None` if cancelled
+	pub fn deadline(&self) -> Option<time::Instant> {
+		self.control.state.lock().unwrap().0
+	}
+
+	/// The sleep was cancelled, and not rescheduled since
+	pub fn is_cancelled(&self) -> bool {
+		self.deadline().is_none()
+	}
+
+	fn update(&self, due: Option<time::Instant>) {
+		let waker = {
+			let mut state = self.control.state.lock().unwrap();
+			state.0 = due;
+			state.1.take()
+		};
+
+		// the sleep re-arms, or drops its timer, on its own thread
+		if let Some(waker) = waker {
+			waker.wake()
+		}
+	}
+}
+
+impl std::fmt::Debug for SleepHandle {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("SleepHandle").field("deadline", &self.deadline()).finish()
+	}
+}
+
+/// Creates a [`Sleep`] for each duration, all measured from the same instant.
+///
+/// The timers reach the sleeping thread in a single message, waking it once. Cheaper than calling [`sleep`] in a loop when arming many timers at once
+pub fn sleep_batch<I: IntoIterator<Item = time::Duration>>(durations: I) -> Vec<Sleep> {
+	// collected first, the iterator may create timers of its own
+	let durations = durations.into_iter().collect::<Vec<_>>();
+
+	SLEEPING_THREAD.with_borrow_mut(|s| match s {
+		Some(s) => {
+			s.start();
+			let now = s.clock.now();
+			let (sleeps, trackers) = durations.into_iter().map(|dur| s.timer(now, dur)).unzip::<_, _, Vec<_>, Vec<_>>();
+
+			if !trackers.is_empty() {
+				s.register(trackers);
+			}
+
+			sleeps
+		}
+		None => panic!("Sleeping thread has not been initialized"),
+	})
 }
 
 /// Immediately returns if `due` has already passed during the time of invocation.
 pub struct Sleep {
+	pub(crate) id: u64,
 	pub(crate) due: time::Instant,
 	pub(crate) sender: Option<oneshot::Sender<task::Waker>>,
+	/// see [`Sleep::high_priority`]
+	pub(crate) high_priority: bool,
+	/// shared with the handles of [`sleep_cancellable`]
+	control: Option<sync::Arc<SleepControl>>,
 	pub(crate) _marker: marker::PhantomData<*mut u8>,
 }
 
 impl Unpin for Sleep {}
 
+impl Sleep {
+	/// When the sleep completes. The latest deadline set through a [`SleepHandle`], or the last one before it was cancelled
+	pub fn deadline(&self) -> time::Instant {
+		self.control.as_ref().and_then(|control| control.state.lock().unwrap().0).unwrap_or(self.due)
+	}
+
+	/// Checks whether the deadline has passed, without polling the sleep. Never true once cancelled
+	pub fn is_elapsed(&self) -> bool {
+		!self.is_cancelled() && now() >= self.deadline()
+	}
+
+	/// Time left until the deadline, zero once it has passed
+	pub fn remaining(&self) -> time::Duration {
+		self.deadline().saturating_duration_since(now())
+	}
+
+	/// Cancelled through a [`SleepHandle`]
+	fn is_cancelled(&self) -> bool {
+		self.control.as_ref().is_some_and(|control| control.state.lock().unwrap().0.is_none())
+	}
+
+	/// Forgets the timer armed for the current deadline, so it doesn't wake the task
+	fn disarm(&mut self) {
+		self.sender = None;
+
+		// the tracker would otherwise linger until due, then wake a task that stopped waiting
+		let _ = SLEEPING_THREAD.try_with(|s| {
+			if let Ok(mut s) = s.try_borrow_mut()
+				&& let Some(s) = &mut *s
+				&& s.clock.now() < self.due
+			{
+				s.cancel(self.id)
+			}
+		});
+	}
+
+	/// Once the sleep fires, its task is polled ahead of ordinary wakes in the same batch, such as audio or input deadlines queued behind bulk work.
+	/// Ignored by runtimes in [`fifo`](crate::rt::Builder::fifo) mode
+	pub fn high_priority(mut self) -> Self {
+		self.high_priority = true;
+		self
+	}
+
+	/// Waker handed to the sleeping thread
+	fn waker(&self, cx: &task::Context<'_>) -> task::Waker {
+		match self.high_priority {
+			true => task::Waker::from(sync::Arc::new(Prioritized(cx.waker().clone()))),
+			false => cx.waker().clone(),
+		}
+	}
+}
+
+/// Waker of a high priority [`Sleep`], marks the wrapped wake as urgent for its runtime
+struct Prioritized(task::Waker);
+
+impl task::Wake for Prioritized {
+	fn wake(self: sync::Arc<Self>) {
+		crate::rt::wake_prioritized(&self.0)
+	}
+
+	fn wake_by_ref(self: &sync::Arc<Self>) {
+		crate::rt::wake_prioritized(&self.0)
+	}
+}
+
+impl Drop for Sleep {
+	fn drop(&mut self) {
+		self.disarm()
+	}
+}
+
 impl Future for Sleep {
 	type Output = time::Instant;
 
 	fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
-		// we've been woken by the runtime, as the oneshot is consumed
-		if self.sender.is_none() {
-			return task::Poll::Ready(self.due);
-		}
-
-		// avoid waking self if due is passed
-		match time::Instant::now() > self.due {
-			true => task::Poll::Ready(self.due),
-			false => {
-				// the runtime will wake us when timer is done
-				if let Some(s) = self.sender.take() {
-					s.send(cx.waker().clone()).expect("Receiver dropped, can't send Waker");
+		crate::coop::poll_budgeted(cx, |cx| {
+			// handles may have cancelled or rescheduled the sleep since it was last polled
+			if let Some(control) = self.control.clone() {
+				let mut state = control.state.lock().unwrap();
+				state.1 = Some(cx.waker().clone());
+
+				match state.0 {
+					None => {
+						drop(state);
+						self.disarm();
+						return task::Poll::Pending;
+					}
+					Some(due) if due != self.due => {
+						drop(state);
+						self.disarm();
+						self.due = due;
+					}
+					Some(_) => (),
 				}
+			}
 
-				task::Poll::Pending
+			// the sleeping thread already holds a waker, polled early by a spurious wake a new one is armed, possibly for another task
+			if self.sender.is_none() {
+				if now() < self.due {
+					self.disarm();
+					self.id = wake_at(self.due, self.waker(cx));
+					crate::rt::record_await(crate::tasks::Awaiting::Timer(self.due));
+					return task::Poll::Pending;
+				}
+
+				crate::rt::emit_timer_fired(self.due);
+				return task::Poll::Ready(self.due);
 			}
+
+			// avoid waking self if due is passed
+			match now() > self.due {
+				true => {
+					crate::rt::emit_timer_fired(self.due);
+					task::Poll::Ready(self.due)
+				}
+				false => {
+					// the runtime will wake us when timer is done
+					let waker = self.waker(cx);
+					if let Some(s) = self.sender.take() {
+						s.send(waker).expect("Receiver dropped, can't send Waker");
+					}
+
+					crate::rt::record_await(crate::tasks::Awaiting::Timer(self.due));
+					task::Poll::Pending
+				}
+			}
+		})
+	}
+}
+
+/// A point in time measured by the timers' [`Clock`], cheap to copy and check. Lets APIs accept a deadline shared by several operations, instead of a duration each
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline(time::Instant);
+
+impl Deadline {
+	/// The deadline `dur` from now
+	pub fn after(dur: time::Duration) -> Self {
+		Deadline(now() + dur)
+	}
+
+	/// The deadline at `instant`
+	pub fn at(instant: time::Instant) -> Self {
+		Deadline(instant)
+	}
+
+	/// When the deadline is due
+	pub fn instant(&self) -> time::Instant {
+		self.0
+	}
+
+	/// Checks whether the deadline has passed, without registering a timer
+	pub fn is_elapsed(&self) -> bool {
+		now() >= self.0
+	}
+
+	/// Time left until the deadline, zero once it has passed
+	pub fn remaining(&self) -> time::Duration {
+		self.0.saturating_duration_since(now())
+	}
+
+	/// A [`Sleep`] completing at the deadline, the timer is only registered now
+	pub fn sleep(&self) -> Sleep {
+		sleep(self.remaining())
+	}
+}
+
+impl From<time::Instant> for Deadline {
+	fn from(instant: time::Instant) -> Self {
+		Deadline(instant)
+	}
+}
+
+impl core::future::IntoFuture for Deadline {
+	type Output = time::Instant;
+	type IntoFuture = Sleep;
+
+	fn into_future(self) -> Sleep {
+		self.sleep()
+	}
+}
+
+/// Polls `sleep` until `due`, arming it on demand. A sleep completing before `due`, armed against a rounded or adjusted clock, is replaced by a fresh one
+fn poll_deadline(sleep: &mut Option<Sleep>, due: time::Instant, cx: &mut task::Context<'_>) -> task::Poll<()> {
+	while let Some(remaining) = due.checked_duration_since(now()).filter(|r| !r.is_zero()) {
+		let armed = sleep.get_or_insert_with(|| self::sleep(remaining));
+		task::ready!(Pin::new(armed).poll(cx));
+		*sleep = None;
+	}
+
+	*sleep = None;
+	task::Poll::Ready(())
+}
+
+/// What an [`Interval`] does with ticks that were missed, because its consumer fell behind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissedTickBehavior {
+	/// Missed ticks fire back to back until the schedule has caught up
+	#[default]
+	Burst,
+	/// The schedule restarts from the late tick, later ticks stay one period apart
+	Delay,
+	/// Missed ticks are dropped, the next tick fires on the original schedule
+	Skip,
+}
+
+/// Creates an [`Interval`] ticking every `period`. The first tick completes immediately
+pub fn interval(period: time::Duration) -> Interval {
+	assert!(!period.is_zero(), "Interval period must be non-zero");
+	Interval { period, next: now(), sleep: None, behavior: MissedTickBehavior::default() }
+}
+
+/// Ticks at a fixed period, driven by the sleeping thread. Also a [`Stream`](futures_core::Stream) of tick instants
+pub struct Interval {
+	period: time::Duration,
+	next: time::Instant,
+	sleep: Option<Sleep>,
+	behavior: MissedTickBehavior,
+}
+
+impl Interval {
+	/// Completes at the next scheduled tick, returning the instant it was scheduled for
+	pub async fn tick(&mut self) -> time::Instant {
+		std::future::poll_fn(|cx| self.poll_tick(cx)).await
+	}
+
+	/// Polls for the next tick, see [`tick`](Interval::tick)
+	pub fn poll_tick(&mut self, cx: &mut task::Context<'_>) -> task::Poll<time::Instant> {
+		task::ready!(poll_deadline(&mut self.sleep, self.next, cx));
+		let tick = self.next;
+		let current = now();
+
+		self.next = match self.behavior {
+			MissedTickBehavior::Burst => tick + self.period,
+			MissedTickBehavior::Delay if current > tick => current + self.period,
+			MissedTickBehavior::Skip if current > tick => {
+				// in u128 nanoseconds, a long stall may miss more than u32::MAX periods
+				let period = self.period.as_nanos();
+				let offset = ((current - tick).as_nanos() / period + 1) * period;
+
+				u64::try_from(offset / 1_000_000_000).ok().and_then(|secs| tick.checked_add(time::Duration::new(secs, (offset % 1_000_000_000) as u32))).unwrap_or(current + self.period)
+			}
+			_ => tick + self.period,
+		};
+
+		task::Poll::Ready(tick)
+	}
+
+	/// The time between ticks
+	pub fn period(&self) -> time::Duration {
+		self.period
+	}
+
+	/// Restarts the schedule from now, the next tick is a full period away. E.g. to push back a keepalive on every message sent
+	pub fn reset(&mut self) {
+		self.reset_at(now() + self.period)
+	}
+
+	/// Restarts the schedule, ticking at `instant` and every period after it
+	pub fn reset_at(&mut self, instant: time::Instant) {
+		self.next = instant;
+		self.sleep = None;
+	}
+
+	/// How missed ticks are handled
+	pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+		self.behavior
+	}
+
+	/// Changes how missed ticks are handled, [`Burst`](MissedTickBehavior::Burst) by default
+	pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+		self.behavior = behavior;
+	}
+}
+
+impl futures_core::Stream for Interval {
+	type Item = time::Instant;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Option<Self::Item>> {
+		self.get_mut().poll_tick(cx).map(Some)
+	}
+}
+
+/// Token bucket, handing out up to `permits` per `per` and bursts of at most `permits`.
+///
+/// Permits are reserved in order of [`acquire`](RateLimiter::acquire) calls, a cancelled `acquire` still uses up its permit
+pub struct RateLimiter {
+	/// time between refills of a single permit
+	interval: time::Duration,
+	/// how far ahead of the refill schedule a burst may run
+	burst: time::Duration,
+	/// when the bucket is considered full again, given every permit handed out so far
+	full_at: cell::Cell<time::Instant>,
+}
+
+impl RateLimiter {
+	/// Creates a full bucket, refilling `permits` evenly over `per`
+	pub fn new(permits: u32, per: time::Duration) -> Self {
+		assert!(permits > 0, "RateLimiter needs at least one permit");
+
+		let interval = per / permits;
+		RateLimiter { interval, burst: interval * (permits - 1), full_at: cell::Cell::new(now()) }
+	}
+
+	/// Waits until a permit is available, and takes it
+	pub async fn acquire(&self) {
+		let current = now();
+		let start = self.full_at.get().max(current);
+		self.full_at.set(start + self.interval);
+
+		let ready_at = start.checked_sub(self.burst).unwrap_or(current);
+		while let Some(remaining) = ready_at.checked_duration_since(now()).filter(|r| !r.is_zero()) {
+			sleep(remaining).await;
+		}
+	}
+
+	/// Takes a permit if one is available right away
+	pub fn try_acquire(&self) -> bool {
+		let current = now();
+		let start = self.full_at.get().max(current);
+
+		match start.checked_sub(self.burst).is_none_or(|ready_at| ready_at <= current) {
+			true => {
+				self.full_at.set(start + self.interval);
+				true
+			}
+			false => false,
+		}
+	}
+}
+
+/// How [`retry`] spaces out attempts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+	initial: time::Duration,
+	factor: u32,
+	max_delay: time::Duration,
+	max_attempts: u32,
+	jitter: f64,
+}
+
+impl RetryPolicy {
+	/// Waits `delay` between every attempt
+	pub fn fixed(delay: time::Duration) -> Self {
+		RetryPolicy { initial: delay, factor: 1, max_delay: delay, max_attempts: u32::MAX, jitter: 0.0 }
+	}
+
+	/// Waits `initial` after the first failure, doubling the delay after each further one
+	pub fn exponential(initial: time::Duration) -> Self {
+		RetryPolicy { initial, factor: 2, max_delay: time::Duration::MAX, max_attempts: u32::MAX, jitter: 0.0 }
+	}
+
+	/// Caps the delay between attempts
+	pub fn max_delay(mut self, max: time::Duration) -> Self {
+		self.max_delay = max;
+		self
+	}
+
+	/// Gives up after `attempts` in total, including the first one. Unlimited by default
+	pub fn max_attempts(mut self, attempts: u32) -> Self {
+		self.max_attempts = attempts.max(1);
+		self
+	}
+
+	/// Shortens each delay by a random amount, up to `fraction` of it, so clients failing together don't retry together
+	pub fn jitter(mut self, fraction: f64) -> Self {
+		self.jitter = fraction.clamp(0.0, 1.0);
+		self
+	}
+
+	/// Delay before retrying, after `failures` failed attempts
+	fn delay(&self, failures: u32) -> time::Duration {
+		let scale = self.factor.checked_pow(failures - 1).unwrap_or(u32::MAX);
+		let delay = self.initial.checked_mul(scale).unwrap_or(time::Duration::MAX).min(self.max_delay);
+
+		if self.jitter == 0.0 {
+			return delay;
+		}
+
+		// randomly seeded hashers are a source of randomness, without a dependency
+		let random = std::hash::BuildHasher::hash_one(&std::hash::RandomState::new(), failures) as f64 / u64::MAX as f64;
+		delay.mul_f64(1.0 - self.jitter * random)
+	}
+}
+
+/// Runs `f` until it succeeds, or the policy's attempts run out, sleeping between attempts. Returns the last error on failure
+pub async fn retry<T, E, F: AsyncFnMut() -> Result<T, E>>(policy: RetryPolicy, mut f: F) -> Result<T, E> {
+	let mut failures = 0;
+
+	loop {
+		let error = match f().await {
+			Ok(res) => return Ok(res),
+			Err(e) => e,
+		};
+
+		failures += 1;
+		if failures >= policy.max_attempts {
+			return Err(error);
+		}
+
+		let retry_at = now() + policy.delay(failures);
+		while let Some(remaining) = retry_at.checked_duration_since(now()).filter(|r| !r.is_zero()) {
+			sleep(remaining).await;
+		}
+	}
+}
+
+/// Longest a [`WallSleep`] trusts the monotonic clock, before comparing against the system clock again
+const WALL_CLOCK_RECHECK: time::Duration = time::Duration::from_secs(1);
+
+/// Creates a [`WallSleep`] future, completing once the system clock reaches `deadline`
+pub fn sleep_until_wall(deadline: time::SystemTime) -> WallSleep {
+	WallSleep { deadline, sleep: None }
+}
+
+/// Sleeps until a point in wall-clock time, e.g. for "run at 03:00" scheduling.
+///
+/// Monotonic timers drift across clock adjustments and suspends, so the deadline is re-evaluated against [`SystemTime`](time::SystemTime) at least once a second
+pub struct WallSleep {
+	deadline: time::SystemTime,
+	sleep: Option<Sleep>,
+}
+
+impl WallSleep {
+	/// The wall-clock time this future completes at
+	pub fn deadline(&self) -> time::SystemTime {
+		self.deadline
+	}
+}
+
+impl Future for WallSleep {
+	type Output = time::SystemTime;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+		loop {
+			// a clock set backwards simply leaves more time remaining
+			let remaining = match self.deadline.duration_since(time::SystemTime::now()) {
+				Ok(remaining) if !remaining.is_zero() => remaining,
+				_ => return task::Poll::Ready(self.deadline),
+			};
+
+			let sleep = self.sleep.get_or_insert_with(|| sleep(remaining.min(WALL_CLOCK_RECHECK)));
+			task::ready!(Pin::new(sleep).poll(cx));
+			self.sleep = None;
+		}
+	}
+}
+
+/// Time based adapters for [`Stream`](futures_core::Stream)s, driven by the sleeping thread. Adapted streams must be [`Unpin`], pin them with [`Box::pin`] otherwise
+pub trait StreamExt: futures_core::Stream + Sized {
+	/// Spaces items at least `period` apart, delaying but never dropping them
+	fn throttle(self, period: time::Duration) -> Throttle<Self> {
+		Throttle { stream: self, period, next: None, sleep: None }
+	}
+
+	/// Only yields an item once the stream has been quiet for `quiet`, dropping any item superseded before then. The latest item is flushed when the stream ends
+	fn debounce(self, quiet: time::Duration) -> Debounce<Self> {
+		Debounce { stream: self, quiet, latest: None, due: now(), sleep: None, done: false }
+	}
+
+	/// Yields `Err(Elapsed)` whenever the stream goes `limit` without producing an item. The stream keeps going afterwards, with a fresh limit
+	fn timeout(self, limit: time::Duration) -> Timeout<Self> {
+		Timeout { stream: self, limit, due: None, sleep: None }
+	}
+}
+
+impl<S: futures_core::Stream> StreamExt for S {}
+
+/// Stream returned by [`StreamExt::throttle`]
+pub struct Throttle<S> {
+	stream: S,
+	period: time::Duration,
+	next: Option<time::Instant>,
+	sleep: Option<Sleep>,
+}
+
+impl<S: futures_core::Stream + Unpin> futures_core::Stream for Throttle<S> {
+	type Item = S::Item;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Option<Self::Item>> {
+		let this = self.get_mut();
+
+		if let Some(next) = this.next {
+			task::ready!(poll_deadline(&mut this.sleep, next, cx));
+			this.next = None;
 		}
+
+		let item = task::ready!(Pin::new(&mut this.stream).poll_next(cx));
+		this.next = item.is_some().then(|| now() + this.period);
+
+		task::Poll::Ready(item)
+	}
+}
+
+/// Stream returned by [`StreamExt::debounce`]
+pub struct Debounce<S: futures_core::Stream> {
+	stream: S,
+	quiet: time::Duration,
+	latest: Option<S::Item>,
+	due: time::Instant,
+	sleep: Option<Sleep>,
+	done: bool,
+}
+
+// items are never pinned
+impl<S: futures_core::Stream + Unpin> Unpin for Debounce<S> {}
+
+impl<S: futures_core::Stream + Unpin> futures_core::Stream for Debounce<S> {
+	type Item = S::Item;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Option<Self::Item>> {
+		let this = self.get_mut();
+
+		// newer items replace the latest one, and push its deadline back
+		while !this.done {
+			match Pin::new(&mut this.stream).poll_next(cx) {
+				task::Poll::Ready(Some(item)) => {
+					this.latest = Some(item);
+					this.due = now() + this.quiet;
+				}
+				task::Poll::Ready(None) => this.done = true,
+				task::Poll::Pending => break,
+			}
+		}
+
+		if this.done {
+			return task::Poll::Ready(this.latest.take());
+		}
+
+		match this.latest {
+			Some(_) => {
+				task::ready!(poll_deadline(&mut this.sleep, this.due, cx));
+				task::Poll::Ready(this.latest.take())
+			}
+			None => task::Poll::Pending,
+		}
+	}
+}
+
+/// Error yielded by [`Timeout`] when its stream stalls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed(());
+
+impl std::fmt::Display for Elapsed {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Stream produced no item before the timeout elapsed")
+	}
+}
+
+impl std::error::Error for Elapsed {}
+
+/// Stream returned by [`StreamExt::timeout`]
+pub struct Timeout<S> {
+	stream: S,
+	limit: time::Duration,
+	due: Option<time::Instant>,
+	sleep: Option<Sleep>,
+}
+
+impl<S: futures_core::Stream + Unpin> futures_core::Stream for Timeout<S> {
+	type Item = Result<S::Item, Elapsed>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Option<Self::Item>> {
+		let this = self.get_mut();
+
+		if let task::Poll::Ready(item) = Pin::new(&mut this.stream).poll_next(cx) {
+			this.due = None;
+			this.sleep = None;
+			return task::Poll::Ready(item.map(Ok));
+		}
+
+		// the limit starts counting from the first poll waiting for an item
+		let due = *this.due.get_or_insert_with(|| now() + this.limit);
+		task::ready!(poll_deadline(&mut this.sleep, due, cx));
+		this.due = None;
+
+		task::Poll::Ready(Some(Err(Elapsed(()))))
 	}
 }
@@ -0,0 +1,111 @@
+use super::{Sleep, now, poll_deadline};
+use std::{future::Future, pin::Pin, task, time};
+
+/// A future completing after a duration, like `futures_timer::Delay`
+pub struct Delay {
+	due: time::Instant,
+	sleep: Option<Sleep>,
+}
+
+impl Delay {
+	/// Completes `dur` after it's created
+	pub fn new(dur: time::Duration) -> Self {
+		Delay { due: now() + dur, sleep: None }
+	}
+
+	/// Restarts the delay, completing `dur` from now. Works on completed delays too
+	pub fn reset(&mut self, dur: time::Duration) {
+		self.due = now() + dur;
+		self.sleep = None;
+	}
+}
+
+impl Unpin for Delay {}
+
+impl Future for Delay {
+	type Output = ();
+
+	fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<()> {
+		let this = self.get_mut();
+		poll_deadline(&mut this.sleep, this.due, cx)
+	}
+}
+
+/// A timer firing once or periodically, like `async_io::Timer`. Also a [`Stream`](futures_core::Stream) of the instants it fired at
+pub struct Timer {
+	/// `None` for timers that never fire
+	due: Option<time::Instant>,
+	period: Option<time::Duration>,
+	sleep: Option<Sleep>,
+}
+
+impl Timer {
+	/// Fires once, `dur` from now
+	pub fn after(dur: time::Duration) -> Self {
+		Self::at(now() + dur)
+	}
+
+	/// Fires once, at `instant`
+	pub fn at(instant: time::Instant) -> Self {
+		Timer { due: Some(instant), period: None, sleep: None }
+	}
+
+	/// Fires every `period`, starting one period from now
+	pub fn interval(period: time::Duration) -> Self {
+		Self::interval_at(now() + period, period)
+	}
+
+	/// Fires every `period`, starting at `start`
+	pub fn interval_at(start: time::Instant, period: time::Duration) -> Self {
+		Timer { due: Some(start), period: Some(period), sleep: None }
+	}
+
+	/// Never fires
+	pub fn never() -> Self {
+		Timer { due: None, period: None, sleep: None }
+	}
+
+	/// Makes the timer fire once, `dur` from now
+	pub fn set_after(&mut self, dur: time::Duration) {
+		self.set_at(now() + dur)
+	}
+
+	/// Makes the timer fire once, at `instant`
+	pub fn set_at(&mut self, instant: time::Instant) {
+		*self = Self::at(instant)
+	}
+
+	/// Makes the timer fire every `period`, starting one period from now
+	pub fn set_interval(&mut self, period: time::Duration) {
+		*self = Self::interval(period)
+	}
+
+	fn poll_fire(&mut self, cx: &mut task::Context<'_>) -> task::Poll<time::Instant> {
+		// never firing, or a one-shot timer already spent
+		let Some(due) = self.due else { return task::Poll::Pending };
+		task::ready!(poll_deadline(&mut self.sleep, due, cx));
+
+		self.due = self.period.map(|p| due + p);
+		task::Poll::Ready(due)
+	}
+}
+
+impl Unpin for Timer {}
+
+/// Resolves to the instant the timer fired at. Polling a one-shot timer again after it fired stays pending
+impl Future for Timer {
+	type Output = time::Instant;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<time::Instant> {
+		self.get_mut().poll_fire(cx)
+	}
+}
+
+/// Periodic timers yield every period. A one-shot timer yields once, then stays pending, like polling it as a future
+impl futures_core::Stream for Timer {
+	type Item = time::Instant;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Option<time::Instant>> {
+		self.get_mut().poll_fire(cx).map(Some)
+	}
+}